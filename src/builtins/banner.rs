@@ -0,0 +1,105 @@
+use crate::error::Error;
+use crate::run::Program;
+use crate::value::{Type, Value};
+
+builtins! {
+    "banner" "renders the string in big letters made of blocks, five lines high" {
+        [String] => _banner,
+    }
+}
+
+/// the glyphs of the banner font, `#` is drawn as a block; lowercase letters use the uppercase ones
+const FONT: &[(char, [&str; 5])] = &[
+    ('A', [" ### ", "#   #", "#####", "#   #", "#   #"]),
+    ('B', ["#### ", "#   #", "#### ", "#   #", "#### "]),
+    ('C', [" ####", "#    ", "#    ", "#    ", " ####"]),
+    ('D', ["#### ", "#   #", "#   #", "#   #", "#### "]),
+    ('E', ["#####", "#    ", "#### ", "#    ", "#####"]),
+    ('F', ["#####", "#    ", "#### ", "#    ", "#    "]),
+    ('G', [" ####", "#    ", "#  ##", "#   #", " ####"]),
+    ('H', ["#   #", "#   #", "#####", "#   #", "#   #"]),
+    ('I', ["#####", "  #  ", "  #  ", "  #  ", "#####"]),
+    ('J', ["#####", "   # ", "   # ", "#  # ", " ##  "]),
+    ('K', ["#   #", "#  # ", "###  ", "#  # ", "#   #"]),
+    ('L', ["#    ", "#    ", "#    ", "#    ", "#####"]),
+    ('M', ["#   #", "## ##", "# # #", "#   #", "#   #"]),
+    ('N', ["#   #", "##  #", "# # #", "#  ##", "#   #"]),
+    ('O', [" ### ", "#   #", "#   #", "#   #", " ### "]),
+    ('P', ["#### ", "#   #", "#### ", "#    ", "#    "]),
+    ('Q', [" ### ", "#   #", "# # #", "#  # ", " ## #"]),
+    ('R', ["#### ", "#   #", "#### ", "#  # ", "#   #"]),
+    ('S', [" ####", "#    ", " ### ", "    #", "#### "]),
+    ('T', ["#####", "  #  ", "  #  ", "  #  ", "  #  "]),
+    ('U', ["#   #", "#   #", "#   #", "#   #", " ### "]),
+    ('V', ["#   #", "#   #", "#   #", " # # ", "  #  "]),
+    ('W', ["#   #", "#   #", "# # #", "## ##", "#   #"]),
+    ('X', ["#   #", " # # ", "  #  ", " # # ", "#   #"]),
+    ('Y', ["#   #", " # # ", "  #  ", "  #  ", "  #  "]),
+    ('Z', ["#####", "   # ", "  #  ", " #   ", "#####"]),
+    ('0', [" ### ", "#  ##", "# # #", "##  #", " ### "]),
+    ('1', ["  #  ", " ##  ", "  #  ", "  #  ", " ### "]),
+    ('2', [" ### ", "#   #", "  ## ", " #   ", "#####"]),
+    ('3', ["#### ", "    #", " ### ", "    #", "#### "]),
+    ('4', ["#   #", "#   #", "#####", "    #", "    #"]),
+    ('5', ["#####", "#    ", "#### ", "    #", "#### "]),
+    ('6', [" ### ", "#    ", "#### ", "#   #", " ### "]),
+    ('7', ["#####", "    #", "   # ", "  #  ", "  #  "]),
+    ('8', [" ### ", "#   #", " ### ", "#   #", " ### "]),
+    ('9', [" ### ", "#   #", " ####", "    #", " ### "]),
+    (' ', ["   ", "   ", "   ", "   ", "   "]),
+    ('!', ["#", "#", "#", " ", "#"]),
+    ('?', [" ### ", "#   #", "  ## ", "     ", "  #  "]),
+    ('.', [" ", " ", " ", " ", "#"]),
+    (',', ["  ", "  ", "  ", " #", "# "]),
+    (':', [" ", "#", " ", "#", " "]),
+    ('\'', ["#", "#", " ", " ", " "]),
+    ('-', ["    ", "    ", "####", "    ", "    "]),
+];
+
+/// the glyph of a char, chars the font doesn't have are drawn as `?`
+fn glyph(char: char) -> &'static [&'static str; 5] {
+    let find = |char: char| FONT.iter().find(|(c, _)| *c == char).map(|(_, glyph)| glyph);
+    find(char.to_ascii_uppercase()).or_else(|| find('?')).unwrap()
+}
+/// renders every line of `text` as five lines of glyphs with a column of space between them
+fn render(text: &str) -> String {
+    let mut lines = vec![];
+    for line in text.lines() {
+        let glyphs: Vec<&[&str; 5]> = line.chars().map(glyph).collect();
+        for row in 0..5 {
+            let row: Vec<&str> = glyphs.iter().map(|glyph| glyph[row]).collect();
+            lines.push(row.join(" ").replace('#', "█").trim_end().to_string());
+        }
+    }
+    lines.join("\n")
+}
+
+fn _banner(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        program.stack.push(Value::String(render(&string)));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use crate::run::tests::stack;
+    use crate::value::Value;
+
+    #[test]
+    fn renders_glyphs() {
+        assert_eq!(render("Hi"), "█   █ █████\n█   █   █\n█████   █\n█   █   █\n█   █ █████");
+        assert_eq!(render("abc"), render("ABC"));
+        assert_eq!(render(""), "");
+    }
+
+    #[test]
+    fn unknown_chars_and_lines() {
+        assert_eq!(render("ü"), render("?"));
+        assert_eq!(render("a\nb").lines().count(), 10);
+        assert_eq!(stack("\"!\" banner").unwrap(), vec![Value::String("█\n█\n█\n\n█".to_string())]);
+    }
+}
@@ -0,0 +1,1201 @@
+use crate::error;
+use crate::error::Error;
+use crate::error_no_pos;
+use crate::builtins;
+use crate::parser::{Node, NodeType};
+use crate::run::{Checkpoint, Program, Stack};
+use crate::value::{Type, Value};
+
+builtins! {
+    "LEN" "pushes the number of values on the stack" {
+        [] => _stack_len,
+    }
+    "builtins" "pushes the names of all builtin macros and their count" {
+        [] => _builtins,
+    }
+    "macros" "pushes the names of all defined macros and aliases and their count" {
+        [] => _macros,
+    }
+    "vars" "pushes the names of all variables and their count" {
+        [] => _vars,
+    }
+    "help" "pushes the doc and the overloads of the macro with the given name" {
+        [String] => _help,
+    }
+    "len" "pushes the number of chars in the string, which indices count too" {
+        [String] => _len,
+    }
+    "byte-len" "pushes the number of bytes the string takes in UTF-8" {
+        [String] => _byte_len,
+    }
+    "drop" "removes the top value" {
+        [Any] => _drop,
+    }
+    "copy" "duplicates the top value" {
+        [Any] => _copy,
+    }
+    "swap" "swaps the top two values" {
+        [Any Any] => _swap,
+    }
+    "over" "copies the second value onto the top" {
+        [Any Any] => _over,
+    }
+    "checkpoint" "saves the stack, rollback restores it" {
+        [] => _checkpoint,
+    }
+    "checkpoint-vars" "saves the stack and the variables, rollback restores both" {
+        [] => _checkpoint_vars,
+    }
+    "rollback" "restores what the latest checkpoint saved and removes the checkpoint" {
+        [] => _rollback,
+    }
+    "commit" "removes the latest checkpoint, keeping the stack and variables as they are" {
+        [] => _commit,
+    }
+    "fail" "raises the string as an error at the place fail is called" {
+        [String] => _fail,
+    }
+    "assert" "raises an error if the boolean is false" {
+        [Boolean] => _assert,
+    }
+    "assert-eq" "raises an error showing both values if they aren't equal" {
+        [Any Any] => _assert_eq,
+    }
+    "explain-stack" "prints every value with its type and the position it was pushed at, which needs --trace-origins" {
+        [] => _explain_stack,
+    }
+    "call" "runs the block on top of the stack" {
+        [Block] => _call,
+    }
+    "apply" "runs the macro reference on top of the stack, like call does for any block" {
+        [Block] => _call,
+    }
+    "curry" "puts the value in front of the block's code, so it's pushed first when the block runs" {
+        [Any Block] => _curry,
+    }
+    "dip" "runs the block with the value below it taken off the stack, then pushes that value back" {
+        [Any Block] => _dip,
+    }
+    "keep" "runs the block on the value below it, then pushes that value again" {
+        [Any Block] => _keep,
+    }
+    "map" "runs the block on each char of the string and joins the chars or strings it leaves" {
+        [String Block] => _map,
+    }
+    "filter" "keeps the chars of the string for which the block leaves true" {
+        [String Block] => _filter,
+    }
+    "fold" "runs the block with the accumulator and each char of the string, leaving the final accumulator" {
+        [String Any Block] => _fold,
+    }
+    "+" "adds two numbers, or appends a string or char to a string" {
+        [Int Int] => _add,
+        [Float Float] => _add,
+        [Int Float] => _add,
+        [Float Int] => _add,
+        [String String] => _add,
+        [String Char] => _add,
+    }
+    "*" "multiplies two numbers, or repeats a string or char" {
+        [Int Int] => _mult,
+        [Float Float] => _mult,
+        [Int Float] => _mult,
+        [Float Int] => _mult,
+        [String Int] => _mult,
+        [Char Int] => _mult,
+    }
+    "repeat-join" "repeats a string with the separator between the copies" {
+        [String Int String] => _repeat_join,
+    }
+    "and" "logical and of two booleans" {
+        [Boolean Boolean] => _and,
+    }
+    "or" "logical or of two booleans" {
+        [Boolean Boolean] => _or,
+    }
+    "not" "negates a boolean" {
+        [Boolean] => _not,
+    }
+    "=" "pushes whether the top two values are equal" {
+        [Any Any] => _eq,
+    }
+    "!=" "pushes whether the top two values differ" {
+        [Any Any] => _ne,
+    }
+    "." "the char at an index, or the slice between two indices with an optional step" {
+        [String Int] => _index,
+        [String Int Int] => _index_range,
+        [String Int Int Int] => _index_step,
+    }
+    "rev" "reverses the string" {
+        [String] => _rev,
+    }
+    "pos" "pushes the index of a char or substring and true, or only false if it isn't found" {
+        [String String] => _pos,
+        [String Char] => _pos,
+    }
+    "remove" "removes the char at an index and pushes it" {
+        [String Int] => _remove,
+    }
+    "replace-range" "replaces the chars between two indices with a string" {
+        [String Int Int String] => _replace_range,
+    }
+    "strip-prefix" "removes the prefix if the string starts with it, pushing the string and whether it did" {
+        [String String] => _strip_prefix,
+        [String Char] => _strip_prefix,
+    }
+    "strip-suffix" "removes the suffix if the string ends with it, pushing the string and whether it did" {
+        [String String] => _strip_suffix,
+        [String Char] => _strip_suffix,
+    }
+    "count" "counts the non-overlapping occurrences of a char or substring" {
+        [String Char] => _count,
+        [String String] => _count,
+    }
+    "count-overlapping" "counts the occurrences of a char or substring, including overlapping ones" {
+        [String Char] => _count_overlapping,
+        [String String] => _count_overlapping,
+    }
+    "split" "splits the string at a separator, pushing the parts and their count" {
+        [String Char] => _split,
+        [String String] => _split,
+    }
+    "splitn" "splits the string into at most n parts, pushing the parts and their count" {
+        [String Char Int] => _splitn,
+        [String String Int] => _splitn,
+    }
+    "chunks" "splits the string into pieces of n chars, the last one may be shorter, pushing the pieces and their count" {
+        [String Int] => _chunks,
+    }
+    "zip" "interleaves the chars of two strings, stopping at the end of the shorter one or padding it with the given char" {
+        [String String] => _zip,
+        [String String Char] => _zip,
+    }
+    "cut" "pushes the field with the given number, counting from 1 like Unix cut, or the fields in a range joined by the delimiter" {
+        [String Char Int] => _cut,
+        [String String Int] => _cut,
+        [String Char Int Int] => _cut,
+        [String String Int Int] => _cut,
+    }
+    "chars" "pushes every char of the string and then their count" {
+        [String] => _chars,
+    }
+    "join" "joins the whole stack into one string with a separator" {
+        [Char] => _join,
+        [String] => _join,
+    }
+    "join-n" "joins the top n values into one string with a separator" {
+        [Int Char] => _join_n,
+        [Int String] => _join_n,
+    }
+}
+
+fn _stack_len(program: &mut Program) -> Result<(), Error> {
+    program.stack.push(Value::Int(program.stack.len() as i64));
+    Ok(())
+}
+fn _builtins(program: &mut Program) -> Result<(), Error> {
+    let mut names: Vec<&str> = vec![];
+    // groups like decimal add overloads to names other groups already have
+    for builtin in builtins::all(&program.features) {
+        if !names.contains(&builtin.name) {
+            names.push(builtin.name);
+        }
+    }
+    for name in names.iter() {
+        program.stack.push(Value::String(name.to_string()));
+    }
+    program.stack.push(Value::Int(names.len() as i64));
+    Ok(())
+}
+fn push_names(program: &mut Program, mut names: Vec<String>) {
+    names.sort();
+    let len = names.len();
+    for name in names {
+        program.stack.push(Value::String(name));
+    }
+    program.stack.push(Value::Int(len as i64));
+}
+fn _macros(program: &mut Program) -> Result<(), Error> {
+    let names = program.macros.keys().chain(program.aliases.keys()).cloned().collect();
+    push_names(program, names);
+    Ok(())
+}
+fn _vars(program: &mut Program) -> Result<(), Error> {
+    let names = program.vars.keys().cloned().collect();
+    push_names(program, names);
+    Ok(())
+}
+fn _help(program: &mut Program) -> Result<(), Error> {
+    let Value::String(name) = program.stack.pop().unwrap() else {
+        panic!("type checking error!!!")
+    };
+    let Some(macros) = program.get_macro(&name) else {
+        return error_no_pos!("no macro named {name:?}")
+    };
+    let mut help = macros.doc.clone().map(|doc| doc + "\n").unwrap_or_default();
+    help.push_str(macros.display(&name).trim_end());
+    program.stack.push(Value::String(help));
+    Ok(())
+}
+fn _len(program: &mut Program) -> Result<(), Error> {
+    let a = program.stack.pop().unwrap();
+    match a {
+        Value::String(string) => program.stack.push(Value::Int(string.chars().count() as i64)),
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _byte_len(program: &mut Program) -> Result<(), Error> {
+    let a = program.stack.pop().unwrap();
+    match a {
+        Value::String(string) => program.stack.push(Value::Int(string.len() as i64)),
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _chars(program: &mut Program) -> Result<(), Error> {
+    let Value::String(string) = program.stack.pop().unwrap() else { panic!("type checking error!!!") };
+    let mut len = 0;
+    for char in string.chars() {
+        program.stack.push(Value::Char(char));
+        len += 1;
+    }
+    program.stack.push(Value::Int(len));
+    Ok(())
+}
+fn _drop(program: &mut Program) -> Result<(), Error> {
+    program.stack.pop();
+    Ok(())
+}
+fn _copy(program: &mut Program) -> Result<(), Error> {
+    let a = program.stack.peek().unwrap();
+    program.stack.push(a.clone());
+    Ok(())
+}
+fn _swap(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    program.stack.push(b);
+    program.stack.push(a);
+    Ok(())
+}
+fn _checkpoint(program: &mut Program) -> Result<(), Error> {
+    let stack = program.stack.clone();
+    program.checkpoints.push(Checkpoint { stack, vars: None });
+    Ok(())
+}
+fn _checkpoint_vars(program: &mut Program) -> Result<(), Error> {
+    let (stack, vars) = (program.stack.clone(), program.vars.clone());
+    program.checkpoints.push(Checkpoint { stack, vars: Some(vars) });
+    Ok(())
+}
+fn _rollback(program: &mut Program) -> Result<(), Error> {
+    let Some(checkpoint) = program.checkpoints.pop() else {
+        return error_no_pos!("there is no checkpoint to roll back to")
+    };
+    program.stack = checkpoint.stack;
+    if let Some(vars) = checkpoint.vars {
+        program.vars = vars;
+    }
+    Ok(())
+}
+fn _commit(program: &mut Program) -> Result<(), Error> {
+    if program.checkpoints.pop().is_none() {
+        return error_no_pos!("there is no checkpoint to commit")
+    }
+    Ok(())
+}
+fn _fail(program: &mut Program) -> Result<(), Error> {
+    let Value::String(msg) = program.stack.pop().unwrap() else { panic!("type checking error!!!") };
+    error_no_pos!("{msg}")
+}
+fn _assert(program: &mut Program) -> Result<(), Error> {
+    let Value::Boolean(ok) = program.stack.pop().unwrap() else { panic!("type checking error!!!") };
+    if !ok {
+        return error_no_pos!("assertion failed")
+    }
+    Ok(())
+}
+fn _assert_eq(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if a != b {
+        return error_no_pos!("assertion failed, {a:?}:{} isn't equal to {b:?}:{}", a.typ(), b.typ())
+    }
+    Ok(())
+}
+/// the lines explain-stack prints, the top value first
+fn explain(stack: &Stack) -> Vec<String> {
+    if stack.len() == 0 {
+        return vec![String::from("<empty>")]
+    }
+    stack.with_origins().enumerate().rev().map(|(idx, (value, origin))| {
+        let origin = match origin {
+            Some(pos) => format!("pushed at {pos}"),
+            None if stack.tracing() => String::from("pushed before tracing started"),
+            None => String::from("unknown origin, run with --trace-origins")
+        };
+        format!("{idx}: {value:?}:{} {origin}", value.typ())
+    }).collect()
+}
+fn _explain_stack(program: &mut Program) -> Result<(), Error> {
+    for line in explain(&program.stack) {
+        eprintln!("{line}");
+    }
+    Ok(())
+}
+fn _call(program: &mut Program) -> Result<(), Error> {
+    if let Value::Block(body) = program.stack.pop().unwrap() {
+        program.run(*body)
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _curry(program: &mut Program) -> Result<(), Error> {
+    let Value::Block(body) = program.stack.pop().unwrap() else { panic!("type checking error!!!") };
+    let literal = match program.stack.pop().unwrap() {
+        Value::String(string) => NodeType::String(string),
+        Value::Char(char) => NodeType::Char(char),
+        Value::Int(int) => NodeType::Int(int),
+        Value::Float(float) => NodeType::Float(float),
+        Value::Boolean(boolean) => NodeType::Boolean(boolean),
+        Value::Block(block) => NodeType::Block(block),
+        #[cfg(feature = "decimal")]
+        Value::Decimal(_) => return error_no_pos!("cannot curry a dec value, curry the string and use dec in the block")
+    };
+    let pos = body.pos.clone();
+    let mut nodes = vec![Node::new(literal, pos.clone())];
+    // an empty body would print as a trailing space
+    if !matches!(&body.node, NodeType::Chunk(body) if body.is_empty()) {
+        nodes.push(*body);
+    }
+    let curried = Node::new(NodeType::Chunk(nodes), pos);
+    program.stack.push(Value::Block(Box::new(curried)));
+    Ok(())
+}
+fn _dip(program: &mut Program) -> Result<(), Error> {
+    let Value::Block(body) = program.stack.pop().unwrap() else { panic!("type checking error!!!") };
+    let value = program.stack.pop().unwrap();
+    program.run(*body)?;
+    program.stack.push(value);
+    Ok(())
+}
+fn _keep(program: &mut Program) -> Result<(), Error> {
+    let Value::Block(body) = program.stack.pop().unwrap() else { panic!("type checking error!!!") };
+    let value = program.stack.peek().unwrap().clone();
+    program.run(*body)?;
+    program.stack.push(value);
+    Ok(())
+}
+/// pops the block and the string below it
+fn pop_block(program: &mut Program) -> (Node, String) {
+    match (program.stack.pop().unwrap(), program.stack.pop().unwrap()) {
+        (Value::Block(body), Value::String(string)) => (*body, string),
+        _ => panic!("type checking error!!!")
+    }
+}
+/// runs `body` with `args` pushed and pops what it leaves on top
+fn apply(program: &mut Program, body: &Node, args: Vec<Value>) -> Result<Value, Error> {
+    for arg in args {
+        program.stack.push(arg);
+    }
+    program.run(body.clone())?;
+    match program.stack.pop() {
+        Some(value) => Ok(value),
+        None => error_no_pos!("the block left no value on the stack")
+    }
+}
+fn _map(program: &mut Program) -> Result<(), Error> {
+    let (body, string) = pop_block(program);
+    let mut mapped = String::new();
+    for char in string.chars() {
+        match apply(program, &body, vec![Value::Char(char)])? {
+            Value::Char(char) => mapped.push(char),
+            Value::String(string) => mapped.push_str(&string),
+            value => return error_no_pos!("expected the block to leave a char or string, got {}", value.typ())
+        }
+    }
+    program.stack.push(Value::String(mapped));
+    Ok(())
+}
+fn _filter(program: &mut Program) -> Result<(), Error> {
+    let (body, string) = pop_block(program);
+    let mut filtered = String::new();
+    for char in string.chars() {
+        match apply(program, &body, vec![Value::Char(char)])? {
+            Value::Boolean(true) => filtered.push(char),
+            Value::Boolean(false) => {}
+            value => return error_no_pos!("expected the block to leave a boolean, got {}", value.typ())
+        }
+    }
+    program.stack.push(Value::String(filtered));
+    Ok(())
+}
+fn _fold(program: &mut Program) -> Result<(), Error> {
+    let Value::Block(body) = program.stack.pop().unwrap() else { panic!("type checking error!!!") };
+    let mut acc = program.stack.pop().unwrap();
+    let Value::String(string) = program.stack.pop().unwrap() else { panic!("type checking error!!!") };
+    for char in string.chars() {
+        acc = apply(program, &body, vec![acc, Value::Char(char)])?;
+    }
+    program.stack.push(acc);
+    Ok(())
+}
+fn _over(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    let c = a.clone();
+    program.stack.push(a);
+    program.stack.push(b);
+    program.stack.push(c);
+    Ok(())
+}
+fn _add(program: &mut Program) -> Result<(), Error> {
+    let (mut b, mut a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a.clone(), b) {
+        (Value::Int(v1), Value::Int(v2)) => match v1.checked_add(v2) {
+            Some(sum) => program.stack.push(Value::Int(sum)),
+            None => return error_no_pos!("{v1} + {v2} overflows an int")
+        }
+        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Float(v1 + v2)),
+        (Value::Int(int), Value::Float(float)) |
+        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Float(int as f64 + float)),
+        (Value::String(v1), Value::String(v2)) => program.stack.push(Value::String(v1 + &v2)),
+        (Value::String(mut v1), Value::Char(v2)) => {
+            v1.push(v2);
+            program.stack.push(Value::String(v1));
+        }
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _mult(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a, b) {
+        (Value::Int(v1), Value::Int(v2)) => match v1.checked_mul(v2) {
+            Some(product) => program.stack.push(Value::Int(product)),
+            None => return error_no_pos!("{v1} * {v2} overflows an int")
+        }
+        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Float(v1 * v2)),
+        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Float(int as f64 * float)),
+        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Float(float * int as f64)),
+        (Value::String(s), Value::Int(rep)) => {
+            builtins::check_len(rep.max(0) as usize, s.len())?;
+            program.stack.push(Value::String(s.repeat(rep.max(0) as usize)))
+        }
+        (Value::Char(c), Value::Int(rep)) => {
+            builtins::check_len(rep.max(0) as usize, c.len_utf8())?;
+            program.stack.push(Value::String(c.to_string().repeat(rep.max(0) as usize)))
+        }
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _repeat_join(program: &mut Program) -> Result<(), Error> {
+    let (c, b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a, b, c) {
+        (Value::String(string), Value::Int(rep), Value::String(sep)) => {
+            builtins::check_len(rep.max(0) as usize, string.len() + sep.len())?;
+            program.stack.push(Value::String(vec![string; rep.max(0) as usize].join(&sep)))
+        }
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _and(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a, b) {
+        (Value::Boolean(v1), Value::Boolean(v2)) => program.stack.push(Value::Boolean(v1 && v2)),
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _or(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a, b) {
+        (Value::Boolean(v1), Value::Boolean(v2)) => program.stack.push(Value::Boolean(v1 || v2)),
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _not(program: &mut Program) -> Result<(), Error> {
+    let a = program.stack.pop().unwrap();
+    match a {
+        Value::Boolean(v) => program.stack.push(Value::Boolean(!v)),
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _eq(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    program.stack.push(Value::Boolean(a == b));
+    Ok(())
+}
+fn _ne(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    program.stack.push(Value::Boolean(a != b));
+    Ok(())
+}
+/// maps `idx` into `0..len`, negative indices count from the end
+///
+/// out-of-range indices wrap around, unless the program is in strict index mode
+fn wrap_index(program: &Program, idx: i64, len: usize) -> Result<usize, Error> {
+    if len == 0 {
+        return error_no_pos!("cannot index into an empty string")
+    }
+    if program.strict_index && (idx >= len as i64 || idx < -(len as i64)) {
+        return error_no_pos!("index {idx} out of range for length {len}")
+    }
+    Ok(idx.rem_euclid(len as i64) as usize)
+}
+/// like `wrap_index`, but `len` itself is allowed as the exclusive end of a range
+fn wrap_range_end(program: &Program, end: i64, len: usize) -> Result<usize, Error> {
+    if end == len as i64 { Ok(len) } else { wrap_index(program, end, len) }
+}
+fn _index(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(string), Value::Int(idx)) = (a, b) {
+        let idx = wrap_index(program, idx, string.chars().count())?;
+        program.stack.push(Value::Char(string.chars().nth(idx).unwrap()));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _index_range(program: &mut Program) -> Result<(), Error> {
+    let (c, b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(string), Value::Int(start), Value::Int(end)) = (a, b, c) {
+        let len = string.chars().count();
+        let (start, end) = if len == 0 && start == 0 && end == 0 {
+            (0, 0)
+        } else {
+            (wrap_index(program, start, len)?, wrap_range_end(program, end, len)?)
+        };
+        if start > end {
+            return error_no_pos!("range start {start} is after its end {end}")
+        }
+        program.stack.push(Value::String(string.chars().skip(start).take(end - start).collect()));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+/// every `step`th char from `start` towards `end` (exclusive), a negative step walks backwards and
+/// then takes an `end` before `-len` as the position before the first char
+fn _index_step(program: &mut Program) -> Result<(), Error> {
+    let (d, c, b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap(), program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(string), Value::Int(start), Value::Int(end), Value::Int(step)) = (a, b, c, d) {
+        if step == 0 {
+            return error_no_pos!("slice step cannot be zero")
+        }
+        let chars: Vec<char> = string.chars().collect();
+        let start = wrap_index(program, start, chars.len())? as i64;
+        let end = if step < 0 && end < -(chars.len() as i64) {
+            -1
+        } else {
+            wrap_range_end(program, end, chars.len())? as i64
+        };
+        let mut slice = String::new();
+        let mut idx = Some(start);
+        while let Some(at) = idx.filter(|at| (step > 0 && *at < end) || (step < 0 && *at > end)) {
+            slice.push(chars[at as usize]);
+            idx = at.checked_add(step);
+        }
+        program.stack.push(Value::String(slice));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _rev(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        program.stack.push(Value::String(string.chars().rev().collect()));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _pos(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a, b) {
+        (Value::String(string), Value::Char(char)) => {
+            match string.find(char) {
+                Some(index) => {
+                    program.stack.push(Value::Int(string[..index].chars().count() as i64));
+                    program.stack.push(Value::Boolean(true));
+                }
+                None => program.stack.push(Value::Boolean(false))
+            }
+        }
+        (Value::String(string), Value::String(sub)) => {
+            match string.find(&sub) {
+                Some(index) => {
+                    program.stack.push(Value::Int(string[..index].chars().count() as i64));
+                    program.stack.push(Value::Boolean(true));
+                }
+                None => program.stack.push(Value::Boolean(false))
+            }
+        }
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _remove(program: &mut Program) -> Result<(), Error> {
+    let (b, mut a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a, b) {
+        (Value::String(mut string), Value::Int(idx)) => {
+            let idx = wrap_index(program, idx, string.chars().count())?;
+            let (byte_idx, _) = string.char_indices().nth(idx).unwrap();
+            program.stack.push(Value::Char(string.remove(byte_idx)));
+        }
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _replace_range(program: &mut Program) -> Result<(), Error> {
+    let (d, c, b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap(), program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(string), Value::Int(start), Value::Int(end), Value::String(replacement)) = (a, b, c, d) {
+        let len = string.chars().count();
+        let (start, end) = if len == 0 && start == 0 && end == 0 {
+            (0, 0)
+        } else {
+            (wrap_index(program, start, len)?, wrap_range_end(program, end, len)?)
+        };
+        if start > end {
+            return error_no_pos!("range start {start} is after its end {end}")
+        }
+        let mut spliced: String = string.chars().take(start).collect();
+        spliced.push_str(replacement.as_str());
+        spliced.extend(string.chars().skip(end));
+        program.stack.push(Value::String(spliced));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _count(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a, b) {
+        (Value::String(string), Value::Char(count_char)) => {
+            let mut count: usize = 0;
+            for char in string.chars() {
+                if char == count_char {
+                    count += 1;
+                }
+            }
+            program.stack.push(Value::Int(count as i64));
+        }
+        (Value::String(string), Value::String(count_string)) => {
+            program.stack.push(Value::Int(string.matches(count_string.as_str()).count() as i64));
+        }
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _count_overlapping(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    let (string, sub) = match (a, b) {
+        (Value::String(string), Value::Char(char)) => (string, char.to_string()),
+        (Value::String(string), Value::String(sub)) => (string, sub),
+        _ => panic!("type checking error!!!")
+    };
+    if sub.is_empty() {
+        program.stack.push(Value::Int(string.chars().count() as i64 + 1));
+        return Ok(())
+    }
+    let mut count: usize = 0;
+    let mut start = 0;
+    while let Some(idx) = string[start..].find(sub.as_str()) {
+        count += 1;
+        start += idx + string[start + idx..].chars().next().map_or(1, char::len_utf8);
+    }
+    program.stack.push(Value::Int(count as i64));
+    Ok(())
+}
+fn _split(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a, b) {
+        (Value::String(string), Value::Char(pattern)) => {
+            let mut parts: Vec<&str> = string.split(pattern).collect();
+            let len = parts.len();
+            for part in parts {
+                program.stack.push(Value::String(part.to_string()));
+            }
+            program.stack.push(Value::Int(len as i64));
+        }
+        (Value::String(string), Value::String(pattern)) => {
+            let mut parts: Vec<&str> = string.split(pattern.as_str()).collect();
+            let len = parts.len();
+            for part in parts {
+                program.stack.push(Value::String(part.to_string()));
+            }
+            program.stack.push(Value::Int(len as i64));
+        }
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _splitn(program: &mut Program) -> Result<(), Error> {
+    let (c, b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    let parts: Vec<String> = match (a, b, c) {
+        (Value::String(string), Value::Char(pattern), Value::Int(max)) => {
+            string.splitn(max.max(1) as usize, pattern).map(|part| part.to_string()).collect()
+        }
+        (Value::String(string), Value::String(pattern), Value::Int(max)) => {
+            string.splitn(max.max(1) as usize, pattern.as_str()).map(|part| part.to_string()).collect()
+        }
+        _ => panic!("type checking error!!!")
+    };
+    let len = parts.len();
+    for part in parts {
+        program.stack.push(Value::String(part));
+    }
+    program.stack.push(Value::Int(len as i64));
+    Ok(())
+}
+/// pops the string and the char or string affix of `strip-prefix` and `strip-suffix`
+fn pop_affix(program: &mut Program) -> (String, String) {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a, b) {
+        (Value::String(string), Value::String(affix)) => (string, affix),
+        (Value::String(string), Value::Char(affix)) => (string, affix.to_string()),
+        _ => panic!("type checking error!!!")
+    }
+}
+fn _strip_prefix(program: &mut Program) -> Result<(), Error> {
+    let (string, prefix) = pop_affix(program);
+    let stripped = string.strip_prefix(prefix.as_str()).map(str::to_string);
+    let found = stripped.is_some();
+    program.stack.push(Value::String(stripped.unwrap_or(string)));
+    program.stack.push(Value::Boolean(found));
+    Ok(())
+}
+fn _strip_suffix(program: &mut Program) -> Result<(), Error> {
+    let (string, suffix) = pop_affix(program);
+    let stripped = string.strip_suffix(suffix.as_str()).map(str::to_string);
+    let found = stripped.is_some();
+    program.stack.push(Value::String(stripped.unwrap_or(string)));
+    program.stack.push(Value::Boolean(found));
+    Ok(())
+}
+fn _chunks(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(string), Value::Int(size)) = (a, b) {
+        if size < 1 {
+            return error_no_pos!("chunk size has to be at least 1, got {size}")
+        }
+        let chars: Vec<char> = string.chars().collect();
+        let len = chars.chunks(size as usize).len();
+        for chunk in chars.chunks(size as usize) {
+            program.stack.push(Value::String(chunk.iter().collect()));
+        }
+        program.stack.push(Value::Int(len as i64));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _zip(program: &mut Program) -> Result<(), Error> {
+    let pad = match program.stack.peek() {
+        Some(Value::Char(pad)) => {
+            let pad = *pad;
+            program.stack.pop();
+            Some(pad)
+        }
+        _ => None
+    };
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(a), Value::String(b)) = (a, b) {
+        let (mut a, mut b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+        if let Some(pad) = pad {
+            let len = a.len().max(b.len());
+            a.resize(len, pad);
+            b.resize(len, pad);
+        }
+        program.stack.push(Value::String(a.into_iter().zip(b).flat_map(|(x, y)| [x, y]).collect()));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _cut(program: &mut Program) -> Result<(), Error> {
+    let Value::Int(to) = program.stack.pop().unwrap() else { panic!("type checking error!!!") };
+    let from = match program.stack.peek() {
+        Some(Value::Int(from)) => {
+            let from = *from;
+            program.stack.pop();
+            from
+        }
+        _ => to
+    };
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    let (line, delimiter) = match (a, b) {
+        (Value::String(line), Value::Char(delimiter)) => (line, delimiter.to_string()),
+        (Value::String(line), Value::String(delimiter)) => (line, delimiter),
+        _ => panic!("type checking error!!!")
+    };
+    if from < 1 || to < 1 {
+        return error_no_pos!("fields are numbered from 1, got {}", from.min(to))
+    }
+    if delimiter.is_empty() {
+        return error_no_pos!("cannot cut with an empty delimiter")
+    }
+    let fields: Vec<&str> = line.split(delimiter.as_str()).collect();
+    let (from, to) = ((from as usize - 1).min(fields.len()), (to as usize).min(fields.len()));
+    program.stack.push(Value::String(fields[from..to.max(from)].join(delimiter.as_str())));
+    Ok(())
+}
+fn _join(program: &mut Program) -> Result<(), Error> {
+    let a = program.stack.pop().unwrap();
+    let len = program.stack.len();
+    let mut strings = vec![];
+    for _ in 0..len {
+        let Some(value) = program.stack.pop() else { break };
+        if let Value::String(value) = value {
+            strings.push(value);
+        } else {
+            strings.push(value.to_string())
+        }
+    }
+    let strings: Vec<String> = strings.iter().rev().cloned().collect();
+    match a {
+        Value::Char(char) => {
+            program.stack.push(Value::String(strings.join(char.to_string().as_str())));
+        }
+        Value::String(string) => {
+            program.stack.push(Value::String(strings.join(string.as_str())));
+        }
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _join_n(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    let (Value::Int(count), sep) = (a, b) else { panic!("type checking error!!!") };
+    let sep = match sep {
+        Value::Char(char) => char.to_string(),
+        Value::String(string) => string,
+        _ => panic!("type checking error!!!")
+    };
+    let count = count.max(0) as usize;
+    if count > program.stack.len() {
+        return error_no_pos!("cannot join {count} values, the stack only has {}", program.stack.len())
+    }
+    let mut strings = vec![];
+    for _ in 0..count {
+        strings.push(program.stack.pop().unwrap().to_string());
+    }
+    strings.reverse();
+    program.stack.push(Value::String(strings.join(sep.as_str())));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{explain, BUILTINS};
+    use crate::run::Program;
+    use crate::run::tests::{run_in, stack};
+    use crate::value::Value;
+
+    fn string(string: &str) -> Vec<Value> { vec![Value::String(string.to_string())] }
+
+    #[test]
+    fn repeat_join() {
+        assert_eq!(stack(r#""ab" 3 ", " repeat-join"#).unwrap(), string("ab, ab, ab"));
+        assert_eq!(stack(r#""ab" 1 ", " repeat-join"#).unwrap(), string("ab"));
+        assert_eq!(stack(r#""ab" 0 ", " repeat-join"#).unwrap(), string(""));
+        assert_eq!(stack(r#""ab" -2 ", " repeat-join"#).unwrap(), string(""));
+    }
+    #[test]
+    fn repeat_join_too_long() {
+        assert!(stack(r#""a" 99999999999999 ", " repeat-join"#).is_err());
+        assert!(stack(r#""a" 9223372036854775807 "" repeat-join"#).is_err());
+        assert!(stack(r#""a" 99999999999999 *"#).is_err());
+        assert!(stack("'a' 99999999999999 *").is_err());
+    }
+    #[test]
+    fn index_counts_chars() {
+        assert_eq!(stack(r#""héllo" 1 ."#).unwrap(), vec![Value::Char('é')]);
+        assert_eq!(stack(r#""日本語" -1 ."#).unwrap(), vec![Value::Char('語')]);
+        assert_eq!(stack(r#""añb" 3 ."#).unwrap(), vec![Value::Char('a')]);
+    }
+    #[test]
+    fn index_range_counts_chars() {
+        assert_eq!(stack(r#""héllo wörld" 1 4 ."#).unwrap(), string("éll"));
+        assert_eq!(stack(r#""héllo wörld" -5 -1 ."#).unwrap(), string("wörl"));
+        assert_eq!(stack(r#""日本語" 1 3 ."#).unwrap(), string("本語"));
+        assert_eq!(stack(r#""日本語" 2 2 ."#).unwrap(), string(""));
+    }
+    #[test]
+    fn index_range_of_empty_string() {
+        assert_eq!(stack(r#""" 0 0 ."#).unwrap(), string(""));
+        assert!(stack(r#""" 0 ."#).is_err());
+        assert!(stack(r#""" 0 1 ."#).is_err());
+    }
+    #[test]
+    fn index_step_reverses() {
+        assert_eq!(stack(r#""hello" -1 -6 -1 ."#).unwrap(), string("olleh"));
+        assert_eq!(stack(r#""hello" 4 -100 -1 ."#).unwrap(), string("olleh"));
+        assert_eq!(stack(r#""héllo" -1 -6 -2 ."#).unwrap(), string("olh"));
+    }
+    #[test]
+    fn index_step_huge() {
+        assert_eq!(stack(r#""hello" 1 5 9223372036854775807 ."#).unwrap(), string("e"));
+        assert_eq!(stack(r#""hello" 3 -6 -9223372036854775808 ."#).unwrap(), string("l"));
+        assert_eq!(stack(r#""hello" 0 5 3 ."#).unwrap(), string("hl"));
+    }
+    #[test]
+    fn index_step_reversed_range() {
+        assert_eq!(stack(r#""hello" 4 1 1 ."#).unwrap(), string(""));
+        assert_eq!(stack(r#""hello" 1 4 -1 ."#).unwrap(), string(""));
+        assert!(stack(r#""hello" 0 5 0 ."#).is_err());
+    }
+    #[test]
+    fn index_step_reverses_part() {
+        assert_eq!(stack(r#""hello" 4 0 -1 ."#).unwrap(), string("olle"));
+        assert_eq!(stack(r#""hello" 3 -5 -1 ."#).unwrap(), string("lle"));
+        assert_eq!(stack(r#""hello" 2 -6 -1 ."#).unwrap(), string("leh"));
+        assert_eq!(stack(r#""hello" 0 5 2 ."#).unwrap(), string("hlo"));
+    }
+    fn strict() -> Program {
+        let mut program = Program::std_program();
+        program.strict_index = true;
+        program
+    }
+    #[test]
+    fn index_wraps_around() {
+        assert_eq!(stack(r#""abc" 5 . "abc" -1 . "abc" 4 remove"#).unwrap(),
+            vec![Value::Char('c'), Value::Char('c'), Value::Char('b')]);
+    }
+    #[test]
+    fn strict_index_errors_out_of_range() {
+        let error = run_in(strict(), r#""abc" 3 ."#).err().unwrap();
+        assert!(error.msg().contains("out of range"), "{}", error.msg());
+        assert_eq!(error.pos().map(|pos| pos.col.clone()), Some(8..9));
+        // negative indices still count from the end, only ones before the first char are errors
+        assert_eq!(run_in(strict(), r#""abc" -1 ."#).unwrap().stack().as_slice(), &[Value::Char('c')]);
+        assert!(run_in(strict(), r#""abc" -4 ."#).is_err());
+        assert!(run_in(strict(), r#""abc" 3 remove"#).is_err());
+        assert!(run_in(strict(), r#""abc" 0 5 ."#).is_err());
+        assert_eq!(run_in(strict(), r#""abc" 2 ."#).unwrap().stack().as_slice(), &[Value::Char('c')]);
+    }
+    fn counted(strings: &[&str]) -> Vec<Value> {
+        let mut values: Vec<Value> = strings.iter().map(|string| Value::String(string.to_string())).collect();
+        values.push(Value::Int(strings.len() as i64));
+        values
+    }
+    #[test]
+    fn split_pushes_parts_and_count() {
+        assert_eq!(stack(r#""a,,b" "," split"#).unwrap(), counted(&["a", "", "b"]));
+        assert_eq!(stack(r#""" "," split"#).unwrap(), counted(&[""]));
+    }
+    #[test]
+    fn splitn_limits_the_parts() {
+        assert_eq!(stack(r#""a,b,c" "," 2 splitn"#).unwrap(), counted(&["a", "b,c"]));
+        assert_eq!(stack(r#""a,b,c" "," 5 splitn"#).unwrap(), counted(&["a", "b", "c"]));
+        // fewer than one part still gives the whole string
+        assert_eq!(stack(r#""a,b,c" "," 0 splitn"#).unwrap(), counted(&["a,b,c"]));
+    }
+    #[test]
+    fn join_n_joins_only_the_top_values() {
+        assert_eq!(stack(r#""a" "b" "c" 2 "-" join-n"#).unwrap(), vec![Value::String("a".to_string()), Value::String("b-c".to_string())]);
+        assert_eq!(stack(r#""a" 1 2 "-" join-n"#).unwrap(), string("a-1"));
+        assert_eq!(stack(r#""a" 0 "-" join-n"#).unwrap(), vec![Value::String("a".to_string()), Value::String(String::new())]);
+        assert_eq!(stack(r#""a" "b" 5 "-" join-n"#).err().unwrap().msg(), "cannot join 5 values, the stack only has 2");
+    }
+    #[test]
+    fn replace_range() {
+        assert_eq!(stack(r#""hello" 1 3 "EY" replace-range"#).unwrap(), string("hEYlo"));
+        assert_eq!(stack(r#""héllo" 1 2 "e" replace-range"#).unwrap(), string("hello"));
+        assert_eq!(stack(r#""hello" 0 0 ">" replace-range"#).unwrap(), string(">hello"));
+        assert_eq!(stack(r#""hello" copy "ll" pos drop copy 2 + "LL" replace-range"#).unwrap(), string("heLLo"));
+        assert_eq!(stack(r#""hello" 3 1 "x" replace-range"#).err().unwrap().msg(), "range start 3 is after its end 1");
+    }
+    #[test]
+    fn count_does_not_overlap() {
+        assert_eq!(stack(r#""aaaa" "aa" count "aaaa" "aa" count-overlapping"#).unwrap(), vec![Value::Int(2), Value::Int(3)]);
+        assert_eq!(stack(r#""hello" 'l' count "abc" "x" count"#).unwrap(), vec![Value::Int(2), Value::Int(0)]);
+        assert_eq!(stack(r#""ééé" "éé" count-overlapping"#).unwrap(), vec![Value::Int(2)]);
+        // the empty string matches between every two chars
+        assert_eq!(stack(r#""abc" "" count"#).unwrap(), vec![Value::Int(4)]);
+    }
+    #[test]
+    fn builtins_lists_every_name_once() {
+        let program = run_in(Program::std_program(), "builtins").unwrap();
+        let (names, count) = program.stack().as_slice().split_at(program.stack().len() - 1);
+        assert_eq!(count, [Value::Int(names.len() as i64)]);
+        for (idx, name) in names.iter().enumerate() {
+            assert!(!names[..idx].contains(name), "{name} is listed twice");
+            let Value::String(name) = name else { panic!() };
+            assert!(program.doc(name).is_some_and(|doc| !doc.is_empty()), "{name} has no help");
+        }
+        let core = run_in(Program::with_features(&[]), "builtins").unwrap();
+        assert_eq!(core.stack().peek(), Some(&Value::Int(BUILTINS.len() as i64)));
+    }
+    #[test]
+    fn help_shows_the_doc_and_signatures() {
+        assert_eq!(stack(r#""len" help"#).unwrap(), string("pushes the number of chars in the string, which indices count too\n[str] len"));
+    }
+    #[test]
+    fn vars_lists_the_variables_sorted() {
+        assert_eq!(stack("1 (y) 2 (x) vars").unwrap(), counted(&["x", "y"]));
+        assert_eq!(stack("vars").unwrap(), counted(&[]));
+    }
+    #[test]
+    fn macros_includes_user_macros_and_aliases() {
+        let with = stack("macro zz-mine [] 1 end alias zz-alias zz-mine macros").unwrap();
+        let without = stack("macros").unwrap();
+        assert_eq!(with.len(), without.len() + 2);
+        assert!(with.contains(&Value::String("zz-mine".to_string())) && with.contains(&Value::String("zz-alias".to_string())));
+        assert_eq!(with.last(), Some(&Value::Int(with.len() as i64 - 1)));
+    }
+
+    #[test]
+    fn cut() {
+        assert_eq!(stack(r#""a:b:c" ':' 2 cut"#).unwrap(), string("b"));
+        assert_eq!(stack(r#""a::b" "::" 2 cut"#).unwrap(), string("b"));
+        assert_eq!(stack(r#""a:b:c" ':' 2 3 cut"#).unwrap(), string("b:c"));
+        // fields past the end are empty
+        assert_eq!(stack(r#""a:b:c" ':' 4 cut"#).unwrap(), string(""));
+        assert_eq!(stack(r#""a:b:c" ':' 2 9 cut"#).unwrap(), string("b:c"));
+        assert_eq!(stack(r#""a:b:c" ':' 3 2 cut"#).unwrap(), string(""));
+    }
+
+    #[test]
+    fn cut_errors() {
+        assert_eq!(stack(r#""a:b:c" ':' 0 cut"#).err().unwrap().msg(), "fields are numbered from 1, got 0");
+        assert_eq!(stack(r#""a:b:c" ':' -1 2 cut"#).err().unwrap().msg(), "fields are numbered from 1, got -1");
+        assert_eq!(stack(r#""abc" "" 1 cut"#).err().unwrap().msg(), "cannot cut with an empty delimiter");
+    }
+
+    #[test]
+    fn chunks() {
+        assert_eq!(stack(r#""abcdefg" 3 chunks"#).unwrap(),
+            vec![Value::String("abc".into()), Value::String("def".into()), Value::String("g".into()), Value::Int(3)]);
+        assert_eq!(stack(r#""日本語" 2 chunks"#).unwrap(), vec![Value::String("日本".into()), Value::String("語".into()), Value::Int(2)]);
+        assert_eq!(stack(r#""" 3 chunks"#).unwrap(), vec![Value::Int(0)]);
+        assert_eq!(stack(r#""abc" 0 chunks"#).err().unwrap().msg(), "chunk size has to be at least 1, got 0");
+    }
+
+    #[test]
+    fn zip() {
+        assert_eq!(stack(r#""abc" "12345" zip"#).unwrap(), string("a1b2c3"));
+        assert_eq!(stack(r#""abc" "1" '.' zip"#).unwrap(), string("a1b.c."));
+        assert_eq!(stack(r#""" "12" '.' zip"#).unwrap(), string(".1.2"));
+        assert_eq!(stack(r#""" "" zip"#).unwrap(), string(""));
+    }
+
+    #[test]
+    fn strip_prefix_and_suffix() {
+        let stripped = |string: &str, did: bool| vec![Value::String(string.to_string()), Value::Boolean(did)];
+        assert_eq!(stack(r#""foobar" "foo" strip-prefix"#).unwrap(), stripped("bar", true));
+        assert_eq!(stack(r#""foobar" "bar" strip-prefix"#).unwrap(), stripped("foobar", false));
+        assert_eq!(stack(r#""foobar" 'r' strip-suffix"#).unwrap(), stripped("fooba", true));
+        assert_eq!(stack(r#""foobar" 'f' strip-suffix"#).unwrap(), stripped("foobar", false));
+        // the empty string is a prefix of everything
+        assert_eq!(stack(r#""foo" "" strip-suffix"#).unwrap(), stripped("foo", true));
+    }
+
+    #[test]
+    fn explain_stack() {
+        let program = run_in(Program::std_program(), "1 \"a\"").unwrap();
+        assert_eq!(explain(&program.stack), ["1: \"a\":str unknown origin, run with --trace-origins", "0: 1:int unknown origin, run with --trace-origins"]);
+        let mut program = run_in(Program::std_program(), "1").unwrap();
+        program.stack.trace_origins();
+        let program = run_in(program, "\n  'c'").unwrap();
+        assert_eq!(explain(&program.stack)[1], "0: 1:int pushed before tracing started");
+        assert!(explain(&program.stack)[0].starts_with("1: 'c':char pushed at "));
+        assert_eq!(explain(&Program::std_program().stack), ["<empty>"]);
+    }
+
+    #[test]
+    fn map_filter_fold() {
+        assert_eq!(stack(r#""abc" [drop "xy"] map "abc" [] map"#).unwrap(), vec![Value::String("xyxyxy".into()), Value::String("abc".into())]);
+        assert_eq!(stack(r#""abca" ['a' = not] filter"#).unwrap(), string("bc"));
+        assert_eq!(stack(r#""abc" "" [+] fold "abc" 0 [drop 1 +] fold"#).unwrap(), vec![Value::String("abc".into()), Value::Int(3)]);
+        assert_eq!(stack(r#""" 0 [drop] fold "" [1] map"#).unwrap(), vec![Value::Int(0), Value::String("".into())]);
+    }
+
+    #[test]
+    fn map_filter_fold_errors() {
+        assert_eq!(stack(r#""abc" [drop 1] map"#).err().unwrap().msg(), "expected the block to leave a char or string, got int");
+        assert_eq!(stack(r#""abc" [drop 1] filter"#).err().unwrap().msg(), "expected the block to leave a boolean, got int");
+        assert_eq!(stack(r#""abc" [drop] filter"#).err().unwrap().msg(), "the block left no value on the stack");
+    }
+
+    #[test]
+    fn dip_and_keep() {
+        assert_eq!(stack("1 2 [3 +] dip").unwrap(), vec![Value::Int(4), Value::Int(2)]);
+        assert_eq!(stack("1 [2 +] keep 5 [\"a\"] keep").unwrap(),
+            vec![Value::Int(3), Value::Int(1), Value::Int(5), Value::String("a".into()), Value::Int(5)]);
+        // the hidden value can't be reached from the block
+        assert!(stack("1 [drop] dip").err().unwrap().msg().starts_with("\"drop\" needs 1 value but the stack has 0"));
+    }
+
+    #[test]
+    fn curry() {
+        assert_eq!(stack(r#""a" ["b" +] curry call"#).unwrap(), string("ab"));
+        assert_eq!(stack(r#""a" ["b" +] curry"#).unwrap()[0].to_string(), r#"["a" "b" +]"#);
+        assert_eq!(stack("1.5 [] curry 'c' [] curry [1] [call] curry").unwrap().iter().map(Value::to_string).collect::<Vec<_>>(),
+            ["[1.5]", "['c']", "[[1] call]"]);
+        assert_eq!(stack("true [] curry call [1] [call] curry call").unwrap(), vec![Value::Boolean(true), Value::Int(1)]);
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn curry_rejects_decimals() {
+        assert_eq!(stack(r#""1.5" dec drop [] curry"#).err().unwrap().msg(), "cannot curry a dec value, curry the string and use dec in the block");
+    }
+
+    #[test]
+    fn checkpoint_and_rollback() {
+        assert_eq!(stack("1 checkpoint drop 2 3 rollback").unwrap(), vec![Value::Int(1)]);
+        assert_eq!(stack("1 checkpoint 2 commit").unwrap(), vec![Value::Int(1), Value::Int(2)]);
+        // checkpoints nest, rollback restores the latest one
+        assert_eq!(stack("1 checkpoint 2 checkpoint 3 rollback 4 rollback").unwrap(), vec![Value::Int(1)]);
+        // the variables are only restored by checkpoint-vars
+        assert_eq!(stack("1 (x) checkpoint x rollback x").err().unwrap().msg(), "unknown id \"x\"");
+        let program = run_in(Program::std_program(), "1 (x) checkpoint-vars x 2 (y) rollback").unwrap();
+        assert_eq!(program.stack().as_slice(), []);
+        assert_eq!(program.vars.get("x"), Some(&Value::Int(1)));
+        assert_eq!(program.vars.get("y"), None);
+    }
+
+    #[test]
+    fn rollback_without_checkpoint() {
+        assert_eq!(stack("rollback").err().unwrap().msg(), "there is no checkpoint to roll back to");
+        assert_eq!(stack("checkpoint commit commit").err().unwrap().msg(), "there is no checkpoint to commit");
+    }
+
+    #[test]
+    fn fail() {
+        let error = stack(r#""bad input" fail"#).err().unwrap();
+        assert_eq!(error.msg(), "bad input");
+        assert_eq!(error.pos().unwrap().col.start, 12);
+        // inside a macro the error points at the fail in its body
+        let error = stack(r#"macro f [] "oops" fail end f"#).err().unwrap();
+        assert_eq!((error.msg(), error.pos().unwrap().col.start), ("oops", 18));
+    }
+
+    #[test]
+    fn len_counts_chars() {
+        assert_eq!(stack(r#""héllo" len "héllo" byte-len "日本" len "日本" byte-len"#).unwrap(), [5, 6, 2, 6].map(Value::Int).to_vec());
+        assert_eq!(stack(r#""" len "" byte-len"#).unwrap(), vec![Value::Int(0), Value::Int(0)]);
+        // indexes count chars like len does
+        assert_eq!(stack(r#""héllo" 1 . "héllo" 'l' pos"#).unwrap(), vec![Value::Char('é'), Value::Int(2), Value::Boolean(true)]);
+    }
+
+    #[test]
+    fn assert() {
+        assert_eq!(stack(r#"true assert 1 1 assert-eq "a" "a" assert-eq"#).unwrap(), vec![]);
+        let error = stack("1 2 = assert").err().unwrap();
+        assert_eq!((error.msg(), error.pos().unwrap().col.start), ("assertion failed", 6));
+        assert_eq!(stack("1 1.0 assert-eq").err().unwrap().msg(), "assertion failed, 1:int isn't equal to 1.0:float");
+        assert_eq!(stack(r#""a" "b" assert-eq"#).err().unwrap().msg(), r#"assertion failed, "a":str isn't equal to "b":str"#);
+    }
+
+    #[test]
+    fn chars() {
+        assert_eq!(stack(r#""héllo" chars"#).unwrap(),
+            vec![Value::Char('h'), Value::Char('é'), Value::Char('l'), Value::Char('l'), Value::Char('o'), Value::Int(5)]);
+        assert_eq!(stack(r#""" chars"#).unwrap(), vec![Value::Int(0)]);
+    }
+}
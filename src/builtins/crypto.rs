@@ -0,0 +1,58 @@
+use crate::error::Error;
+use crate::run::Program;
+use crate::value::{Type, Value};
+
+builtins! {
+    "crc32" "pushes the CRC-32 checksum of the string" {
+        [String] => _crc32,
+    }
+    "adler32" "pushes the Adler-32 checksum of the string" {
+        [String] => _adler32,
+    }
+}
+
+fn _crc32(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let mut crc = 0xffffffffu32;
+        for byte in string.bytes() {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+            }
+        }
+        program.stack.push(Value::Int(!crc as i64));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _adler32(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let (mut a, mut b) = (1u32, 0u32);
+        for byte in string.bytes() {
+            a = (a + byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        program.stack.push(Value::Int(((b << 16) | a) as i64));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run::tests::stack;
+    use crate::value::Value;
+
+    #[test]
+    fn crc32() {
+        assert_eq!(stack(r#""The quick brown fox jumps over the lazy dog" crc32"#).unwrap(), vec![Value::Int(0x414fa339)]);
+        assert_eq!(stack(r#""" crc32"#).unwrap(), vec![Value::Int(0)]);
+    }
+    #[test]
+    fn adler32() {
+        assert_eq!(stack(r#""Wikipedia" adler32"#).unwrap(), vec![Value::Int(0x11e60398)]);
+        assert_eq!(stack(r#""" adler32"#).unwrap(), vec![Value::Int(1)]);
+    }
+}
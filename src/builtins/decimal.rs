@@ -0,0 +1,227 @@
+use std::cmp::Ordering;
+
+use crate::error;
+use crate::error::Error;
+use crate::error_no_pos;
+use crate::decimal::Decimal;
+use crate::run::Program;
+use crate::value::{Type, Value};
+
+builtins! {
+    "dec" "parses a string like \"12.50\" into an exact decimal and pushes true, or only false if it's invalid" {
+        [String] => _dec,
+    }
+    "to-dec" "converts an int or float into an exact decimal" {
+        [Int] => _to_dec,
+        [Float] => _to_dec,
+    }
+    "to-float" "converts a decimal into the nearest float" {
+        [Decimal] => _to_float,
+    }
+    "round" "rounds a decimal half away from zero to the given number of places" {
+        [Decimal Int] => _round,
+    }
+    "dec-format" "formats a decimal with the given number of places and ',' between thousands" {
+        [Decimal Int] => _dec_format,
+    }
+    "+" "adds two numbers" {
+        [Decimal Decimal] => _add,
+        [Decimal Int] => _add,
+        [Int Decimal] => _add,
+    }
+    "-" "subtracts two numbers" {
+        [Decimal Decimal] => _sub,
+        [Decimal Int] => _sub,
+        [Int Decimal] => _sub,
+    }
+    "*" "multiplies two numbers" {
+        [Decimal Decimal] => _mul,
+        [Decimal Int] => _mul,
+        [Int Decimal] => _mul,
+    }
+    "/" "divides two numbers" {
+        [Decimal Decimal] => _div,
+        [Decimal Int] => _div,
+        [Int Decimal] => _div,
+    }
+    "<" "pushes whether the first number is less than the second" {
+        [Decimal Decimal] => _lt,
+        [Decimal Int] => _lt,
+        [Int Decimal] => _lt,
+    }
+    ">" "pushes whether the first number is greater than the second" {
+        [Decimal Decimal] => _gt,
+        [Decimal Int] => _gt,
+        [Int Decimal] => _gt,
+    }
+    "<=" "pushes whether the first number is less than or equal to the second" {
+        [Decimal Decimal] => _le,
+        [Decimal Int] => _le,
+        [Int Decimal] => _le,
+    }
+    ">=" "pushes whether the first number is greater than or equal to the second" {
+        [Decimal Decimal] => _ge,
+        [Decimal Int] => _ge,
+        [Int Decimal] => _ge,
+    }
+}
+
+/// pops two decimals, ints are converted
+fn pop_decimals(program: &mut Program) -> (Decimal, Decimal) {
+    let mut pop = || match program.stack.pop().unwrap() {
+        Value::Decimal(decimal) => decimal,
+        Value::Int(int) => Decimal::from_int(int),
+        _ => panic!("type checking error!!!")
+    };
+    let b = pop();
+    (pop(), b)
+}
+fn pop_places(program: &mut Program) -> Result<u32, Error> {
+    match program.stack.pop().unwrap() {
+        Value::Int(places) => match u32::try_from(places) {
+            Ok(places) => Ok(places),
+            Err(_) => error_no_pos!("invalid number of decimal places {places}")
+        }
+        _ => panic!("type checking error!!!")
+    }
+}
+
+fn _dec(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        match Decimal::parse(string.trim()) {
+            Some(decimal) => {
+                program.stack.push(Value::Decimal(decimal));
+                program.stack.push(Value::Boolean(true));
+            }
+            None => program.stack.push(Value::Boolean(false))
+        }
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _to_dec(program: &mut Program) -> Result<(), Error> {
+    let decimal = match program.stack.pop().unwrap() {
+        Value::Int(int) => Decimal::from_int(int),
+        Value::Float(float) => match Decimal::from_float(float) {
+            Some(decimal) => decimal,
+            None => return error_no_pos!("cannot convert {float} to a decimal")
+        }
+        _ => panic!("type checking error!!!")
+    };
+    program.stack.push(Value::Decimal(decimal));
+    Ok(())
+}
+fn _to_float(program: &mut Program) -> Result<(), Error> {
+    if let Value::Decimal(decimal) = program.stack.pop().unwrap() {
+        program.stack.push(Value::Float(decimal.to_float()));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _round(program: &mut Program) -> Result<(), Error> {
+    let places = pop_places(program)?;
+    if let Value::Decimal(decimal) = program.stack.pop().unwrap() {
+        program.stack.push(Value::Decimal(decimal.round(places)));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _dec_format(program: &mut Program) -> Result<(), Error> {
+    let places = pop_places(program)?;
+    if let Value::Decimal(decimal) = program.stack.pop().unwrap() {
+        program.stack.push(Value::String(decimal.format(places, ',')));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _add(program: &mut Program) -> Result<(), Error> {
+    let (a, b) = pop_decimals(program);
+    program.stack.push(Value::Decimal(a.add(&b)));
+    Ok(())
+}
+fn _sub(program: &mut Program) -> Result<(), Error> {
+    let (a, b) = pop_decimals(program);
+    program.stack.push(Value::Decimal(a.sub(&b)));
+    Ok(())
+}
+fn _mul(program: &mut Program) -> Result<(), Error> {
+    let (a, b) = pop_decimals(program);
+    program.stack.push(Value::Decimal(a.mul(&b)));
+    Ok(())
+}
+fn _div(program: &mut Program) -> Result<(), Error> {
+    let (a, b) = pop_decimals(program);
+    match a.div(&b) {
+        Some(decimal) => program.stack.push(Value::Decimal(decimal)),
+        None => return error_no_pos!("division by zero")
+    }
+    Ok(())
+}
+fn _lt(program: &mut Program) -> Result<(), Error> {
+    let (a, b) = pop_decimals(program);
+    program.stack.push(Value::Boolean(a.cmp(&b) == Ordering::Less));
+    Ok(())
+}
+fn _gt(program: &mut Program) -> Result<(), Error> {
+    let (a, b) = pop_decimals(program);
+    program.stack.push(Value::Boolean(a.cmp(&b) == Ordering::Greater));
+    Ok(())
+}
+fn _le(program: &mut Program) -> Result<(), Error> {
+    let (a, b) = pop_decimals(program);
+    program.stack.push(Value::Boolean(a.cmp(&b) != Ordering::Greater));
+    Ok(())
+}
+fn _ge(program: &mut Program) -> Result<(), Error> {
+    let (a, b) = pop_decimals(program);
+    program.stack.push(Value::Boolean(a.cmp(&b) != Ordering::Less));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decimal::Decimal;
+    use crate::run::tests::{run, stack};
+    use crate::value::Value;
+
+    fn decimal(text: &str) -> Value {
+        Value::Decimal(Decimal::parse(text).unwrap())
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(stack("\"12.50\" dec").unwrap(), vec![decimal("12.50"), Value::Boolean(true)]);
+        assert_eq!(stack("\" 3 \" dec").unwrap(), vec![decimal("3"), Value::Boolean(true)]);
+        assert_eq!(stack("\"abc\" dec").unwrap(), vec![Value::Boolean(false)]);
+        assert_eq!(stack("1.5 to-dec 2 to-dec").unwrap(), vec![decimal("1.5"), decimal("2")]);
+        assert_eq!(stack("\"2.5\" dec drop to-float").unwrap(), vec![Value::Float(2.5)]);
+    }
+
+    #[test]
+    fn exact_arithmetic() {
+        assert_eq!(stack("\"0.1\" dec drop \"0.2\" dec drop +").unwrap(), vec![decimal("0.3")]);
+        assert_eq!(stack("\"0.3\" dec drop 1 -").unwrap(), vec![decimal("-0.7")]);
+        assert_eq!(stack("2 \"1.25\" dec drop *").unwrap(), vec![decimal("2.50")]);
+        assert_eq!(stack("\"1\" dec drop 3 /").unwrap(), vec![decimal("0.3333333333333333333333333333")]);
+        assert_eq!(stack("\"1.5\" dec drop 2 < \"1.5\" dec drop \"1.50\" dec drop >=").unwrap(),
+            vec![Value::Boolean(true), Value::Boolean(true)]);
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(run("\"1\" dec drop 0 /").err().unwrap().msg(), "division by zero");
+    }
+
+    #[test]
+    fn round_and_format() {
+        assert_eq!(stack("\"2.345\" dec drop 2 round").unwrap(), vec![decimal("2.35")]);
+        assert_eq!(stack("\"-2.5\" dec drop 0 round").unwrap(), vec![decimal("-3")]);
+        assert_eq!(stack("\"1234567.891\" dec drop 2 dec-format").unwrap(), vec![Value::String("1,234,567.89".into())]);
+        assert_eq!(stack("\"-0.5\" dec drop 2 dec-format").unwrap(), vec![Value::String("-0.50".into())]);
+        assert_eq!(run("\"2.345\" dec drop -1 round").err().unwrap().msg(), "invalid number of decimal places -1");
+    }
+}
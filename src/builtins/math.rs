@@ -0,0 +1,241 @@
+use crate::error;
+use crate::error::Error;
+use crate::error_no_pos;
+use crate::run::Program;
+use crate::value::{Type, Value};
+
+builtins! {
+    "-" "subtracts two numbers" {
+        [Int Int] => _sub,
+        [Float Float] => _sub,
+        [Int Float] => _sub,
+        [Float Int] => _sub,
+    }
+    "/" "divides two numbers, always giving a float" {
+        [Int Int] => _div,
+        [Float Float] => _div,
+        [Int Float] => _div,
+        [Float Int] => _div,
+    }
+    "%" "remainder of dividing two numbers" {
+        [Int Int] => _module,
+        [Float Float] => _module,
+        [Int Float] => _module,
+        [Float Int] => _module,
+    }
+    "<" "pushes whether the first number is less than the second" {
+        [Int Int] => _lt,
+        [Float Float] => _lt,
+        [Int Float] => _lt,
+        [Float Int] => _lt,
+    }
+    ">" "pushes whether the first number is greater than the second" {
+        [Int Int] => _gt,
+        [Float Float] => _gt,
+        [Int Float] => _gt,
+        [Float Int] => _gt,
+    }
+    "<=" "pushes whether the first number is less than or equal to the second" {
+        [Int Int] => _le,
+        [Float Float] => _le,
+        [Int Float] => _le,
+        [Float Int] => _le,
+    }
+    ">=" "pushes whether the first number is greater than or equal to the second" {
+        [Int Int] => _ge,
+        [Float Float] => _ge,
+        [Int Float] => _ge,
+        [Float Int] => _ge,
+    }
+    "to-base" "formats the int in a radix from 2 to 36, using lowercase letters for digits above 9" {
+        [Int Int] => _to_base,
+    }
+    "from-base" "parses a string in a radix from 2 to 36 and pushes the int and true, or only false if it's invalid" {
+        [String Int] => _from_base,
+    }
+}
+
+fn _sub(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a, b) {
+        (Value::Int(v1), Value::Int(v2)) => match v1.checked_sub(v2) {
+            Some(difference) => program.stack.push(Value::Int(difference)),
+            None => return error_no_pos!("{v1} - {v2} overflows an int")
+        }
+        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Float(v1 - v2)),
+        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Float(int as f64 - float)),
+        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Float(float - int as f64)),
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _div(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    let (v1, v2) = match (a, b) {
+        (Value::Int(v1), Value::Int(v2)) => (v1 as f64, v2 as f64),
+        (Value::Float(v1), Value::Float(v2)) => (v1, v2),
+        (Value::Int(int), Value::Float(float)) => (int as f64, float),
+        (Value::Float(float), Value::Int(int)) => (float, int as f64),
+        _ => panic!("type checking error!!!")
+    };
+    if v2 == 0.0 && !program.float_nan {
+        return error_no_pos!("division by zero")
+    }
+    program.stack.push(Value::Float(v1 / v2));
+    Ok(())
+}
+fn _module(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    let (v1, v2) = match (a, b) {
+        (Value::Int(v1), Value::Int(v2)) => match v1.checked_rem(v2) {
+            Some(int) => { program.stack.push(Value::Int(int)); return Ok(()) }
+            None if v2 == 0 => return error_no_pos!("modulo by zero"),
+            None => { program.stack.push(Value::Int(0)); return Ok(()) }
+        }
+        (Value::Float(v1), Value::Float(v2)) => (v1, v2),
+        (Value::Int(int), Value::Float(float)) => (int as f64, float),
+        (Value::Float(float), Value::Int(int)) => (float, int as f64),
+        _ => panic!("type checking error!!!")
+    };
+    if v2 == 0.0 && !program.float_nan {
+        return error_no_pos!("modulo by zero")
+    }
+    program.stack.push(Value::Float(v1 % v2));
+    Ok(())
+}
+fn _lt(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a, b) {
+        (Value::Int(v1), Value::Int(v2)) => program.stack.push(Value::Boolean(v1 < v2)),
+        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Boolean(v1 < v2)),
+        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Boolean((int as f64) < float)),
+        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Boolean(float < int as f64)),
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _gt(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a, b) {
+        (Value::Int(v1), Value::Int(v2)) => program.stack.push(Value::Boolean(v1 > v2)),
+        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Boolean(v1 > v2)),
+        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Boolean(int as f64 > float)),
+        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Boolean(float > int as f64)),
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _le(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a, b) {
+        (Value::Int(v1), Value::Int(v2)) => program.stack.push(Value::Boolean(v1 <= v2)),
+        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Boolean(v1 <= v2)),
+        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Boolean(int as f64 <= float)),
+        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Boolean(float <= int as f64)),
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn _ge(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    match (a, b) {
+        (Value::Int(v1), Value::Int(v2)) => program.stack.push(Value::Boolean(v1 >= v2)),
+        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Boolean(v1 >= v2)),
+        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Boolean(int as f64 >= float)),
+        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Boolean(float >= int as f64)),
+        _ => panic!("type checking error!!!")
+    }
+    Ok(())
+}
+fn pop_radix(program: &mut Program) -> Result<u32, Error> {
+    match program.stack.pop().unwrap() {
+        Value::Int(radix @ 2..=36) => Ok(radix as u32),
+        Value::Int(radix) => error_no_pos!("radix has to be between 2 and 36, got {radix}"),
+        _ => panic!("type checking error!!!")
+    }
+}
+fn _to_base(program: &mut Program) -> Result<(), Error> {
+    let radix = pop_radix(program)?;
+    if let Value::Int(int) = program.stack.pop().unwrap() {
+        let mut rest = int.unsigned_abs();
+        let mut digits = vec![];
+        loop {
+            digits.push(char::from_digit((rest % radix as u64) as u32, radix).unwrap());
+            rest /= radix as u64;
+            if rest == 0 { break }
+        }
+        if int < 0 { digits.push('-') }
+        program.stack.push(Value::String(digits.iter().rev().collect()));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _from_base(program: &mut Program) -> Result<(), Error> {
+    let radix = pop_radix(program)?;
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        match i64::from_str_radix(string.trim(), radix) {
+            Ok(int) => {
+                program.stack.push(Value::Int(int));
+                program.stack.push(Value::Boolean(true));
+            }
+            Err(_) => program.stack.push(Value::Boolean(false))
+        }
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lexer, parser};
+    use crate::run::Program;
+    use crate::run::tests::{run, stack};
+    use crate::value::Value;
+
+    #[test]
+    fn division_by_zero() {
+        for script in ["1 0 /", "1.0 0 /", "1 0.0 /", "1 0 %", "0 0.0 %", "1.5 0.0 %"] {
+            assert!(run(script).is_err(), "{script}");
+        }
+        assert_eq!(run("1 0 /").err().unwrap().msg(), "division by zero");
+        assert_eq!(run("1 0 %").err().unwrap().msg(), "modulo by zero");
+        assert_eq!(stack("7 2 / -7 2 %").unwrap(), vec![Value::Float(3.5), Value::Int(-1)]);
+    }
+    #[test]
+    fn division_by_zero_with_float_nan() {
+        let mut program = Program::std_program();
+        program.float_nan = true;
+        program.run(parser::parse(lexer::lex(String::from("1 0 / -1.0 0 / 0 0.0 %")).unwrap()).unwrap()).unwrap();
+        let [Value::Float(inf), Value::Float(neg_inf), Value::Float(nan)] = program.stack().as_slice() else { panic!() };
+        assert!(*inf == f64::INFINITY && *neg_inf == f64::NEG_INFINITY && nan.is_nan());
+        // ints have no NaN, so their modulo by zero stays an error
+        assert!(program.run(parser::parse(lexer::lex(String::from("1 0 %")).unwrap()).unwrap()).is_err());
+    }
+    #[test]
+    fn int_modulo_of_min_by_minus_one() {
+        assert_eq!(stack("-9223372036854775808 -1 %").unwrap(), vec![Value::Int(0)]);
+    }
+    #[test]
+    fn sub_overflow() {
+        assert_eq!(run("-9223372036854775808 1 -").err().unwrap().msg(), "-9223372036854775808 - 1 overflows an int");
+        assert_eq!(stack("-9223372036854775807 1 -").unwrap(), vec![Value::Int(i64::MIN)]);
+    }
+
+    #[test]
+    fn to_and_from_base() {
+        assert_eq!(stack("255 16 to-base -255 2 to-base 0 36 to-base").unwrap(),
+            vec![Value::String("ff".into()), Value::String("-11111111".into()), Value::String("0".into())]);
+        assert_eq!(stack("-9223372036854775808 16 to-base").unwrap(), vec![Value::String("-8000000000000000".into())]);
+        assert_eq!(stack("\"ff\" 16 from-base \"-101\" 2 from-base \"Z\" 36 from-base").unwrap(),
+            vec![Value::Int(255), Value::Boolean(true), Value::Int(-5), Value::Boolean(true), Value::Int(35), Value::Boolean(true)]);
+        assert_eq!(stack("\"g\" 16 from-base \"\" 10 from-base").unwrap(), vec![Value::Boolean(false), Value::Boolean(false)]);
+    }
+
+    #[test]
+    fn invalid_radix() {
+        assert_eq!(run("1 37 to-base").err().unwrap().msg(), "radix has to be between 2 and 36, got 37");
+        assert_eq!(run("\"1\" 1 from-base").err().unwrap().msg(), "radix has to be between 2 and 36, got 1");
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use crate::error;
+use crate::error::Error;
+use crate::error_no_pos;
+use crate::run::{MacroOverload, MacroType, Operation};
+use crate::value::Type;
+
+/// the most bytes a builtin builds a string of, so a huge count is an error instead of aborting
+pub const MAX_STRING_LEN: usize = 1 << 30;
+
+/// errors if `count` pieces of `len` bytes would make a string longer than `MAX_STRING_LEN`
+pub fn check_len(count: usize, len: usize) -> Result<(), Error> {
+    match count.checked_mul(len) {
+        Some(total) if total <= MAX_STRING_LEN => Ok(()),
+        _ => error_no_pos!("the string would be longer than the limit of {MAX_STRING_LEN} bytes")
+    }
+}
+/// whether CSV text that starts inside quotes if `quoted` ends inside them, the `escape` char escapes
+/// the char after it inside quotes
+pub fn csv_quoted(text: &str, quote: char, escape: Option<char>, mut quoted: bool) -> bool {
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if Some(c) == escape && quoted {
+            chars.next();
+        } else if c == quote {
+            quoted = !quoted;
+        }
+    }
+    quoted
+}
+
+/// a builtin macro with all of its overloads and a short help text
+pub struct Builtin {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub overloads: &'static [(&'static [Type], Operation)]
+}
+impl Builtin {
+    /// adds the overloads to the macro of the same name, so groups can extend each other's builtins
+    pub fn register(&self, macros: &mut HashMap<String, MacroOverload>) {
+        let macro_overload = macros.entry(self.name.to_string()).or_insert_with(MacroOverload::new);
+        for (types, func) in self.overloads {
+            macro_overload.def(types.to_vec(), MacroType::Operation(*func));
+        }
+        if macro_overload.doc.is_none() {
+            macro_overload.doc = Some(self.help.to_string());
+        }
+    }
+}
+
+/// declares the `BUILTINS` table of a module
+///
+/// ```ignore
+/// builtins! {
+///     "name" "help text" {
+///         [String Int] => _function,
+///     }
+/// }
+/// ```
+macro_rules! builtins {
+    ($($name:literal $help:literal { $([$($typ:ident)*] => $func:expr),* $(,)? })*) => {
+        pub const BUILTINS: &[crate::builtins::Builtin] = &[$(
+            crate::builtins::Builtin {
+                name: $name,
+                help: $help,
+                overloads: &[$((&[$(Type::$typ),*], $func)),*]
+            }
+        ),*];
+    };
+}
+
+pub mod core;
+#[cfg(feature = "math")]
+pub mod math;
+#[cfg(feature = "text")]
+pub mod text;
+#[cfg(feature = "crypto")]
+pub mod crypto;
+#[cfg(feature = "rand")]
+pub mod rand;
+#[cfg(feature = "decimal")]
+pub mod decimal;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+#[cfg(feature = "plugins")]
+pub mod plugins;
+#[cfg(feature = "banner")]
+pub mod banner;
+
+/// optional groups of builtins, each one only exists if its cargo feature of the same name is enabled
+///
+/// the core group (stack operations, logic, `+`, `*` and the basic string operations) is always registered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    #[cfg(feature = "math")]
+    Math,
+    #[cfg(feature = "text")]
+    Text,
+    #[cfg(feature = "crypto")]
+    Crypto,
+    #[cfg(feature = "rand")]
+    Rand,
+    #[cfg(feature = "decimal")]
+    Decimal,
+    #[cfg(feature = "parallel")]
+    Parallel,
+    #[cfg(feature = "plugins")]
+    Plugins,
+    #[cfg(feature = "banner")]
+    Banner,
+}
+impl Feature {
+    /// every feature compiled into this build
+    pub const ALL: &'static [Self] = &[
+        #[cfg(feature = "math")]
+        Self::Math,
+        #[cfg(feature = "text")]
+        Self::Text,
+        #[cfg(feature = "crypto")]
+        Self::Crypto,
+        #[cfg(feature = "rand")]
+        Self::Rand,
+        #[cfg(feature = "decimal")]
+        Self::Decimal,
+        #[cfg(feature = "parallel")]
+        Self::Parallel,
+        #[cfg(feature = "plugins")]
+        Self::Plugins,
+        #[cfg(feature = "banner")]
+        Self::Banner,
+    ];
+    pub fn builtins(&self) -> &'static [Builtin] {
+        match *self {
+            #[cfg(feature = "math")]
+            Self::Math => math::BUILTINS,
+            #[cfg(feature = "text")]
+            Self::Text => text::BUILTINS,
+            #[cfg(feature = "crypto")]
+            Self::Crypto => crypto::BUILTINS,
+            #[cfg(feature = "rand")]
+            Self::Rand => rand::BUILTINS,
+            #[cfg(feature = "decimal")]
+            Self::Decimal => decimal::BUILTINS,
+            #[cfg(feature = "parallel")]
+            Self::Parallel => parallel::BUILTINS,
+            #[cfg(feature = "plugins")]
+            Self::Plugins => plugins::BUILTINS,
+            #[cfg(feature = "banner")]
+            Self::Banner => banner::BUILTINS,
+        }
+    }
+}
+
+/// the core builtins followed by the ones of each feature
+pub fn all(features: &[Feature]) -> impl Iterator<Item = &'static Builtin> + '_ {
+    core::BUILTINS.iter().chain(features.iter().flat_map(|feature| feature.builtins()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn core_only_without_features() {
+        let program = crate::run::Program::with_features(&[]);
+        let mut names: Vec<&String> = program.macros.keys().collect();
+        names.sort();
+        let mut core: Vec<&str> = core::BUILTINS.iter().map(|builtin| builtin.name).collect();
+        core.sort();
+        assert_eq!(names, core);
+    }
+    #[cfg(feature = "math")]
+    #[test]
+    fn math_only_with_its_feature() {
+        let without = crate::run::Program::with_features(&[]);
+        let with = crate::run::Program::with_features(&[Feature::Math]);
+        for builtin in math::BUILTINS {
+            assert!(without.get_macro(builtin.name).is_none(), "{:?} is registered without the math feature", builtin.name);
+            assert!(with.get_macro(builtin.name).is_some(), "{:?} is missing with the math feature", builtin.name);
+        }
+    }
+    #[test]
+    fn overloads_do_not_overlap() {
+        let mut overloads: HashMap<&str, Vec<&[Type]>> = HashMap::new();
+        for builtin in all(Feature::ALL) {
+            overloads.entry(builtin.name).or_default().extend(builtin.overloads.iter().map(|(types, _)| *types));
+        }
+        for (name, overloads) in overloads {
+            for (idx, types) in overloads.iter().enumerate() {
+                assert!(!overloads[..idx].contains(types), "overloads of {name:?} overlap at {types:?}");
+            }
+        }
+    }
+}
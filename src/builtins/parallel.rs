@@ -0,0 +1,157 @@
+use std::thread;
+
+use crate::error;
+use crate::error::Error;
+use crate::error_no_pos;
+use crate::lexer::Position;
+use crate::parser::{Node, NodeType};
+use crate::run::Program;
+use crate::value::{Type, Value};
+
+builtins! {
+    "pmap" "runs a pure block or the named macro on each of the top n values in parallel, each on its own copy of the program, pushing the results and their count" {
+        [Int Block] => _pmap,
+        [Int String] => _pmap,
+    }
+}
+
+/// builtins that read or change state the copies of the program don't share
+const IMPURE: [&str; 9] = ["seed", "rand-int", "shuffle", "uuid", "checkpoint", "checkpoint-vars", "rollback", "commit", "load-plugin"];
+
+/// why `node` could give other results in a copy of the program than in the program itself, if it could,
+/// `seen` are the macros already checked
+fn impurity(program: &Program, node: &Node, seen: &mut Vec<String>) -> Option<String> {
+    match &node.node {
+        NodeType::Chunk(nodes) => nodes.iter().find_map(|node| impurity(program, node, seen)),
+        NodeType::ID(id) => {
+            if IMPURE.contains(&id.as_str()) {
+                return Some(format!("it calls {id:?}"))
+            }
+            if seen.contains(id) { return None }
+            seen.push(id.clone());
+            let macro_overload = program.get_macro(id)?;
+            macro_overload.user_macros().find_map(|(_, _, guard, body)| {
+                guard.and_then(|guard| impurity(program, guard, seen)).or_else(|| impurity(program, body, seen))
+            }).map(|reason| format!("{reason} in {id:?}"))
+        }
+        NodeType::If(case_node, else_node) => impurity(program, case_node, seen)
+            .or_else(|| impurity(program, else_node.as_deref()?, seen)),
+        NodeType::Match(cases, else_node) => cases.iter().find_map(|(_, body)| impurity(program, body, seen))
+            .or_else(|| impurity(program, else_node.as_deref()?, seen)),
+        NodeType::While(cond, body) => impurity(program, cond, seen).or_else(|| impurity(program, body, seen)),
+        NodeType::Repeat(body) | NodeType::ForEach(body) | NodeType::Let(body) | NodeType::Block(body) => impurity(program, body, seen),
+        NodeType::WithLines(_) | NodeType::WithRecords(_) => Some(String::from("it reads a file")),
+        NodeType::Macro(name, ..) => Some(format!("it defines the macro {name:?}")),
+        NodeType::Alias(name, _) => Some(format!("it defines the alias {name:?}")),
+        NodeType::Include(path) | NodeType::Import(path, _) => Some(format!("it loads {path:?}")),
+        _ => None
+    }
+}
+
+/// runs `body` on every value with an empty stack and the variables of the program, each run has
+/// to leave exactly one value
+fn map(mut program: Program, body: &Node, values: Vec<Value>) -> Result<Vec<Value>, Error> {
+    let vars = program.vars.clone();
+    let mut results = vec![];
+    for value in values {
+        program.stack = Default::default();
+        program.vars = vars.clone();
+        program.stack.push(value);
+        program.run(body.clone())?;
+        if program.stack.len() != 1 {
+            return error_no_pos!("pmap expects the block to leave one value, it left {}", program.stack.len())
+        }
+        results.push(program.stack.pop().unwrap());
+    }
+    Ok(results)
+}
+fn _pmap(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    let (count, body) = match (a, b) {
+        (Value::Int(count), Value::Block(body)) => (count, *body),
+        (Value::Int(count), Value::String(id)) => {
+            if program.get_macro(&id).is_none() {
+                return error_no_pos!("unknown macro {id:?}")
+            }
+            (count, Node::new(NodeType::ID(id), Position::zero()))
+        }
+        _ => panic!("type checking error!!!")
+    };
+    let count = count.max(0) as usize;
+    if count > program.stack.len() {
+        return error_no_pos!("cannot map {count} values, the stack only has {}", program.stack.len())
+    }
+    // blocks among the values can be called by the body, so they have to be pure as well
+    let blocks = program.stack.as_slice()[program.stack.len() - count..].iter().filter_map(|value| match value {
+        Value::Block(block) => Some(block.as_ref()),
+        _ => None
+    });
+    if let Some(reason) = std::iter::once(&body).chain(blocks).find_map(|node| impurity(program, node, &mut vec![])) {
+        return error_no_pos!("pmap only runs pure code, but {reason}")
+    }
+    let mut values = vec![];
+    for _ in 0..count {
+        values.push(program.stack.pop().unwrap());
+    }
+    values.reverse();
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let chunk_size = count.div_ceil(threads).max(1);
+    let results: Vec<Result<Vec<Value>, Error>> = thread::scope(|scope| {
+        let workers: Vec<_> = values.chunks(chunk_size).map(|chunk| {
+            let (fork, body) = (program.fork(), &body);
+            scope.spawn(move || map(fork, body, chunk.to_vec()))
+        }).collect();
+        workers.into_iter().map(|worker| worker.join().expect("pmap worker panicked")).collect()
+    });
+    for result in results {
+        for value in result? {
+            program.stack.push(value);
+        }
+    }
+    program.stack.push(Value::Int(count as i64));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run::tests::{run, stack};
+    use crate::value::Value;
+
+    fn strings(strings: &[&str]) -> Vec<Value> {
+        strings.iter().map(|string| Value::String(string.to_string())).collect()
+    }
+
+    #[test]
+    fn pmap_block() {
+        let mut expected = strings(&["a!", "b!", "c!"]);
+        expected.push(Value::Int(3));
+        assert_eq!(stack(r#""a" "b" "c" 3 ["!" +] pmap"#).unwrap(), expected);
+    }
+    #[test]
+    fn pmap_macro() {
+        let mut expected = strings(&["x", "A!", "B!"]);
+        expected.push(Value::Int(2));
+        assert_eq!(stack(r#"macro shout [str] "!" + end "x" "A" "B" 2 "shout" pmap"#).unwrap(), expected);
+        assert!(run(r#""a" 1 "nothing" pmap"#).is_err());
+    }
+    #[test]
+    fn pmap_sees_the_same_variables_for_every_value() {
+        let mut expected = strings(&["a!", "b!", "c!"]);
+        expected.push(Value::Int(3));
+        assert_eq!(stack(r#""!" (x) "a" "b" "c" 3 [x +] pmap"#).unwrap(), expected);
+    }
+    #[test]
+    fn pmap_rejects_impure_code() {
+        let error = run(r#""a" 1 [drop 0 9 rand-int] pmap"#).err().unwrap();
+        assert_eq!(error.msg(), "pmap only runs pure code, but it calls \"rand-int\"");
+        let error = run(r#"macro save [str] checkpoint end "a" 1 "save" pmap"#).err().unwrap();
+        assert_eq!(error.msg(), "pmap only runs pure code, but it calls \"checkpoint\" in \"save\"");
+        assert!(run(r#""a" 1 [macro f [] 1 end] pmap"#).is_err());
+        assert!(run(r#"[shuffle] 1 [call] pmap"#).is_err());
+    }
+    #[test]
+    fn pmap_count() {
+        assert!(run(r#""a" 2 ["!" +] pmap"#).is_err());
+        assert_eq!(stack(r#""a" 0 ["!" +] pmap"#).unwrap(), vec![Value::String("a".to_string()), Value::Int(0)]);
+    }
+}
@@ -0,0 +1,26 @@
+use crate::error::Error;
+use crate::plugin;
+use crate::run::{MacroOverload, MacroType, Program};
+use crate::value::{Type, Value};
+
+builtins! {
+    "load-plugin" "loads the native macros of a plugin library" {
+        [String] => _load_plugin,
+    }
+}
+
+fn _load_plugin(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(path) = program.stack.pop().unwrap() {
+        let file = match program.resolve(&path) {
+            Some(file) => file.to_string_lossy().to_string(),
+            None => path
+        };
+        for (name, native) in plugin::load(&file)? {
+            program.macros.entry(name).or_insert_with(MacroOverload::new)
+                .def(vec![Type::Any; native.arity], MacroType::Native(native));
+        }
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
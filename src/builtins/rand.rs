@@ -0,0 +1,98 @@
+use crate::error::Error;
+use crate::run::Program;
+use crate::value::{Type, Value};
+
+builtins! {
+    "seed" "reseeds the random number generator" {
+        [Int] => _seed,
+    }
+    "rand-int" "pushes a random int between two bounds, both inclusive" {
+        [Int Int] => _rand_int,
+    }
+    "shuffle" "shuffles the chars of the string" {
+        [String] => _shuffle,
+    }
+    "uuid" "pushes a random version 4 UUID" {
+        [] => _uuid,
+    }
+}
+
+fn _seed(program: &mut Program) -> Result<(), Error> {
+    if let Value::Int(seed) = program.stack.pop().unwrap() {
+        program.set_seed(seed as u64);
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _rand_int(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::Int(min), Value::Int(max)) = (a, b) {
+        let int = program.rng.range(min, max);
+        program.stack.push(Value::Int(int));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _shuffle(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let mut chars: Vec<char> = string.chars().collect();
+        for idx in (1..chars.len()).rev() {
+            let other = program.rng.range(0, idx as i64) as usize;
+            chars.swap(idx, other);
+        }
+        program.stack.push(Value::String(chars.into_iter().collect()));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _uuid(program: &mut Program) -> Result<(), Error> {
+    let (high, low) = (program.rng.next_u64(), program.rng.next_u64());
+    let high = (high & !0xf000) | 0x4000;
+    let low = (low & !(0xc << 60)) | (0x8 << 60);
+    let hex = format!("{high:016x}{low:016x}");
+    program.stack.push(Value::String(format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lexer, parser};
+    use crate::run::Program;
+    use crate::run::tests::stack;
+    use crate::value::Value;
+
+    #[test]
+    fn seed_makes_runs_reproducible() {
+        let script = r#"42 seed 0 1000 rand-int "abcdef" shuffle uuid"#;
+        assert_eq!(stack(script).unwrap(), stack(script).unwrap());
+        assert_ne!(stack(r#"1 seed uuid"#).unwrap(), stack(r#"2 seed uuid"#).unwrap());
+    }
+    #[test]
+    fn set_seed_matches_the_builtin() {
+        let mut program = Program::std_program();
+        program.set_seed(42);
+        program.run(parser::parse(lexer::lex(String::from("0 1000 rand-int")).unwrap()).unwrap()).unwrap();
+        assert_eq!(program.stack().as_slice(), stack("42 seed 0 1000 rand-int").unwrap().as_slice());
+    }
+    #[test]
+    fn rand_int_bounds() {
+        assert_eq!(stack("3 3 rand-int").unwrap(), vec![Value::Int(3)]);
+        for value in stack("7 seed 1 3 rand-int 1 3 rand-int 1 3 rand-int 3 1 rand-int").unwrap() {
+            assert!(matches!(value, Value::Int(1..=3)));
+        }
+    }
+    #[test]
+    fn shuffle_and_uuid_shapes() {
+        let Value::String(shuffled) = &stack(r#"5 seed "aabbc" shuffle"#).unwrap()[0] else { panic!() };
+        let mut chars: Vec<char> = shuffled.chars().collect();
+        chars.sort();
+        assert_eq!(chars, vec!['a', 'a', 'b', 'b', 'c']);
+        let Value::String(uuid) = &stack("uuid").unwrap()[0] else { panic!() };
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(uuid.chars().nth(14), Some('4'));
+        assert!(matches!(uuid.chars().nth(19), Some('8' | '9' | 'a' | 'b')));
+    }
+}
@@ -0,0 +1,1662 @@
+use std::{collections::HashMap, env, net::IpAddr};
+
+use crate::error;
+use crate::error::Error;
+use crate::error_no_pos;
+use crate::builtins;
+use crate::run::Program;
+use crate::value::{Type, Value};
+#[cfg(feature = "html-entities")]
+use crate::entities;
+
+builtins! {
+    "detab" "replaces tabs with spaces up to the next tab stop, every 4 columns or the given width" {
+        [String] => _detab,
+        [String Int] => _detab,
+    }
+    "entab" "replaces runs of spaces ending on a tab stop with tabs, every 4 columns or the given width" {
+        [String] => _entab,
+        [String Int] => _entab,
+    }
+    "indent" "prefixes every non-blank line with n spaces or the given string" {
+        [String Int] => _indent,
+        [String String] => _indent,
+    }
+    "dedent" "removes the leading whitespace all non-blank lines have in common" {
+        [String] => _dedent,
+    }
+    "strip-ansi" "removes ANSI escape sequences" {
+        [String] => _strip_ansi,
+    }
+    "sh-quote" "quotes the string for use as a single shell word" {
+        [String] => _sh_quote,
+    }
+    "html-escape" "escapes &, <, >, \" and ' as HTML entities" {
+        [String] => _html_escape,
+    }
+    "html-unescape" "decodes HTML entities and numeric character references" {
+        [String] => _html_unescape,
+    }
+    "slugify" "turns the string into a lowercase, hyphen separated URL slug" {
+        [String] => _slugify,
+    }
+    "parse-duration" "parses a duration like \"1h30m\" into seconds and pushes true, or only false if it's invalid" {
+        [String] => _parse_duration,
+    }
+    "humanize-duration" "formats seconds like \"1 hour 30 minutes\"" {
+        [Int] => _humanize_duration,
+    }
+    "soundex" "pushes the four character Soundex code of a name, like \"S530\" for both \"Smith\" and \"Smyth\"" {
+        [String] => _soundex,
+    }
+    "metaphone" "pushes the Metaphone key of a word, which is the same for most words that sound alike" {
+        [String] => _metaphone,
+    }
+    "jaro-winkler" "pushes the Jaro-Winkler similarity of two strings, from 0.0 for nothing in common to 1.0 for equal" {
+        [String String] => _jaro_winkler,
+    }
+    "ngram-sim" "pushes the share of n-grams two strings have in common (Dice coefficient), of bigrams or the given n" {
+        [String String] => _ngram_sim,
+        [String String Int] => _ngram_sim,
+    }
+    "lcs" "pushes the longest common subsequence of two strings and its length" {
+        [String String] => _lcs,
+    }
+    "lcsubstring" "pushes the longest substring two strings have in common and its length" {
+        [String String] => _lcsubstring,
+    }
+    "is-palindrome" "pushes whether the string reads the same backwards, if given true ignoring case, whitespace and punctuation" {
+        [String] => _is_palindrome,
+        [String Boolean] => _is_palindrome,
+    }
+    "is-anagram" "pushes whether two strings consist of the same chars, if given true ignoring case, whitespace and punctuation" {
+        [String String] => _is_anagram,
+        [String String Boolean] => _is_anagram,
+    }
+    "rle-encode" "run-length encodes the string, \"aaab\" becomes \"3ab\", digits and \\ are escaped with a \\" {
+        [String] => _rle_encode,
+    }
+    "rle-decode" "decodes a run-length encoded string and pushes true, or only false if it's invalid, a run that would exceed the string limit is an error" {
+        [String] => _rle_decode,
+    }
+    "squeeze" "collapses runs of the same char to one if it's in the set, like \"a-z \\t\"" {
+        [String String] => _squeeze,
+    }
+    "delete-chars" "removes every char in the set, like \"0-9_\"" {
+        [String String] => _delete_chars,
+    }
+    "ordinal" "formats the number with its English ordinal suffix, like \"1st\" or \"12th\"" {
+        [Int] => _ordinal,
+    }
+    "pluralize" "pushes the English plural of the word unless the count is 1 or -1" {
+        [String Int] => _pluralize,
+    }
+    "expand-env" "substitutes $VAR, ${VAR} and ${VAR:-default} with environment variables, $$ is a literal $" {
+        [String] => _expand_env,
+    }
+    "glob-match" "pushes whether the whole string matches a wildcard pattern with *, ?, [a-z] and [!a-z]" {
+        [String String] => _glob_match,
+    }
+    "parse-ip" "parses an IPv4 or IPv6 address and pushes its normalized form and true, or only false if it's invalid" {
+        [String] => _parse_ip,
+    }
+    "csv-fields" "splits a CSV record into its fields and pushes them and their count, optionally with another delimiter, quote and escape char" {
+        [String] => _csv_fields,
+        [String Char] => _csv_fields,
+        [String Char Char] => _csv_fields,
+        [String Char Char Char] => _csv_fields,
+    }
+    "csv-records" "splits CSV text at line breaks outside of quotes and pushes the records and their count, optionally with another quote and escape char" {
+        [String] => _csv_records,
+        [String Char] => _csv_records,
+        [String Char Char] => _csv_records,
+    }
+    "clean" "normalizes the string by the flags, one step per flag in the order they are given: t trims, w collapses whitespace, n composes common Latin accents (a subset of NFC), l lowercases" {
+        [String String] => _clean,
+    }
+    "to-morse" "encodes the string in Morse code, letters separated by spaces and words by \" / \"" {
+        [String] => _to_morse,
+    }
+    "from-morse" "decodes Morse code written like to-morse does and pushes true, or only false if it's invalid" {
+        [String] => _from_morse,
+    }
+    "to-nato" "spells the letters and digits of the string with the NATO phonetic alphabet" {
+        [String] => _to_nato,
+    }
+    "to-leet" "replaces letters with look-alike digits, like \"leet\" becomes \"1337\"" {
+        [String] => _to_leet,
+    }
+    "bar-chart" "renders the top n label/number pairs, like the ones char-freq pushes, as a text bar chart with bars up to the given width" {
+        [Int Int] => _bar_chart,
+    }
+    "unpack" "slices the line into fixed-width fields given by widths like \"10 5 8\", pushing the fields without their padding and their count" {
+        [String String] => _unpack,
+    }
+    "word-count" "pushes the number of whitespace separated words" {
+        [String] => _word_count,
+    }
+    "line-count" "pushes the number of lines, a final line break doesn't start another one" {
+        [String] => _line_count,
+    }
+    "char-freq" "pushes every distinct char with how often it occurs, most frequent first, followed by the number of distinct chars" {
+        [String] => _char_freq,
+    }
+}
+
+const TAB_WIDTH: usize = 4;
+
+fn pop_tab_width(program: &mut Program) -> usize {
+    if let Some(Value::Int(width)) = program.stack.peek() {
+        let width = (*width).max(1) as usize;
+        program.stack.pop();
+        width
+    } else {
+        TAB_WIDTH
+    }
+}
+fn _detab(program: &mut Program) -> Result<(), Error> {
+    let width = pop_tab_width(program);
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let mut detabbed = String::new();
+        let mut col = 0;
+        for c in string.chars() {
+            match c {
+                '\t' => {
+                    let spaces = width - col % width;
+                    detabbed.push_str(" ".repeat(spaces).as_str());
+                    col += spaces;
+                }
+                '\n' => { detabbed.push(c); col = 0 }
+                _ => { detabbed.push(c); col += 1 }
+            }
+        }
+        program.stack.push(Value::String(detabbed));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _entab(program: &mut Program) -> Result<(), Error> {
+    let width = pop_tab_width(program);
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let mut entabbed = String::new();
+        let mut col = 0;
+        let mut spaces = 0;
+        for c in string.chars() {
+            if c == ' ' {
+                spaces += 1;
+                col += 1;
+                if col % width == 0 {
+                    entabbed.push(if spaces == 1 { ' ' } else { '\t' });
+                    spaces = 0;
+                }
+                continue
+            }
+            entabbed.push_str(" ".repeat(spaces).as_str());
+            spaces = 0;
+            match c {
+                '\t' => { entabbed.push(c); col += width - col % width }
+                '\n' => { entabbed.push(c); col = 0 }
+                _ => { entabbed.push(c); col += 1 }
+            }
+        }
+        entabbed.push_str(" ".repeat(spaces).as_str());
+        program.stack.push(Value::String(entabbed));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _indent(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    let (string, prefix) = match (a, b) {
+        (Value::String(string), Value::Int(width)) => (string, " ".repeat(width.max(0) as usize)),
+        (Value::String(string), Value::String(prefix)) => (string, prefix),
+        _ => panic!("type checking error!!!")
+    };
+    let indented: Vec<String> = string.split('\n').map(|line| {
+        if line.trim().is_empty() { line.to_string() } else { format!("{prefix}{line}") }
+    }).collect();
+    program.stack.push(Value::String(indented.join("\n")));
+    Ok(())
+}
+fn _dedent(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let margin = string.split('\n')
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+            .min().unwrap_or(0);
+        let dedented: Vec<&str> = string.split('\n').map(|line| {
+            if line.trim().is_empty() { line.trim_start() } else { &line[margin..] }
+        }).collect();
+        program.stack.push(Value::String(dedented.join("\n")));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _strip_ansi(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let mut stripped = String::new();
+        let mut chars = string.chars().peekable();
+        while let Some(c) = chars.next() {
+            let csi = match c {
+                '\x1b' if chars.peek() == Some(&'[') => { chars.next(); true }
+                '\u{9b}' => true,
+                _ => false
+            };
+            if !csi {
+                stripped.push(c);
+                continue
+            }
+            // parameter and intermediate bytes, then a single final byte
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) { break }
+            }
+        }
+        program.stack.push(Value::String(stripped));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _sh_quote(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let safe = !string.is_empty() && string.chars().all(|c| c.is_ascii_alphanumeric() || "@%+=:,./-_".contains(c));
+        if safe {
+            program.stack.push(Value::String(string));
+        } else {
+            program.stack.push(Value::String(format!("'{}'", string.replace('\'', "'\"'\"'"))));
+        }
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _html_escape(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let mut escaped = String::new();
+        for c in string.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                _ => escaped.push(c)
+            }
+        }
+        program.stack.push(Value::String(escaped));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn html_entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => match name.strip_prefix('#') {
+            Some(code) => match code.strip_prefix(['x', 'X']) {
+                Some(hex) => char::from_u32(u32::from_str_radix(hex, 16).ok()?),
+                None => char::from_u32(code.parse().ok()?)
+            }
+            #[cfg(feature = "html-entities")]
+            None => entities::get(name),
+            #[cfg(not(feature = "html-entities"))]
+            None => None
+        }
+    }
+}
+fn _html_unescape(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let mut unescaped = String::new();
+        let mut rest = string.as_str();
+        while let Some(start) = rest.find('&') {
+            unescaped.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let entity = rest.find(';').and_then(|end| Some((html_entity(&rest[1..end])?, end)));
+            if let Some((c, end)) = entity {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            } else {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+        unescaped.push_str(rest);
+        program.stack.push(Value::String(unescaped));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn transliterate(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' | 'ě' => "e",
+        'ğ' => "g",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'ı' => "i",
+        'ł' => "l",
+        'ñ' | 'ń' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "o",
+        'œ' => "oe",
+        'ř' => "r",
+        'ś' | 'š' | 'ş' => "s",
+        'ß' => "ss",
+        'ť' | 'ţ' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => "u",
+        'ý' | 'ÿ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None
+    })
+}
+fn _slugify(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let mut slug = String::new();
+        let mut hyphen = false;
+        for c in string.chars().flat_map(char::to_lowercase) {
+            let ascii = if c.is_ascii_alphanumeric() {
+                c.to_string()
+            } else if let Some(ascii) = transliterate(c) {
+                ascii.to_string()
+            } else {
+                if c.is_whitespace() || c.is_ascii_punctuation() { hyphen = true }
+                continue
+            };
+            if hyphen && !slug.is_empty() { slug.push('-') }
+            hyphen = false;
+            slug.push_str(ascii.as_str());
+        }
+        program.stack.push(Value::String(slug));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+const DURATION_UNITS: [(&str, char, i64); 5] = [
+    ("week", 'w', 604800), ("day", 'd', 86400), ("hour", 'h', 3600), ("minute", 'm', 60), ("second", 's', 1)
+];
+
+fn parse_duration(string: &str) -> Option<i64> {
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+    for c in string.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+        } else if c.is_whitespace() {
+            continue
+        } else {
+            let (_, _, unit) = DURATION_UNITS.iter().find(|(_, symbol, _)| *symbol == c)?;
+            seconds = seconds.checked_add(number.parse::<i64>().ok()?.checked_mul(*unit)?)?;
+            number.clear();
+        }
+    }
+    if number.is_empty() { Some(seconds) } else { seconds.checked_add(number.parse().ok()?) }
+}
+fn _parse_duration(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        match parse_duration(string.trim()) {
+            Some(seconds) if !string.trim().is_empty() => {
+                program.stack.push(Value::Int(seconds));
+                program.stack.push(Value::Boolean(true));
+            }
+            _ => program.stack.push(Value::Boolean(false))
+        }
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _humanize_duration(program: &mut Program) -> Result<(), Error> {
+    if let Value::Int(seconds) = program.stack.pop().unwrap() {
+        let mut rest = seconds.unsigned_abs();
+        let mut parts = vec![];
+        for (name, _, unit) in DURATION_UNITS {
+            let count = rest / unit as u64;
+            rest %= unit as u64;
+            if count > 0 {
+                parts.push(format!("{count} {name}{}", if count == 1 { "" } else { "s" }));
+            }
+        }
+        let humanized = if parts.is_empty() { String::from("0 seconds") } else { parts.join(" ") };
+        program.stack.push(Value::String(if seconds < 0 { format!("-{humanized}") } else { humanized }));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+/// the letters of the string in uppercase ASCII, accented letters are transliterated
+fn ascii_letters(string: &str) -> Vec<char> {
+    let mut letters = vec![];
+    for c in string.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphabetic() {
+            letters.push(c.to_ascii_uppercase());
+        } else if let Some(ascii) = transliterate(c) {
+            letters.extend(ascii.chars().map(|c| c.to_ascii_uppercase()));
+        }
+    }
+    letters
+}
+fn soundex_digit(c: char) -> Option<char> {
+    match c {
+        'B' | 'F' | 'P' | 'V' => Some('1'),
+        'C' | 'G' | 'J' | 'K' | 'Q' | 'S' | 'X' | 'Z' => Some('2'),
+        'D' | 'T' => Some('3'),
+        'L' => Some('4'),
+        'M' | 'N' => Some('5'),
+        'R' => Some('6'),
+        _ => None
+    }
+}
+fn soundex(string: &str) -> String {
+    let letters = ascii_letters(string);
+    let Some(first) = letters.first() else { return String::new() };
+    let mut code = first.to_string();
+    let mut last = soundex_digit(*first);
+    for c in letters[1..].iter() {
+        match soundex_digit(*c) {
+            Some(digit) if last != Some(digit) => code.push(digit),
+            _ => {}
+        }
+        // h and w don't separate letters with the same code, vowels do
+        if !matches!(c, 'H' | 'W') {
+            last = soundex_digit(*c);
+        }
+        if code.len() == 4 { break }
+    }
+    format!("{code:0<4}")
+}
+fn _soundex(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        program.stack.push(Value::String(soundex(&string)));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn metaphone(string: &str) -> String {
+    let mut letters = ascii_letters(string);
+    match letters.as_slice() {
+        ['A', 'E', ..] | ['G' | 'K' | 'P', 'N', ..] | ['W', 'R', ..] => { letters.remove(0); }
+        ['X', ..] => letters[0] = 'S',
+        ['W', 'H', ..] => { letters.remove(1); }
+        _ => {}
+    }
+    let at = |idx: usize| letters.get(idx).copied().unwrap_or(' ');
+    let vowel = |c: char| matches!(c, 'A' | 'E' | 'I' | 'O' | 'U');
+    let mut key = String::new();
+    for (idx, c) in letters.iter().copied().enumerate() {
+        let (prev, next, after) = (if idx > 0 { at(idx - 1) } else { ' ' }, at(idx + 1), at(idx + 2));
+        if c == prev && c != 'C' { continue }
+        match c {
+            'A' | 'E' | 'I' | 'O' | 'U' => if idx == 0 { key.push(c) }
+            'B' => if !(prev == 'M' && next == ' ') { key.push('B') }
+            'C' => if next == 'I' && after == 'A' || next == 'H' && prev != 'S' {
+                key.push('X')
+            } else if matches!(next, 'I' | 'E' | 'Y') {
+                if prev != 'S' { key.push('S') }
+            } else {
+                key.push('K')
+            }
+            'D' => key.push(if next == 'G' && matches!(after, 'E' | 'I' | 'Y') { 'J' } else { 'T' }),
+            'G' => if next == 'H' && !(after == ' ' || vowel(after)) || next == 'N' && (after == ' ' || letters[idx + 2..] == ['E', 'D'])
+                || prev == 'D' && matches!(next, 'I' | 'E' | 'Y') {
+                continue
+            } else if matches!(next, 'I' | 'E' | 'Y') && prev != 'G' {
+                key.push('J')
+            } else {
+                key.push('K')
+            }
+            'H' => if vowel(next) && !matches!(prev, 'C' | 'S' | 'P' | 'T' | 'G') { key.push('H') }
+            'K' => if prev != 'C' { key.push('K') }
+            'P' => key.push(if next == 'H' { 'F' } else { 'P' }),
+            'Q' => key.push('K'),
+            'S' => key.push(if next == 'H' || next == 'I' && matches!(after, 'O' | 'A') { 'X' } else { 'S' }),
+            'T' => if next == 'I' && matches!(after, 'O' | 'A') {
+                key.push('X')
+            } else if next == 'H' {
+                key.push('0')
+            } else if !(next == 'C' && after == 'H') {
+                key.push('T')
+            }
+            'V' => key.push('F'),
+            'W' | 'Y' => if vowel(next) { key.push(c) }
+            'X' => key.push_str("KS"),
+            'Z' => key.push('S'),
+            _ => key.push(c)
+        }
+    }
+    key
+}
+fn _metaphone(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        program.stack.push(Value::String(metaphone(&string)));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn jaro(a: &[char], b: &[char]) -> f64 {
+    if a.is_empty() && b.is_empty() { return 1.0 }
+    let window = (a.len().max(b.len()) / 2).saturating_sub(1);
+    let mut b_matched = vec![false; b.len()];
+    let mut a_matches = vec![];
+    for (i, c) in a.iter().enumerate() {
+        let range = i.saturating_sub(window)..(i + window + 1).min(b.len());
+        if let Some(j) = range.into_iter().find(|j| !b_matched[*j] && b[*j] == *c) {
+            b_matched[j] = true;
+            a_matches.push(*c);
+        }
+    }
+    if a_matches.is_empty() { return 0.0 }
+    let b_matches = b.iter().zip(b_matched).filter(|(_, matched)| *matched).map(|(c, _)| *c);
+    let transpositions = a_matches.iter().zip(b_matches).filter(|(x, y)| **x != *y).count() / 2;
+    let m = a_matches.len() as f64;
+    (m / a.len() as f64 + m / b.len() as f64 + (m - transpositions as f64) / m) / 3.0
+}
+fn _jaro_winkler(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(a), Value::String(b)) = (a, b) {
+        let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+        let similarity = jaro(&a, &b);
+        let prefix = a.iter().zip(b.iter()).take(4).take_while(|(x, y)| x == y).count();
+        program.stack.push(Value::Float(similarity + prefix as f64 * 0.1 * (1.0 - similarity)));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn ngrams(string: &str, n: usize) -> Vec<Vec<char>> {
+    let chars: Vec<char> = string.chars().collect();
+    chars.windows(n).map(|gram| gram.to_vec()).collect()
+}
+fn _ngram_sim(program: &mut Program) -> Result<(), Error> {
+    let n = match program.stack.peek() {
+        Some(Value::Int(n)) if *n < 1 => return error_no_pos!("invalid n-gram size {n}"),
+        Some(Value::Int(n)) => {
+            let n = *n as usize;
+            program.stack.pop();
+            n
+        }
+        _ => 2
+    };
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(a), Value::String(b)) = (a, b) {
+        let (a_grams, mut b_grams) = (ngrams(&a, n), ngrams(&b, n));
+        let similarity = if a_grams.is_empty() || b_grams.is_empty() {
+            if a == b { 1.0 } else { 0.0 }
+        } else {
+            let total = a_grams.len() + b_grams.len();
+            let mut common = 0;
+            for gram in a_grams {
+                if let Some(idx) = b_grams.iter().position(|other| *other == gram) {
+                    b_grams.swap_remove(idx);
+                    common += 1;
+                }
+            }
+            2.0 * common as f64 / total as f64
+        };
+        program.stack.push(Value::Float(similarity));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _lcs(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(a), Value::String(b)) = (a, b) {
+        let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+        // lengths[i][j] is the length of the longest common subsequence of a[i..] and b[j..]
+        let mut lengths = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lengths[i][j] = if a[i] == b[j] { lengths[i + 1][j + 1] + 1 } else { lengths[i + 1][j].max(lengths[i][j + 1]) };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        let mut lcs = String::new();
+        while i < a.len() && j < b.len() {
+            if a[i] == b[j] {
+                lcs.push(a[i]);
+                (i, j) = (i + 1, j + 1);
+            } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        program.stack.push(Value::String(lcs));
+        program.stack.push(Value::Int(lengths[0][0] as i64));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _lcsubstring(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(a), Value::String(b)) = (a, b) {
+        let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+        // lengths[j] is the length of the common suffix of a[..i] and b[..j]
+        let mut lengths = vec![0; b.len() + 1];
+        let (mut end, mut len) = (0, 0);
+        for i in 1..=a.len() {
+            for j in (1..=b.len()).rev() {
+                lengths[j] = if a[i - 1] == b[j - 1] { lengths[j - 1] + 1 } else { 0 };
+                if lengths[j] > len {
+                    (end, len) = (i, lengths[j]);
+                }
+            }
+        }
+        program.stack.push(Value::String(a[end - len..end].iter().collect()));
+        program.stack.push(Value::Int(len as i64));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+/// pops the optional flag of `is-palindrome` and `is-anagram`
+fn pop_loose(program: &mut Program) -> bool {
+    if let Some(Value::Boolean(loose)) = program.stack.peek() {
+        let loose = *loose;
+        program.stack.pop();
+        loose
+    } else {
+        false
+    }
+}
+/// the chars that get compared, only the lowercase letters and digits if `loose`
+fn comparable_chars(string: &str, loose: bool) -> Vec<char> {
+    if loose {
+        string.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+    } else {
+        string.chars().collect()
+    }
+}
+fn _is_palindrome(program: &mut Program) -> Result<(), Error> {
+    let loose = pop_loose(program);
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let chars = comparable_chars(&string, loose);
+        program.stack.push(Value::Boolean(chars.iter().eq(chars.iter().rev())));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _is_anagram(program: &mut Program) -> Result<(), Error> {
+    let loose = pop_loose(program);
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(a), Value::String(b)) = (a, b) {
+        let (mut a, mut b) = (comparable_chars(&a, loose), comparable_chars(&b, loose));
+        a.sort_unstable();
+        b.sort_unstable();
+        program.stack.push(Value::Boolean(a == b));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _rle_encode(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let mut encoded = String::new();
+        let mut chars = string.chars().peekable();
+        while let Some(c) = chars.next() {
+            let mut count = 1;
+            while chars.next_if_eq(&c).is_some() {
+                count += 1;
+            }
+            if count > 1 { encoded.push_str(count.to_string().as_str()) }
+            if c.is_ascii_digit() || c == '\\' { encoded.push('\\') }
+            encoded.push(c);
+        }
+        program.stack.push(Value::String(encoded));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+/// the decoded string, `None` if it isn't valid run-length encoding
+fn rle_decode(string: &str) -> Result<Option<String>, Error> {
+    let mut decoded = String::new();
+    let mut chars = string.chars();
+    let mut count = String::new();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            count.push(c);
+            continue
+        }
+        let c = match c {
+            '\\' => match chars.next() {
+                Some(c) => c,
+                None => return Ok(None)
+            }
+            c => c
+        };
+        let times = match count.parse() {
+            Ok(times) => times,
+            Err(_) if count.is_empty() => 1,
+            Err(_) => return error_no_pos!("the run length {count} is too large")
+        };
+        builtins::check_len(times, c.len_utf8())?;
+        builtins::check_len(1, decoded.len() + times * c.len_utf8())?;
+        decoded.push_str(c.to_string().repeat(times).as_str());
+        count.clear();
+    }
+    Ok(count.is_empty().then_some(decoded))
+}
+fn _rle_decode(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        match rle_decode(&string)? {
+            Some(decoded) => {
+                program.stack.push(Value::String(decoded));
+                program.stack.push(Value::Boolean(true));
+            }
+            None => program.stack.push(Value::Boolean(false))
+        }
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+/// expands a char set like `a-z_\t`: `x-y` is a range, `\t`, `\n`, `\r` are escapes and `\` takes the next char literally
+fn char_set(spec: &str) -> Vec<char> {
+    let mut chars = vec![];
+    let mut spec = spec.chars().peekable();
+    while let Some(c) = spec.next() {
+        let c = match c {
+            '\\' => match spec.next() {
+                Some('t') => '\t',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some(c) => c,
+                None => '\\'
+            }
+            c => c
+        };
+        let mut range = spec.clone();
+        match (range.next(), range.next()) {
+            (Some('-'), Some(end)) if end >= c => {
+                chars.extend(c..=end);
+                spec = range;
+            }
+            _ => chars.push(c)
+        }
+    }
+    chars
+}
+fn _squeeze(program: &mut Program) -> Result<(), Error> {
+    let (set, string) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(string), Value::String(set)) = (string, set) {
+        let set = char_set(&set);
+        let mut squeezed = String::new();
+        for c in string.chars() {
+            if squeezed.ends_with(c) && set.contains(&c) { continue }
+            squeezed.push(c);
+        }
+        program.stack.push(Value::String(squeezed));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _delete_chars(program: &mut Program) -> Result<(), Error> {
+    let (set, string) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(string), Value::String(set)) = (string, set) {
+        let set = char_set(&set);
+        program.stack.push(Value::String(string.chars().filter(|c| !set.contains(c)).collect()));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _ordinal(program: &mut Program) -> Result<(), Error> {
+    if let Value::Int(int) = program.stack.pop().unwrap() {
+        let suffix = match (int.unsigned_abs() % 10, int.unsigned_abs() % 100) {
+            (_, 11..=13) => "th",
+            (1, _) => "st",
+            (2, _) => "nd",
+            (3, _) => "rd",
+            _ => "th"
+        };
+        program.stack.push(Value::String(format!("{int}{suffix}")));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+const IRREGULAR_PLURALS: [(&str, &str); 19] = [
+    ("child", "children"), ("person", "people"), ("man", "men"), ("woman", "women"), ("mouse", "mice"),
+    ("goose", "geese"), ("tooth", "teeth"), ("foot", "feet"), ("ox", "oxen"), ("knife", "knives"),
+    ("wife", "wives"), ("life", "lives"), ("leaf", "leaves"), ("half", "halves"), ("wolf", "wolves"),
+    ("shelf", "shelves"), ("loaf", "loaves"), ("calf", "calves"), ("thief", "thieves")
+];
+const UNCOUNTABLE: [&str; 8] = ["sheep", "fish", "deer", "series", "species", "news", "information", "equipment"];
+
+fn pluralize(word: &str) -> String {
+    let lower = word.to_lowercase();
+    let upper = word.chars().any(char::is_alphabetic) && word.chars().all(|c| !c.is_lowercase());
+    if UNCOUNTABLE.contains(&lower.as_str()) { return word.to_string() }
+    if let Some((_, plural)) = IRREGULAR_PLURALS.iter().find(|(singular, _)| *singular == lower) {
+        if upper { return plural.to_uppercase() }
+        return match word.chars().next() {
+            Some(first) if first.is_uppercase() => first.to_string() + &plural[first.len_utf8()..],
+            _ => plural.to_string()
+        }
+    }
+    let consonant_y = lower.ends_with('y') && !lower[..lower.len() - 1].ends_with(['a', 'e', 'i', 'o', 'u']);
+    let (stem, suffix) = if consonant_y && lower.len() > 1 {
+        (&word[..word.len() - 1], "ies")
+    } else if ["s", "x", "z", "ch", "sh"].iter().any(|end| lower.ends_with(end)) {
+        (word, "es")
+    } else {
+        (word, "s")
+    };
+    if upper { stem.to_string() + &suffix.to_uppercase() } else { stem.to_string() + suffix }
+}
+fn _pluralize(program: &mut Program) -> Result<(), Error> {
+    let (count, word) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(word), Value::Int(count)) = (word, count) {
+        program.stack.push(Value::String(if count.unsigned_abs() == 1 || word.is_empty() { word } else { pluralize(&word) }));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _word_count(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        program.stack.push(Value::Int(string.split_whitespace().count() as i64));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _line_count(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        program.stack.push(Value::Int(string.lines().count() as i64));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _char_freq(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let mut counts: HashMap<char, i64> = HashMap::new();
+        for c in string.chars() {
+            *counts.entry(c).or_insert(0) += 1;
+        }
+        let mut counts: Vec<(char, i64)> = counts.into_iter().collect();
+        counts.sort_by(|(c1, n1), (c2, n2)| n2.cmp(n1).then(c1.cmp(c2)));
+        let len = counts.len();
+        for (c, count) in counts {
+            program.stack.push(Value::Char(c));
+            program.stack.push(Value::Int(count));
+        }
+        program.stack.push(Value::Int(len as i64));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _unpack(program: &mut Program) -> Result<(), Error> {
+    let (spec, line) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(line), Value::String(spec)) = (line, spec) {
+        let mut widths = vec![];
+        for width in spec.split_whitespace() {
+            match width.parse::<usize>() {
+                Ok(width) => widths.push(width),
+                Err(_) => return error_no_pos!("invalid field width {width:?}")
+            }
+        }
+        let mut chars = line.chars();
+        for width in widths.iter() {
+            let field: String = chars.by_ref().take(*width).collect();
+            program.stack.push(Value::String(field.trim().to_string()));
+        }
+        program.stack.push(Value::Int(widths.len() as i64));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn env_var(name: &str) -> Option<String> {
+    env::var_os(name).map(|value| value.to_string_lossy().to_string())
+}
+fn _expand_env(program: &mut Program) -> Result<(), Error> {
+    if !program.allow_env {
+        return error_no_pos!("reading environment variables is not allowed")
+    }
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let mut expanded = String::new();
+        let mut rest = string.as_str();
+        while let Some(idx) = rest.find('$') {
+            expanded.push_str(&rest[..idx]);
+            rest = &rest[idx + 1..];
+            if let Some(after) = rest.strip_prefix('$') {
+                expanded.push('$');
+                rest = after;
+            } else if let Some((reference, after)) = rest.strip_prefix('{').and_then(|rest| rest.split_once('}')) {
+                let (name, default) = match reference.split_once(":-") {
+                    Some((name, default)) => (name, Some(default)),
+                    None => (reference, None)
+                };
+                match env_var(name).filter(|value| !value.is_empty()) {
+                    Some(value) => expanded.push_str(&value),
+                    None => expanded.push_str(default.unwrap_or(""))
+                }
+                rest = after;
+            } else {
+                let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+                if len == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+                    expanded.push('$');
+                    continue
+                }
+                expanded.push_str(&env_var(&rest[..len]).unwrap_or_default());
+                rest = &rest[len..];
+            }
+        }
+        expanded.push_str(rest);
+        program.stack.push(Value::String(expanded));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+/// matches the class starting after a `[` against `c`, pushing the length of the class including the `]`
+fn glob_class(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let negated = matches!(pattern.first(), Some('!' | '^'));
+    let mut idx = negated as usize;
+    let mut matched = false;
+    // a `]` right after the `[` is part of the class
+    let start = idx;
+    while idx < pattern.len() && (pattern[idx] != ']' || idx == start) {
+        if pattern.get(idx + 1) == Some(&'-') && pattern.get(idx + 2).is_some_and(|end| *end != ']') {
+            matched |= (pattern[idx]..=pattern[idx + 2]).contains(&c);
+            idx += 3;
+        } else {
+            matched |= pattern[idx] == c;
+            idx += 1;
+        }
+    }
+    (idx < pattern.len()).then_some((matched != negated, idx + 1))
+}
+fn glob_match(pattern: &[char], string: &[char]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // where to retry from when the last `*` has to match one more char
+    let mut backtrack = None;
+    while s < string.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, s));
+                p += 1;
+                continue
+            }
+            Some('?') => Some(1),
+            Some('[') => match glob_class(&pattern[p + 1..], string[s]) {
+                Some((true, len)) => Some(len + 1),
+                Some((false, _)) => None,
+                None => (string[s] == '[').then_some(1)
+            }
+            Some('\\') if p + 1 < pattern.len() => (pattern[p + 1] == string[s]).then_some(2),
+            Some(c) => (*c == string[s]).then_some(1),
+            None => None
+        };
+        match (step, backtrack) {
+            (Some(step), _) => { p += step; s += 1 }
+            (None, Some((star, from))) => {
+                backtrack = Some((star, from + 1));
+                (p, s) = (star + 1, from + 1);
+            }
+            (None, None) => return false
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+fn _glob_match(program: &mut Program) -> Result<(), Error> {
+    let (pattern, string) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(string), Value::String(pattern)) = (string, pattern) {
+        let (string, pattern): (Vec<char>, Vec<char>) = (string.chars().collect(), pattern.chars().collect());
+        program.stack.push(Value::Boolean(glob_match(&pattern, &string)));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _parse_ip(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        match string.trim().parse::<IpAddr>() {
+            Ok(ip) => {
+                program.stack.push(Value::String(ip.to_string()));
+                program.stack.push(Value::Boolean(true));
+            }
+            Err(_) => program.stack.push(Value::Boolean(false))
+        }
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+/// pops up to `max` chars, the optional arguments of the CSV builtins, in the order they were pushed
+fn pop_chars(program: &mut Program, max: usize) -> Vec<char> {
+    let mut chars = vec![];
+    while chars.len() < max {
+        let Some(Value::Char(c)) = program.stack.peek() else { break };
+        chars.insert(0, *c);
+        program.stack.pop();
+    }
+    chars
+}
+/// the fields of a CSV record, a doubled quote inside quotes is a literal quote
+fn csv_fields(record: &str, delimiter: char, quote: char, escape: Option<char>) -> Option<Vec<String>> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = record.chars().peekable();
+    while let Some(c) = chars.next() {
+        if Some(c) == escape && quoted {
+            field.push(chars.next()?);
+        } else if c == quote && quoted {
+            if chars.next_if_eq(&quote).is_some() { field.push(quote) } else { quoted = false }
+        } else if c == quote && field.is_empty() {
+            quoted = true;
+        } else if c == delimiter && !quoted {
+            fields.push(std::mem::take(&mut field));
+        } else {
+            field.push(c);
+        }
+    }
+    if quoted { return None }
+    fields.push(field);
+    Some(fields)
+}
+fn _csv_fields(program: &mut Program) -> Result<(), Error> {
+    let chars = pop_chars(program, 3);
+    if let Value::String(record) = program.stack.pop().unwrap() {
+        let (delimiter, quote) = (*chars.first().unwrap_or(&','), *chars.get(1).unwrap_or(&'"'));
+        let Some(fields) = csv_fields(record.trim_end_matches(['\r', '\n']), delimiter, quote, chars.get(2).copied()) else {
+            return error_no_pos!("unclosed quote in CSV record {record:?}")
+        };
+        let len = fields.len();
+        for field in fields {
+            program.stack.push(Value::String(field));
+        }
+        program.stack.push(Value::Int(len as i64));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _csv_records(program: &mut Program) -> Result<(), Error> {
+    let chars = pop_chars(program, 2);
+    if let Value::String(text) = program.stack.pop().unwrap() {
+        let (quote, escape) = (*chars.first().unwrap_or(&'"'), chars.get(1).copied());
+        let mut records = vec![];
+        let mut record = String::new();
+        let mut quoted = false;
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if Some(c) == escape && quoted {
+                record.push(c);
+                record.extend(chars.next());
+                continue
+            }
+            if c == quote { quoted = !quoted }
+            if c == '\n' && !quoted {
+                if record.ends_with('\r') { record.pop(); }
+                records.push(std::mem::take(&mut record));
+            } else {
+                record.push(c);
+            }
+        }
+        if quoted {
+            return error_no_pos!("unclosed quote in CSV text")
+        }
+        if !record.is_empty() { records.push(record) }
+        let len = records.len();
+        for record in records {
+            program.stack.push(Value::String(record));
+        }
+        program.stack.push(Value::Int(len as i64));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+/// Latin letters followed by a combining accent and the precomposed char, sorted for binary search
+const COMPOSITIONS: [(char, char, char); 233] = [
+    ('A', '\u{300}', 'À'), ('A', '\u{301}', 'Á'), ('A', '\u{302}', 'Â'), ('A', '\u{303}', 'Ã'), ('A', '\u{304}', 'Ā'), ('A', '\u{306}', 'Ă'),
+    ('A', '\u{307}', 'Ȧ'), ('A', '\u{308}', 'Ä'), ('A', '\u{30a}', 'Å'), ('A', '\u{30c}', 'Ǎ'), ('A', '\u{328}', 'Ą'), ('C', '\u{301}', 'Ć'),
+    ('C', '\u{302}', 'Ĉ'), ('C', '\u{307}', 'Ċ'), ('C', '\u{30c}', 'Č'), ('C', '\u{327}', 'Ç'), ('D', '\u{307}', 'Ḋ'), ('D', '\u{30c}', 'Ď'),
+    ('D', '\u{327}', 'Ḑ'), ('E', '\u{300}', 'È'), ('E', '\u{301}', 'É'), ('E', '\u{302}', 'Ê'), ('E', '\u{303}', 'Ẽ'), ('E', '\u{304}', 'Ē'),
+    ('E', '\u{306}', 'Ĕ'), ('E', '\u{307}', 'Ė'), ('E', '\u{308}', 'Ë'), ('E', '\u{30c}', 'Ě'), ('E', '\u{327}', 'Ȩ'), ('E', '\u{328}', 'Ę'),
+    ('G', '\u{301}', 'Ǵ'), ('G', '\u{302}', 'Ĝ'), ('G', '\u{304}', 'Ḡ'), ('G', '\u{306}', 'Ğ'), ('G', '\u{307}', 'Ġ'), ('G', '\u{30c}', 'Ǧ'),
+    ('G', '\u{327}', 'Ģ'), ('H', '\u{302}', 'Ĥ'), ('H', '\u{307}', 'Ḣ'), ('H', '\u{308}', 'Ḧ'), ('H', '\u{30c}', 'Ȟ'), ('H', '\u{327}', 'Ḩ'),
+    ('I', '\u{300}', 'Ì'), ('I', '\u{301}', 'Í'), ('I', '\u{302}', 'Î'), ('I', '\u{303}', 'Ĩ'), ('I', '\u{304}', 'Ī'), ('I', '\u{306}', 'Ĭ'),
+    ('I', '\u{307}', 'İ'), ('I', '\u{308}', 'Ï'), ('I', '\u{30c}', 'Ǐ'), ('I', '\u{328}', 'Į'), ('J', '\u{302}', 'Ĵ'), ('K', '\u{301}', 'Ḱ'),
+    ('K', '\u{30c}', 'Ǩ'), ('K', '\u{327}', 'Ķ'), ('L', '\u{301}', 'Ĺ'), ('L', '\u{30c}', 'Ľ'), ('L', '\u{327}', 'Ļ'), ('N', '\u{300}', 'Ǹ'),
+    ('N', '\u{301}', 'Ń'), ('N', '\u{303}', 'Ñ'), ('N', '\u{307}', 'Ṅ'), ('N', '\u{30c}', 'Ň'), ('N', '\u{327}', 'Ņ'), ('O', '\u{300}', 'Ò'),
+    ('O', '\u{301}', 'Ó'), ('O', '\u{302}', 'Ô'), ('O', '\u{303}', 'Õ'), ('O', '\u{304}', 'Ō'), ('O', '\u{306}', 'Ŏ'), ('O', '\u{307}', 'Ȯ'),
+    ('O', '\u{308}', 'Ö'), ('O', '\u{30b}', 'Ő'), ('O', '\u{30c}', 'Ǒ'), ('O', '\u{328}', 'Ǫ'), ('R', '\u{301}', 'Ŕ'), ('R', '\u{307}', 'Ṙ'),
+    ('R', '\u{30c}', 'Ř'), ('R', '\u{327}', 'Ŗ'), ('S', '\u{301}', 'Ś'), ('S', '\u{302}', 'Ŝ'), ('S', '\u{307}', 'Ṡ'), ('S', '\u{30c}', 'Š'),
+    ('S', '\u{327}', 'Ş'), ('T', '\u{307}', 'Ṫ'), ('T', '\u{30c}', 'Ť'), ('T', '\u{327}', 'Ţ'), ('U', '\u{300}', 'Ù'), ('U', '\u{301}', 'Ú'),
+    ('U', '\u{302}', 'Û'), ('U', '\u{303}', 'Ũ'), ('U', '\u{304}', 'Ū'), ('U', '\u{306}', 'Ŭ'), ('U', '\u{308}', 'Ü'), ('U', '\u{30a}', 'Ů'),
+    ('U', '\u{30b}', 'Ű'), ('U', '\u{30c}', 'Ǔ'), ('U', '\u{328}', 'Ų'), ('W', '\u{300}', 'Ẁ'), ('W', '\u{301}', 'Ẃ'), ('W', '\u{302}', 'Ŵ'),
+    ('W', '\u{307}', 'Ẇ'), ('W', '\u{308}', 'Ẅ'), ('Y', '\u{300}', 'Ỳ'), ('Y', '\u{301}', 'Ý'), ('Y', '\u{302}', 'Ŷ'), ('Y', '\u{303}', 'Ỹ'),
+    ('Y', '\u{304}', 'Ȳ'), ('Y', '\u{307}', 'Ẏ'), ('Y', '\u{308}', 'Ÿ'), ('Z', '\u{301}', 'Ź'), ('Z', '\u{302}', 'Ẑ'), ('Z', '\u{307}', 'Ż'),
+    ('Z', '\u{30c}', 'Ž'), ('a', '\u{300}', 'à'), ('a', '\u{301}', 'á'), ('a', '\u{302}', 'â'), ('a', '\u{303}', 'ã'), ('a', '\u{304}', 'ā'),
+    ('a', '\u{306}', 'ă'), ('a', '\u{307}', 'ȧ'), ('a', '\u{308}', 'ä'), ('a', '\u{30a}', 'å'), ('a', '\u{30c}', 'ǎ'), ('a', '\u{328}', 'ą'),
+    ('c', '\u{301}', 'ć'), ('c', '\u{302}', 'ĉ'), ('c', '\u{307}', 'ċ'), ('c', '\u{30c}', 'č'), ('c', '\u{327}', 'ç'), ('d', '\u{307}', 'ḋ'),
+    ('d', '\u{30c}', 'ď'), ('d', '\u{327}', 'ḑ'), ('e', '\u{300}', 'è'), ('e', '\u{301}', 'é'), ('e', '\u{302}', 'ê'), ('e', '\u{303}', 'ẽ'),
+    ('e', '\u{304}', 'ē'), ('e', '\u{306}', 'ĕ'), ('e', '\u{307}', 'ė'), ('e', '\u{308}', 'ë'), ('e', '\u{30c}', 'ě'), ('e', '\u{327}', 'ȩ'),
+    ('e', '\u{328}', 'ę'), ('g', '\u{301}', 'ǵ'), ('g', '\u{302}', 'ĝ'), ('g', '\u{304}', 'ḡ'), ('g', '\u{306}', 'ğ'), ('g', '\u{307}', 'ġ'),
+    ('g', '\u{30c}', 'ǧ'), ('g', '\u{327}', 'ģ'), ('h', '\u{302}', 'ĥ'), ('h', '\u{307}', 'ḣ'), ('h', '\u{308}', 'ḧ'), ('h', '\u{30c}', 'ȟ'),
+    ('h', '\u{327}', 'ḩ'), ('i', '\u{300}', 'ì'), ('i', '\u{301}', 'í'), ('i', '\u{302}', 'î'), ('i', '\u{303}', 'ĩ'), ('i', '\u{304}', 'ī'),
+    ('i', '\u{306}', 'ĭ'), ('i', '\u{308}', 'ï'), ('i', '\u{30c}', 'ǐ'), ('i', '\u{328}', 'į'), ('j', '\u{302}', 'ĵ'), ('j', '\u{30c}', 'ǰ'),
+    ('k', '\u{301}', 'ḱ'), ('k', '\u{30c}', 'ǩ'), ('k', '\u{327}', 'ķ'), ('l', '\u{301}', 'ĺ'), ('l', '\u{30c}', 'ľ'), ('l', '\u{327}', 'ļ'),
+    ('n', '\u{300}', 'ǹ'), ('n', '\u{301}', 'ń'), ('n', '\u{303}', 'ñ'), ('n', '\u{307}', 'ṅ'), ('n', '\u{30c}', 'ň'), ('n', '\u{327}', 'ņ'),
+    ('o', '\u{300}', 'ò'), ('o', '\u{301}', 'ó'), ('o', '\u{302}', 'ô'), ('o', '\u{303}', 'õ'), ('o', '\u{304}', 'ō'), ('o', '\u{306}', 'ŏ'),
+    ('o', '\u{307}', 'ȯ'), ('o', '\u{308}', 'ö'), ('o', '\u{30b}', 'ő'), ('o', '\u{30c}', 'ǒ'), ('o', '\u{328}', 'ǫ'), ('r', '\u{301}', 'ŕ'),
+    ('r', '\u{307}', 'ṙ'), ('r', '\u{30c}', 'ř'), ('r', '\u{327}', 'ŗ'), ('s', '\u{301}', 'ś'), ('s', '\u{302}', 'ŝ'), ('s', '\u{307}', 'ṡ'),
+    ('s', '\u{30c}', 'š'), ('s', '\u{327}', 'ş'), ('t', '\u{307}', 'ṫ'), ('t', '\u{308}', 'ẗ'), ('t', '\u{30c}', 'ť'), ('t', '\u{327}', 'ţ'),
+    ('u', '\u{300}', 'ù'), ('u', '\u{301}', 'ú'), ('u', '\u{302}', 'û'), ('u', '\u{303}', 'ũ'), ('u', '\u{304}', 'ū'), ('u', '\u{306}', 'ŭ'),
+    ('u', '\u{308}', 'ü'), ('u', '\u{30a}', 'ů'), ('u', '\u{30b}', 'ű'), ('u', '\u{30c}', 'ǔ'), ('u', '\u{328}', 'ų'), ('w', '\u{300}', 'ẁ'),
+    ('w', '\u{301}', 'ẃ'), ('w', '\u{302}', 'ŵ'), ('w', '\u{307}', 'ẇ'), ('w', '\u{308}', 'ẅ'), ('w', '\u{30a}', 'ẘ'), ('y', '\u{300}', 'ỳ'),
+    ('y', '\u{301}', 'ý'), ('y', '\u{302}', 'ŷ'), ('y', '\u{303}', 'ỹ'), ('y', '\u{304}', 'ȳ'), ('y', '\u{307}', 'ẏ'), ('y', '\u{308}', 'ÿ'),
+    ('y', '\u{30a}', 'ẙ'), ('z', '\u{301}', 'ź'), ('z', '\u{302}', 'ẑ'), ('z', '\u{307}', 'ż'), ('z', '\u{30c}', 'ž')
+];
+
+/// canonical composition for the Latin letters with common accents, a subset of NFC
+fn compose(string: &str) -> String {
+    let mut composed = String::new();
+    for c in string.chars() {
+        if let Some(base) = composed.chars().last() {
+            if let Ok(idx) = COMPOSITIONS.binary_search_by(|(b, mark, _)| (*b, *mark).cmp(&(base, c))) {
+                composed.pop();
+                composed.push(COMPOSITIONS[idx].2);
+                continue
+            }
+        }
+        composed.push(c);
+    }
+    composed
+}
+fn _clean(program: &mut Program) -> Result<(), Error> {
+    let (flags, string) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    if let (Value::String(mut string), Value::String(flags)) = (string, flags) {
+        for flag in flags.chars() {
+            string = match flag {
+                't' => string.trim().to_string(),
+                'w' => string.split_whitespace().collect::<Vec<&str>>().join(" "),
+                'n' => compose(&string),
+                'l' => string.to_lowercase(),
+                _ => return error_no_pos!("unknown clean flag {flag:?}, expected t, w, n or l")
+            }
+        }
+        program.stack.push(Value::String(string));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+const MORSE: [(char, &str); 54] = [
+    ('a', ".-"), ('b', "-..."), ('c', "-.-."), ('d', "-.."), ('e', "."), ('f', "..-."), ('g', "--."), ('h', "...."),
+    ('i', ".."), ('j', ".---"), ('k', "-.-"), ('l', ".-.."), ('m', "--"), ('n', "-."), ('o', "---"), ('p', ".--."),
+    ('q', "--.-"), ('r', ".-."), ('s', "..."), ('t', "-"), ('u', "..-"), ('v', "...-"), ('w', ".--"), ('x', "-..-"),
+    ('y', "-.--"), ('z', "--.."), ('0', "-----"), ('1', ".----"), ('2', "..---"), ('3', "...--"), ('4', "....-"),
+    ('5', "....."), ('6', "-...."), ('7', "--..."), ('8', "---.."), ('9', "----."), ('.', ".-.-.-"), (',', "--..--"),
+    ('?', "..--.."), ('\'', ".----."), ('!', "-.-.--"), ('/', "-..-."), ('(', "-.--."), (')', "-.--.-"), ('&', ".-..."),
+    (':', "---..."), (';', "-.-.-."), ('=', "-...-"), ('+', ".-.-."), ('-', "-....-"), ('_', "..--.-"), ('"', ".-..-."),
+    ('$', "...-..-"), ('@', ".--.-.")
+];
+fn _to_morse(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let mut words = vec![];
+        for word in string.split_whitespace() {
+            let mut codes = vec![];
+            for c in word.chars().flat_map(char::to_lowercase) {
+                match MORSE.iter().find(|(letter, _)| *letter == c) {
+                    Some((_, code)) => codes.push(*code),
+                    None => return error_no_pos!("{c:?} has no Morse code")
+                }
+            }
+            words.push(codes.join(" "));
+        }
+        program.stack.push(Value::String(words.join(" / ")));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn from_morse(string: &str) -> Option<String> {
+    let mut words = vec![];
+    for word in string.split('/') {
+        let mut letters = String::new();
+        for code in word.split_whitespace() {
+            letters.push(MORSE.iter().find(|(_, other)| *other == code)?.0);
+        }
+        words.push(letters);
+    }
+    Some(words.join(" ").trim().to_string())
+}
+fn _from_morse(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        match from_morse(&string) {
+            Some(decoded) => {
+                program.stack.push(Value::String(decoded));
+                program.stack.push(Value::Boolean(true));
+            }
+            None => program.stack.push(Value::Boolean(false))
+        }
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+const NATO: [&str; 36] = [
+    "Alfa", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel", "India", "Juliett", "Kilo", "Lima",
+    "Mike", "November", "Oscar", "Papa", "Quebec", "Romeo", "Sierra", "Tango", "Uniform", "Victor", "Whiskey",
+    "X-ray", "Yankee", "Zulu", "Zero", "One", "Two", "Three", "Four", "Five", "Six", "Seven", "Eight", "Nine"
+];
+fn _to_nato(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let words: Vec<String> = string.chars().filter(|c| !c.is_whitespace()).map(|c| match c.to_ascii_lowercase() {
+            c @ 'a'..='z' => NATO[(c as u8 - b'a') as usize].to_string(),
+            c @ '0'..='9' => NATO[26 + (c as u8 - b'0') as usize].to_string(),
+            c => c.to_string()
+        }).collect();
+        program.stack.push(Value::String(words.join(" ")));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _to_leet(program: &mut Program) -> Result<(), Error> {
+    if let Value::String(string) = program.stack.pop().unwrap() {
+        let leet = string.chars().map(|c| match c.to_ascii_lowercase() {
+            'a' => '4', 'b' => '8', 'e' => '3', 'g' => '9', 'i' | 'l' => '1', 'o' => '0', 's' => '5', 't' => '7',
+            _ => c
+        }).collect();
+        program.stack.push(Value::String(leet));
+        Ok(())
+    } else {
+        panic!("type checking error!!!")
+    }
+}
+fn _bar_chart(program: &mut Program) -> Result<(), Error> {
+    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
+    let (Value::Int(count), Value::Int(width)) = (a, b) else { panic!("type checking error!!!") };
+    let count = count.max(0) as usize;
+    if count > program.stack.len() / 2 {
+        return error_no_pos!("cannot chart {count} pairs, the stack only has {} values", program.stack.len())
+    }
+    let mut rows = vec![];
+    for pair in program.stack.as_slice()[program.stack.len() - count * 2..].chunks(2) {
+        let (label, value) = (pair[0].to_string(), &pair[1]);
+        let number = match value {
+            Value::Int(int) => *int as f64,
+            Value::Float(float) => *float,
+            value => return error_no_pos!("expected a number for the bar {label:?}, got {}", value.typ())
+        };
+        if !number.is_finite() {
+            return error_no_pos!("cannot chart the value {value} of {label:?}, bars need a finite number")
+        }
+        if number < 0.0 {
+            return error_no_pos!("cannot chart the negative value {value} of {label:?}")
+        }
+        rows.push((label, value.to_string(), number));
+    }
+    program.stack.truncate(program.stack.len() - count * 2);
+    let label_width = rows.iter().map(|(label, _, _)| label.chars().count()).max().unwrap_or(0);
+    let max = rows.iter().map(|(_, _, number)| *number).fold(0.0, f64::max);
+    let mut chart = vec![];
+    for (label, value, number) in rows {
+        let len = if max > 0.0 { (number / max * width.max(0) as f64).round() as usize } else { 0 };
+        chart.push(format!("{label:<label_width$} | {} {value}", "#".repeat(len)));
+    }
+    program.stack.push(Value::String(chart.join("\n")));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run::Program;
+    use crate::run::tests::{run, run_in, stack};
+    use crate::value::Value;
+
+    fn string(string: &str) -> Value { Value::String(string.to_string()) }
+
+    #[test]
+    fn rle_round_trip() {
+        for text in ["aaab", "", "abc", "1112223", "\\\\\\x", "ééé日日"] {
+            assert_eq!(stack(&format!("{text:?} rle-encode rle-decode")).unwrap(), vec![string(text), Value::Boolean(true)]);
+        }
+        assert_eq!(stack(r#""aaab" rle-encode"#).unwrap(), vec![string("3ab")]);
+        assert_eq!(stack(r#""112" rle-encode"#).unwrap(), vec![string("2\\1\\2")]);
+    }
+    #[test]
+    fn rle_decode_invalid() {
+        assert_eq!(stack(r#""3" rle-decode"#).unwrap(), vec![Value::Boolean(false)]);
+        assert_eq!(stack(r#""a\\" rle-decode"#).unwrap(), vec![Value::Boolean(false)]);
+    }
+    #[test]
+    fn rle_decode_too_long() {
+        assert!(stack(r#""18446744073709551615a" rle-decode"#).is_err());
+        assert!(stack(r#""99999999999999999999999a" rle-decode"#).is_err());
+    }
+    /// the strings followed by their count, like the CSV builtins push them
+    fn counted(strings: &[&str]) -> Vec<Value> {
+        let mut values: Vec<Value> = strings.iter().map(|text| string(text)).collect();
+        values.push(Value::Int(strings.len() as i64));
+        values
+    }
+    #[test]
+    fn csv_fields_options() {
+        assert_eq!(stack(r#""a,\"b,c\",\"d\"\"e\"" csv-fields"#).unwrap(), counted(&["a", "b,c", "d\"e"]));
+        assert_eq!(stack(r#""a;'b;c';'d''e'" ';' '\'' csv-fields"#).unwrap(), counted(&["a", "b;c", "d'e"]));
+        assert_eq!(stack(r#""a|\"b\\\"c\"" '|' '"' '\\' csv-fields"#).unwrap(), counted(&["a", "b\"c"]));
+        assert!(stack(r#""a,\"b" csv-fields"#).is_err());
+    }
+    #[test]
+    fn csv_records_options() {
+        assert_eq!(stack("\"a,b\r\n\\\"c\nd\\\",e\n\" csv-records").unwrap(), counted(&["a,b", "\"c\nd\",e"]));
+        assert_eq!(stack("\"'x\ny'\nz\" '\\'' csv-records").unwrap(), counted(&["'x\ny'", "z"]));
+        assert_eq!(stack("\"\\\"a\\\\\\\"\nb\\\"\" '\"' '\\\\' csv-records").unwrap(), counted(&["\"a\\\"\nb\""]));
+        assert!(stack("\"\\\"a\nb\" csv-records").is_err());
+    }
+    #[test]
+    fn clean_flags() {
+        assert_eq!(stack("\"  a  b \" \"t\" clean").unwrap(), vec![string("a  b")]);
+        assert_eq!(stack("\" a \n\t b \" \"w\" clean").unwrap(), vec![string("a b")]);
+        assert_eq!(stack("\"e\u{301}A\u{30a}n\u{303}\" \"n\" clean").unwrap(), vec![string("éÅñ")]);
+        assert_eq!(stack("\"ÉCOLE\" \"l\" clean").unwrap(), vec![string("école")]);
+        // only Latin letters are composed, other marks stay combining
+        assert_eq!(stack("\"\u{3b1}\u{301}\" \"n\" clean").unwrap(), vec![string("\u{3b1}\u{301}")]);
+        assert_eq!(stack("\"  A \" \"\" clean").unwrap(), vec![string("  A ")]);
+        assert!(stack("\"a\" \"tx\" clean").is_err());
+    }
+    #[test]
+    fn clean_flag_order() {
+        assert_eq!(stack("\" E\u{301}  B \" \"twnl\" clean").unwrap(), vec![string("é b")]);
+        assert_eq!(stack("\" E\u{301}  B \" \"lnwt\" clean").unwrap(), vec![string("é b")]);
+        // there is a lowercase ẗ but no uppercase one, so only lowercasing first composes it
+        assert_eq!(stack("\"T\u{308}\" \"ln\" clean").unwrap(), vec![string("ẗ")]);
+        assert_eq!(stack("\"T\u{308}\" \"nl\" clean").unwrap(), vec![string("t\u{308}")]);
+    }
+    #[test]
+    fn bar_chart() {
+        assert_eq!(stack(r#""a" 2 "bb" 4 2 4 bar-chart"#).unwrap(), vec![string("a  | ## 2\nbb | #### 4")]);
+        assert_eq!(stack(r#""a" 0 1 4 bar-chart"#).unwrap(), vec![string("a |  0")]);
+    }
+    #[test]
+    fn bar_chart_invalid_values() {
+        let error = stack(r#""a" 1 "b" nan 2 4 bar-chart"#).err().unwrap();
+        assert_eq!(error.msg(), "cannot chart the value NaN of \"b\", bars need a finite number");
+        assert!(stack(r#""a" inf 1 4 bar-chart"#).is_err());
+        let error = stack(r#""a" -1 "b" 1 2 4 bar-chart"#).err().unwrap();
+        assert_eq!(error.msg(), "cannot chart the negative value -1 of \"a\"");
+        assert!(stack(r#""a" "b" 1 4 bar-chart"#).is_err());
+        assert!(stack(r#""a" 1 2 4 bar-chart"#).is_err());
+    }
+    #[test]
+    fn detab_and_entab() {
+        assert_eq!(stack("\"a\tb\" detab").unwrap(), vec![string("a   b")]);
+        assert_eq!(stack("\"ab\tc\" 8 detab").unwrap(), vec![string("ab      c")]);
+        assert_eq!(stack("\"        a\" entab").unwrap(), vec![string("\t\ta")]);
+        // two spaces that don't reach a tab stop stay spaces
+        assert_eq!(stack("\"a  b\" entab").unwrap(), vec![string("a  b")]);
+    }
+    #[test]
+    fn indent_and_dedent() {
+        assert_eq!(stack("\"a\n\nb\" 2 indent").unwrap(), vec![string("  a\n\n  b")]);
+        assert_eq!(stack("\"a\nb\" \"> \" indent").unwrap(), vec![string("> a\n> b")]);
+        assert_eq!(stack("\"  a\n    b\n\" dedent").unwrap(), vec![string("a\n  b\n")]);
+        assert_eq!(stack("\"a\n  b\" dedent").unwrap(), vec![string("a\n  b")]);
+    }
+    #[test]
+    fn strip_ansi() {
+        assert_eq!(stack("\"\x1b[1;31mbold\x1b[0m \u{9b}4mline\" strip-ansi").unwrap(), vec![string("bold line")]);
+        assert_eq!(stack("\"plain [0m\" strip-ansi").unwrap(), vec![string("plain [0m")]);
+        // an unfinished sequence at the end is dropped
+        assert_eq!(stack("\"a\x1b[31\" strip-ansi").unwrap(), vec![string("a")]);
+    }
+    #[test]
+    fn sh_quote() {
+        assert_eq!(stack(r#""file-1.txt" sh-quote"#).unwrap(), vec![string("file-1.txt")]);
+        assert_eq!(stack(r#""a b" sh-quote"#).unwrap(), vec![string("'a b'")]);
+        assert_eq!(stack(r#""it's" sh-quote"#).unwrap(), vec![string(r#"'it'"'"'s'"#)]);
+        assert_eq!(stack(r#""" sh-quote"#).unwrap(), vec![string("''")]);
+    }
+    #[test]
+    fn html_escape_round_trip() {
+        assert_eq!(stack(r#""<a href=\"x\">&'</a>" html-escape"#).unwrap(), vec![string("&lt;a href=&quot;x&quot;&gt;&amp;&#39;&lt;/a&gt;")]);
+        assert_eq!(stack(r#""<a href=\"x\">&'</a>" copy html-escape html-unescape ="#).unwrap(), vec![Value::Boolean(true)]);
+        assert_eq!(stack(r#""&#65;&#x42;&lt;" html-unescape"#).unwrap(), vec![string("AB<")]);
+        // unknown entities and invalid references are kept as they are
+        assert_eq!(stack(r#""&bogus; &#xZZ; &#1114112; & x" html-unescape"#).unwrap(), vec![string("&bogus; &#xZZ; &#1114112; & x")]);
+    }
+    #[cfg(feature = "html-entities")]
+    #[test]
+    fn html_unescape_named_entities() {
+        assert_eq!(stack(r#""&euml;&nbsp;&copy;&amp;" html-unescape"#).unwrap(), vec![string("ë\u{a0}©&")]);
+        assert_eq!(stack(r#""&notanentity;" html-unescape"#).unwrap(), vec![string("&notanentity;")]);
+    }
+    #[cfg(not(feature = "html-entities"))]
+    #[test]
+    fn html_unescape_only_basic_entities() {
+        assert_eq!(stack(r#""&euml;&amp;" html-unescape"#).unwrap(), vec![string("&euml;&")]);
+    }
+    #[test]
+    fn slugify() {
+        assert_eq!(stack(r#""Hello, World!" slugify"#).unwrap(), vec![string("hello-world")]);
+        assert_eq!(stack(r#""  Crème Brûlée  " slugify"#).unwrap(), vec![string("creme-brulee")]);
+        assert_eq!(stack(r#""--a__b--" slugify"#).unwrap(), vec![string("a-b")]);
+        // nothing to keep leaves an empty slug
+        assert_eq!(stack(r#""日本 !!" slugify"#).unwrap(), vec![string("")]);
+    }
+    #[test]
+    fn parse_duration() {
+        assert_eq!(stack(r#""1h30m" parse-duration"#).unwrap(), vec![Value::Int(5400), Value::Boolean(true)]);
+        assert_eq!(stack(r#""90s" parse-duration"#).unwrap(), vec![Value::Int(90), Value::Boolean(true)]);
+        assert_eq!(stack(r#""2d 3h" parse-duration"#).unwrap(), vec![Value::Int(183600), Value::Boolean(true)]);
+        // a number without a unit counts as seconds
+        assert_eq!(stack(r#""5" parse-duration"#).unwrap(), vec![Value::Int(5), Value::Boolean(true)]);
+        for invalid in ["1x", "", "h"] {
+            assert_eq!(stack(&format!("{invalid:?} parse-duration")).unwrap(), vec![Value::Boolean(false)], "{invalid:?}");
+        }
+    }
+    #[test]
+    fn humanize_duration() {
+        assert_eq!(stack("5400 humanize-duration").unwrap(), vec![string("1 hour 30 minutes")]);
+        assert_eq!(stack("90061 humanize-duration").unwrap(), vec![string("1 day 1 hour 1 minute 1 second")]);
+        assert_eq!(stack("0 humanize-duration").unwrap(), vec![string("0 seconds")]);
+        assert_eq!(stack("-5 humanize-duration").unwrap(), vec![string("-5 seconds")]);
+        assert_eq!(stack(r#""1h30m" parse-duration drop humanize-duration"#).unwrap(), vec![string("1 hour 30 minutes")]);
+    }
+
+    #[test]
+    fn soundex() {
+        assert_eq!(stack("\"Smith\" soundex \"Smyth\" soundex").unwrap(), vec![string("S530"), string("S530")]);
+        assert_eq!(stack("\"Robert\" soundex \"Rupert\" soundex").unwrap(), vec![string("R163"), string("R163")]);
+        assert_eq!(stack("\"Tymczak\" soundex \"Ashcraft\" soundex").unwrap(), vec![string("T522"), string("A261")]);
+        assert_eq!(stack("\"\" soundex \"123\" soundex").unwrap(), vec![string(""), string("")]);
+    }
+
+    #[test]
+    fn metaphone() {
+        assert_eq!(stack("\"Knight\" metaphone \"Night\" metaphone").unwrap(), vec![string("NT"), string("NT")]);
+        assert_eq!(stack("\"Thompson\" metaphone").unwrap(), vec![string("0MPSN")]);
+        assert_eq!(stack("\"\" metaphone").unwrap(), vec![string("")]);
+    }
+
+    #[test]
+    fn jaro_winkler() {
+        assert_eq!(stack("\"MARTHA\" \"MARHTA\" jaro-winkler").unwrap(), vec![Value::Float(0.9611111111111111)]);
+        assert_eq!(stack("\"same\" \"same\" jaro-winkler \"\" \"\" jaro-winkler").unwrap(), vec![Value::Float(1.0), Value::Float(1.0)]);
+        assert_eq!(stack("\"abc\" \"xyz\" jaro-winkler \"abc\" \"\" jaro-winkler").unwrap(), vec![Value::Float(0.0), Value::Float(0.0)]);
+    }
+
+    #[test]
+    fn ngram_sim() {
+        assert_eq!(stack("\"night\" \"nacht\" ngram-sim").unwrap(), vec![Value::Float(0.25)]);
+        assert_eq!(stack("\"abc\" \"abd\" 1 ngram-sim").unwrap(), vec![Value::Float(2.0 / 3.0)]);
+        // strings shorter than n only match when they are equal
+        assert_eq!(stack("\"a\" \"a\" ngram-sim \"a\" \"b\" ngram-sim").unwrap(), vec![Value::Float(1.0), Value::Float(0.0)]);
+        assert_eq!(run("\"abc\" \"abd\" 0 ngram-sim").err().unwrap().msg(), "invalid n-gram size 0");
+    }
+
+    #[test]
+    fn lcs() {
+        assert_eq!(stack("\"ABCBDAB\" \"BDCABA\" lcs").unwrap(), vec![string("BDAB"), Value::Int(4)]);
+        assert_eq!(stack("\"\" \"abc\" lcs").unwrap(), vec![string(""), Value::Int(0)]);
+    }
+
+    #[test]
+    fn lcsubstring() {
+        assert_eq!(stack("\"xabcy\" \"zabcw\" lcsubstring").unwrap(), vec![string("abc"), Value::Int(3)]);
+        assert_eq!(stack("\"ab\" \"cd\" lcsubstring").unwrap(), vec![string(""), Value::Int(0)]);
+    }
+
+    #[test]
+    fn is_palindrome() {
+        assert_eq!(stack("\"racecar\" is-palindrome \"日本日\" is-palindrome \"\" is-palindrome").unwrap(),
+            vec![Value::Boolean(true), Value::Boolean(true), Value::Boolean(true)]);
+        assert_eq!(stack("\"A man, a plan, a canal: Panama\" is-palindrome").unwrap(), vec![Value::Boolean(false)]);
+        assert_eq!(stack("\"A man, a plan, a canal: Panama\" true is-palindrome").unwrap(), vec![Value::Boolean(true)]);
+    }
+
+    #[test]
+    fn is_anagram() {
+        assert_eq!(stack("\"listen\" \"silent\" is-anagram \"ab\" \"abb\" is-anagram").unwrap(), vec![Value::Boolean(true), Value::Boolean(false)]);
+        assert_eq!(stack("\"Dormitory\" \"dirty room!\" is-anagram").unwrap(), vec![Value::Boolean(false)]);
+        assert_eq!(stack("\"Dormitory\" \"dirty room!\" true is-anagram").unwrap(), vec![Value::Boolean(true)]);
+    }
+
+    #[test]
+    fn squeeze() {
+        assert_eq!(stack("\"aaa  bbb\" \"a \" squeeze").unwrap(), vec![string("a bbb")]);
+        assert_eq!(stack("\"a\t\t b\" \"\\t\" squeeze").unwrap(), vec![string("a\t b")]);
+        // a - at the end is literal, not a range
+        assert_eq!(stack("\"a--b\" \"a-z\" squeeze \"a--b\" \"z-\" squeeze").unwrap(), vec![string("a--b"), string("a-b")]);
+        assert_eq!(stack("\"\" \"a\" squeeze").unwrap(), vec![string("")]);
+    }
+
+    #[test]
+    fn delete_chars() {
+        assert_eq!(stack("\"a1b2_c\" \"0-9_\" delete-chars").unwrap(), vec![string("abc")]);
+        assert_eq!(stack("\"abc\" \"\" delete-chars \"a-b\" \"-\" delete-chars").unwrap(), vec![string("abc"), string("ab")]);
+    }
+
+    #[test]
+    fn ordinal() {
+        assert_eq!(stack("1 ordinal 2 ordinal 3 ordinal 4 ordinal 0 ordinal").unwrap(),
+            vec![string("1st"), string("2nd"), string("3rd"), string("4th"), string("0th")]);
+        assert_eq!(stack("11 ordinal 12 ordinal 13 ordinal 21 ordinal 112 ordinal -22 ordinal").unwrap(),
+            vec![string("11th"), string("12th"), string("13th"), string("21st"), string("112th"), string("-22nd")]);
+    }
+
+    #[test]
+    fn pluralize() {
+        assert_eq!(stack("\"cat\" 1 pluralize \"cat\" -1 pluralize \"cat\" 0 pluralize \"cat\" 2 pluralize").unwrap(),
+            vec![string("cat"), string("cat"), string("cats"), string("cats")]);
+        assert_eq!(stack("\"box\" 2 pluralize \"city\" 2 pluralize \"day\" 2 pluralize \"Bus\" 2 pluralize").unwrap(),
+            vec![string("boxes"), string("cities"), string("days"), string("Buses")]);
+        assert_eq!(stack("\"child\" 2 pluralize \"Leaf\" 2 pluralize \"MOUSE\" 2 pluralize").unwrap(),
+            vec![string("children"), string("Leaves"), string("MICE")]);
+        assert_eq!(stack("\"\" 2 pluralize \"cat\" -9223372036854775807 1 - pluralize").unwrap(), vec![string(""), string("cats")]);
+    }
+
+    #[test]
+    fn word_and_line_count() {
+        assert_eq!(stack("\"a b  c\" word-count \"\" word-count").unwrap(), vec![Value::Int(3), Value::Int(0)]);
+        assert_eq!(stack("\"a\nb\n\" line-count \"a\nb\" line-count \"\" line-count").unwrap(), vec![Value::Int(2), Value::Int(2), Value::Int(0)]);
+    }
+
+    #[test]
+    fn char_freq() {
+        assert_eq!(stack("\"abca\" char-freq").unwrap(),
+            vec![Value::Char('a'), Value::Int(2), Value::Char('b'), Value::Int(1), Value::Char('c'), Value::Int(1), Value::Int(3)]);
+        assert_eq!(stack("\"\" char-freq").unwrap(), vec![Value::Int(0)]);
+    }
+
+    #[test]
+    fn unpack() {
+        assert_eq!(stack("\"abcdefghij\" \"3 2 5\" unpack").unwrap(), vec![string("abc"), string("de"), string("fghij"), Value::Int(3)]);
+        assert_eq!(stack("\"ab   cd\" \"5 2\" unpack \"日本語\" \"1 2\" unpack").unwrap(),
+            vec![string("ab"), string("cd"), Value::Int(2), string("日"), string("本語"), Value::Int(2)]);
+        // fields past the end of a short line are empty
+        assert_eq!(stack("\"ab\" \"1 5 2\" unpack").unwrap(), vec![string("a"), string("b"), string(""), Value::Int(3)]);
+        assert_eq!(stack("\"abc\" \"\" unpack").unwrap(), vec![Value::Int(0)]);
+    }
+
+    #[test]
+    fn unpack_invalid_widths() {
+        assert_eq!(run("\"abc\" \"2 x\" unpack").err().unwrap().msg(), "invalid field width \"x\"");
+        assert_eq!(run("\"abc\" \"-1\" unpack").err().unwrap().msg(), "invalid field width \"-1\"");
+    }
+
+    #[test]
+    fn expand_env() {
+        std::env::set_var("STR_TEST_EXPAND", "x y");
+        std::env::remove_var("STR_TEST_UNSET");
+        let expand = |template: &str| {
+            let mut program = Program::std_program();
+            program.allow_env = true;
+            run_in(program, &format!("{template:?} expand-env")).map(|program| program.stack().as_slice().to_vec())
+        };
+        assert_eq!(expand("a $STR_TEST_EXPAND-b ${STR_TEST_EXPAND}c").unwrap(), vec![string("a x y-b x yc")]);
+        assert_eq!(expand("[$STR_TEST_UNSET] ${STR_TEST_UNSET:-none} ${STR_TEST_EXPAND:-none}").unwrap(), vec![string("[] none x y")]);
+        // $$ is a literal $ and anything that isn't a name is kept
+        assert_eq!(expand("$$HOME costs $5 ${unclosed $").unwrap(), vec![string("$HOME costs $5 ${unclosed $")]);
+    }
+
+    #[test]
+    fn expand_env_needs_permission() {
+        assert_eq!(run("\"$HOME\" expand-env").err().unwrap().msg(), "reading environment variables is not allowed");
+    }
+
+    #[test]
+    fn glob_match() {
+        let matches = |string: &str, pattern: &str| stack(&format!("{string:?} {pattern:?} glob-match")).unwrap() == vec![Value::Boolean(true)];
+        assert!(matches("main.rs", "*.rs") && matches("a/b.rs", "*.rs") && matches("", "*"));
+        assert!(matches("abc", "a?c") && matches("日本", "??") && !matches("ac", "a?c"));
+        assert!(matches("b", "[a-c]") && !matches("d", "[a-c]") && matches("d", "[!a-c]") && matches("d", "[^a-c]"));
+        // a ] first in a class and a - last in it are literal
+        assert!(matches("]", "[]]") && matches("-", "[a-]"));
+        // an unclosed [ is literal, a \ escapes the next char
+        assert!(matches("[", "[") && matches("a*", "a\\*") && !matches("ab", "a\\*"));
+        assert!(!matches("aaaaaaaaaaaaaaaaaaaaaaaaaaaaab", "*a*a*a*a*a*a*a*a*c"));
+    }
+
+    #[test]
+    fn parse_ip() {
+        let ip = |normalized: &str| vec![string(normalized), Value::Boolean(true)];
+        assert_eq!(stack("\"192.168.0.1\" parse-ip").unwrap(), ip("192.168.0.1"));
+        assert_eq!(stack("\" 1.2.3.4 \" parse-ip").unwrap(), ip("1.2.3.4"));
+        assert_eq!(stack("\"2001:0db8:0000:0000:0000:0000:0000:0001\" parse-ip").unwrap(), ip("2001:db8::1"));
+        assert_eq!(stack("\"::FFFF:1.2.3.4\" parse-ip").unwrap(), ip("::ffff:1.2.3.4"));
+        for invalid in ["256.1.1.1", "01.2.3.4", "1.2.3", "::g", ""] {
+            assert_eq!(stack(&format!("{invalid:?} parse-ip")).unwrap(), vec![Value::Boolean(false)], "{invalid}");
+        }
+    }
+
+    #[test]
+    fn morse_round_trip() {
+        assert_eq!(stack("\"Hi there\" to-morse").unwrap(), vec![string(".... .. / - .... . .-. .")]);
+        assert_eq!(stack("\"  a   b \" to-morse \"\" to-morse").unwrap(), vec![string(".- / -..."), string("")]);
+        assert_eq!(stack("\"SOS 73\" to-morse from-morse").unwrap(), vec![string("sos 73"), Value::Boolean(true)]);
+        assert_eq!(stack("\"...---\" from-morse").unwrap(), vec![Value::Boolean(false)]);
+        assert_eq!(run("\"é\" to-morse").err().unwrap().msg(), "'é' has no Morse code");
+    }
+
+    #[test]
+    fn nato_and_leet() {
+        assert_eq!(stack("\"ab1\" to-nato \"a-b!\" to-nato").unwrap(), vec![string("Alfa Bravo One"), string("Alfa - Bravo !")]);
+        assert_eq!(stack("\"leet\" to-leet \"Hello, World\" to-leet").unwrap(), vec![string("1337"), string("H3110, W0r1d")]);
+    }
+}
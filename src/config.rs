@@ -0,0 +1,140 @@
+use std::{env, fs, io::{stderr, IsTerminal}, path::PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+enum ConfigValue {
+    String(String), Boolean(bool), Int(i64), Array(Vec<ConfigValue>)
+}
+impl ConfigValue {
+    fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if let Some(string) = text.strip_prefix('"') {
+            let Some(string) = string.strip_suffix('"') else { return Err(format!("unclosed string {text}")) };
+            return Ok(Self::String(string.replace("\\\"", "\"").replace("\\\\", "\\")))
+        }
+        if let Some(items) = text.strip_prefix('[') {
+            let Some(items) = items.strip_suffix(']') else { return Err(format!("unclosed array {text}")) };
+            return items.split(',')
+                .filter(|item| !item.trim().is_empty())
+                .map(Self::parse)
+                .collect::<Result<Vec<Self>, String>>()
+                .map(Self::Array)
+        }
+        match text {
+            "true" => Ok(Self::Boolean(true)),
+            "false" => Ok(Self::Boolean(false)),
+            _ => text.replace('_', "").parse().map(Self::Int).map_err(|_| format!("invalid value {text}"))
+        }
+    }
+    fn strings(self) -> Result<Vec<String>, String> {
+        match self {
+            Self::String(string) => Ok(vec![string]),
+            Self::Array(items) => items.into_iter().map(|item| match item {
+                Self::String(string) => Ok(string),
+                item => Err(format!("expected string, got {item:?}"))
+            }).collect(),
+            value => Err(format!("expected string or array of strings, got {value:?}"))
+        }
+    }
+}
+
+/// the line without a `#` comment at its end, a `#` inside a string doesn't start one
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut chars = line.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '\\' if quoted => { chars.next(); }
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..idx],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// startup defaults read from `~/.config/str/config.toml`
+///
+/// only a flat subset of TOML is understood: `key = value` lines with strings,
+/// booleans, integers and single-line arrays
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub color: bool,
+    pub prelude: Vec<String>,
+    pub path: Vec<String>,
+    /// the step limit of the REPL, `None` for no limit
+    pub limit: Option<u64>
+}
+impl Config {
+    pub fn new() -> Self {
+        Self { color: stderr().is_terminal(), prelude: vec![], path: vec![], limit: Some(10_000_000) }
+    }
+    pub fn file() -> Option<PathBuf> {
+        let dir = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config")
+        };
+        Some(dir.join("str").join("config.toml"))
+    }
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Self::new();
+        for (ln, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() { continue }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("{}: expected `key = value`", ln + 1))
+            };
+            let value = ConfigValue::parse(value).map_err(|e| format!("{}: {e}", ln + 1))?;
+            match (key.trim(), value) {
+                ("color", ConfigValue::Boolean(color)) => config.color = color,
+                ("prelude", value) => config.prelude = value.strings().map_err(|e| format!("{}: {e}", ln + 1))?,
+                ("path", value) => config.path = value.strings().map_err(|e| format!("{}: {e}", ln + 1))?,
+                ("limit", ConfigValue::Int(limit)) if limit >= 0 => config.limit = (limit > 0).then_some(limit as u64),
+                (key, value) => return Err(format!("{}: unexpected {value:?} for key {key:?}", ln + 1))
+            }
+        }
+        Ok(config)
+    }
+    /// loads the config file, falling back to the defaults if it doesn't exist or is invalid
+    pub fn load() -> Self {
+        let Some(file) = Self::file() else { return Self::new() };
+        let Ok(text) = fs::read_to_string(&file) else { return Self::new() };
+        match Self::parse(&text) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("ignoring config file {}:{e}", file.display());
+                Self::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_readme_example() {
+        let config = Config::parse(concat!(
+            "color = true                 # color error messages\n",
+            "prelude = [\"mylib.str\"]      # used when STR_PRELUDE isn't set\n",
+            "path = [\"/home/me/str-lib\"]  # extra search path directories\n",
+            "limit = 10_000_000           # step limit of the REPL, 0 for none\n",
+        )).unwrap();
+        assert_eq!(config, Config { color: true, prelude: vec!["mylib.str".to_string()], path: vec!["/home/me/str-lib".to_string()], limit: Some(10_000_000) });
+    }
+    #[test]
+    fn parse_values() {
+        let config = Config::parse("# comment\n\ncolor = false\nprelude = \"a#b.str\"\npath = [\"a\", \"b\",]\nlimit = 0").unwrap();
+        assert_eq!((config.color, config.prelude, config.path, config.limit), (false, vec!["a#b.str".to_string()], vec!["a".to_string(), "b".to_string()], None));
+        assert_eq!(Config::parse("").unwrap().limit, Config::new().limit);
+    }
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Config::parse("color").unwrap_err(), "1: expected `key = value`");
+        assert!(Config::parse("color = 1").is_err());
+        assert!(Config::parse("limit = -1").is_err());
+        assert!(Config::parse("path = [1]").is_err());
+        assert!(Config::parse("prelude = \"a").is_err());
+        assert!(Config::parse("\nfont = \"x\"").unwrap_err().starts_with("2: "));
+    }
+}
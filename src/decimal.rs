@@ -0,0 +1,236 @@
+use std::{cmp::Ordering, fmt::{Display, Debug}};
+
+/// fractional digits kept when a division doesn't terminate
+pub const DIV_SCALE: u32 = 28;
+
+/// an exact decimal number, `digits` × 10^-`scale`
+///
+/// the digits are stored least significant first without leading zeros, the scale is
+/// kept as written so `12.50` stays `12.50`
+#[derive(Clone)]
+pub struct Decimal {
+    negative: bool,
+    digits: Vec<u8>,
+    scale: u32
+}
+
+fn cmp_digits(a: &[u8], b: &[u8]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+fn trim(digits: &mut Vec<u8>) {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+}
+fn add_digits(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut digits = vec![];
+    let mut carry = 0;
+    for idx in 0..a.len().max(b.len()) {
+        let sum = a.get(idx).unwrap_or(&0) + b.get(idx).unwrap_or(&0) + carry;
+        digits.push(sum % 10);
+        carry = sum / 10;
+    }
+    if carry > 0 { digits.push(carry) }
+    digits
+}
+/// `a - b`, `a` has to be at least `b`
+fn sub_digits(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut digits = vec![];
+    let mut borrow = 0;
+    for (idx, digit) in a.iter().enumerate() {
+        let mut digit = *digit as i8 - *b.get(idx).unwrap_or(&0) as i8 - borrow;
+        borrow = if digit < 0 { digit += 10; 1 } else { 0 };
+        digits.push(digit as u8);
+    }
+    trim(&mut digits);
+    digits
+}
+fn mul_digits(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut digits = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            digits[i + j] += (*x as u32) * (*y as u32);
+        }
+    }
+    let mut carry = 0;
+    let mut digits: Vec<u8> = digits.into_iter().map(|digit| {
+        let digit = digit + carry;
+        carry = digit / 10;
+        (digit % 10) as u8
+    }).collect();
+    while carry > 0 {
+        digits.push((carry % 10) as u8);
+        carry /= 10;
+    }
+    trim(&mut digits);
+    digits
+}
+/// truncating long division, `b` may not be zero
+fn div_digits(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut quotient = vec![];
+    let mut rest: Vec<u8> = vec![];
+    for digit in a.iter().rev() {
+        rest.insert(0, *digit);
+        trim(&mut rest);
+        let mut count = 0;
+        while cmp_digits(&rest, b) != Ordering::Less {
+            rest = sub_digits(&rest, b);
+            count += 1;
+        }
+        quotient.push(count);
+    }
+    quotient.reverse();
+    trim(&mut quotient);
+    quotient
+}
+
+impl Decimal {
+    fn new(negative: bool, mut digits: Vec<u8>, scale: u32) -> Self {
+        trim(&mut digits);
+        Self { negative: negative && !digits.is_empty(), digits, scale }
+    }
+    pub fn is_zero(&self) -> bool { self.digits.is_empty() }
+    /// parses numbers like `12`, `-0.5` or `1_000.25`
+    pub fn parse(text: &str) -> Option<Self> {
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, text.strip_prefix('+').unwrap_or(text))
+        };
+        let (int, fract) = text.split_once('.').unwrap_or((text, ""));
+        if int.is_empty() && fract.is_empty() { return None }
+        let mut digits = vec![];
+        let mut scale = 0;
+        for (idx, part) in [int, fract].iter().enumerate() {
+            if part.starts_with('_') || part.ends_with('_') { return None }
+            for c in part.chars().filter(|c| *c != '_') {
+                digits.push(c.to_digit(10)? as u8);
+                scale += idx as u32;
+            }
+        }
+        digits.reverse();
+        Some(Self::new(negative, digits, scale))
+    }
+    pub fn from_int(int: i64) -> Self {
+        Self::parse(&int.to_string()).unwrap()
+    }
+    /// the exact value of the shortest representation of `float`, `None` for infinity and NaN
+    pub fn from_float(float: f64) -> Option<Self> {
+        if !float.is_finite() { return None }
+        Self::parse(&float.to_string())
+    }
+    pub fn to_float(&self) -> f64 {
+        self.to_string().parse().unwrap()
+    }
+    /// the same number with at least `scale` fractional digits
+    fn with_scale(&self, scale: u32) -> Self {
+        if scale <= self.scale { return self.clone() }
+        let mut digits = vec![0; (scale - self.scale) as usize];
+        digits.extend(&self.digits);
+        Self::new(self.negative, digits, scale)
+    }
+    /// removes trailing fractional zeros, keeping at least `scale` fractional digits
+    fn reduce(mut self, scale: u32) -> Self {
+        while self.scale > scale && self.digits.first() == Some(&0) {
+            self.digits.remove(0);
+            self.scale -= 1;
+        }
+        if self.is_zero() { self.scale = self.scale.min(scale) }
+        self
+    }
+    pub fn neg(&self) -> Self {
+        Self::new(!self.negative, self.digits.clone(), self.scale)
+    }
+    pub fn add(&self, other: &Self) -> Self {
+        let scale = self.scale.max(other.scale);
+        let (a, b) = (self.with_scale(scale), other.with_scale(scale));
+        if a.negative == b.negative {
+            return Self::new(a.negative, add_digits(&a.digits, &b.digits), scale)
+        }
+        match cmp_digits(&a.digits, &b.digits) {
+            Ordering::Less => Self::new(b.negative, sub_digits(&b.digits, &a.digits), scale),
+            _ => Self::new(a.negative, sub_digits(&a.digits, &b.digits), scale)
+        }
+    }
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+    pub fn mul(&self, other: &Self) -> Self {
+        Self::new(self.negative != other.negative, mul_digits(&self.digits, &other.digits), self.scale + other.scale)
+    }
+    /// exact if the quotient terminates, otherwise truncated to `DIV_SCALE` fractional digits,
+    /// `None` when dividing by zero
+    pub fn div(&self, other: &Self) -> Option<Self> {
+        if other.is_zero() { return None }
+        let scale = self.scale.max(other.scale).max(DIV_SCALE);
+        let mut digits = vec![0; (scale + other.scale - self.scale) as usize];
+        digits.extend(&self.digits);
+        let quotient = Self::new(self.negative != other.negative, div_digits(&digits, &other.digits), scale);
+        Some(quotient.reduce(self.scale.max(other.scale)))
+    }
+    /// rounds half away from zero to exactly `places` fractional digits
+    pub fn round(&self, places: u32) -> Self {
+        if places >= self.scale { return self.with_scale(places) }
+        let cut = (self.scale - places) as usize;
+        if cut > self.digits.len() { return Self::new(false, vec![], places) }
+        let mut digits = self.digits[cut..].to_vec();
+        if self.digits[cut - 1] >= 5 {
+            digits = add_digits(&digits, &[1]);
+        }
+        Self::new(self.negative, digits, places)
+    }
+    /// rounds to `places` and puts `separator` between each group of three digits before the point
+    pub fn format(&self, places: u32, separator: char) -> String {
+        let string = self.round(places).abs_string();
+        let (int, fract) = string.split_once('.').map_or((string.as_str(), None), |(int, fract)| (int, Some(fract)));
+        let mut grouped = String::new();
+        for (idx, c) in int.chars().enumerate() {
+            if idx > 0 && (int.len() - idx) % 3 == 0 { grouped.push(separator) }
+            grouped.push(c);
+        }
+        let sign = if self.round(places).negative { "-" } else { "" };
+        match fract {
+            Some(fract) => format!("{sign}{grouped}.{fract}"),
+            None => format!("{sign}{grouped}")
+        }
+    }
+    fn abs_string(&self) -> String {
+        let mut digits: String = self.digits.iter().rev().map(|digit| (b'0' + digit) as char).collect();
+        let scale = self.scale as usize;
+        if digits.len() <= scale {
+            digits = "0".repeat(scale + 1 - digits.len()) + &digits;
+        }
+        if scale > 0 {
+            digits.insert(digits.len() - scale, '.');
+        }
+        digits
+    }
+}
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Decimal {}
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let difference = self.sub(other);
+        if difference.is_zero() { Ordering::Equal }
+        else if difference.negative { Ordering::Less }
+        else { Ordering::Greater }
+    }
+}
+impl Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", if self.negative { "-" } else { "" }, self.abs_string())
+    }
+}
+impl Debug for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
@@ -0,0 +1,58 @@
+use crate::parser::{Node, NodeType};
+use crate::run::Program;
+
+fn collect_definitions(node: &Node, definitions: &mut Vec<Node>) {
+    match &node.node {
+        NodeType::Chunk(nodes) => {
+            for node in nodes {
+                collect_definitions(node, definitions);
+            }
+        }
+        NodeType::Macro(..) => definitions.push(node.clone()),
+        _ => {}
+    }
+}
+
+/// renders Markdown reference docs for every macro defined in `node` without running the rest of the script
+pub fn markdown(title: &str, node: &Node) -> Result<String, crate::error::Error> {
+    let mut definitions = vec![];
+    collect_definitions(node, &mut definitions);
+    let mut program = Program::new();
+    for definition in definitions {
+        program.run(definition)?;
+    }
+    let mut ids: Vec<&String> = program.macros.keys().collect();
+    ids.sort();
+    let mut text = format!("# {title}\n");
+    for id in ids {
+        text.push_str(format!("\n## `{id}`\n\n```\n{}```\n", program.display_macro(id)).as_str());
+        if let Some(doc) = program.doc(id) {
+            text.push('\n');
+            text.push_str(doc.trim());
+            text.push('\n');
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::{lexer, parser};
+
+    fn markdown(text: &str) -> Result<String, Error> {
+        super::markdown("lib.str", &parser::parse(lexer::lex(text.to_string())?)?)
+    }
+
+    #[test]
+    fn markdown_lists_definitions_with_docs() {
+        let docs = markdown("## adds one\nmacro inc [int] 1 + end\nmacro dec [int -- int] \"subtracts one\"\n  1 -\nend").unwrap();
+        assert_eq!(docs, "# lib.str\n\n## `dec`\n\n```\n[int -- int] dec\n```\n\nsubtracts one\n\n## `inc`\n\n```\n[int] inc\n```\n\nadds one\n");
+    }
+    #[test]
+    fn markdown_does_not_run_the_script() {
+        let docs = markdown("\"stop\" fail\nmacro f [] 1 end").unwrap();
+        assert!(docs.contains("## `f`"));
+        assert!(markdown("macro f [unknown] 1 end").is_err());
+    }
+}
@@ -0,0 +1,210 @@
+//! stack effect annotations like `(a b -- c)` after a macro signature, checked against the body when
+//! the macro is defined
+//!
+//! the check follows the stack height through the body as far as it's known: literals, takes,
+//! reorders, `if`s and `match`es whose branches agree, loops that leave the height as it was and
+//! calls of macros with an annotation or declared outputs. a builtin only tells how many values it
+//! takes, so the check stops after it, like after anything else it can't follow
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::error;
+use crate::error::Error;
+use crate::error_pos;
+use crate::lexer::{Instr, Position};
+use crate::parser::{Node, NodeType};
+use crate::run::Program;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Effect {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>
+}
+impl Display for Effect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({} -- {})", self.inputs.join(" "), self.outputs.join(" "))
+    }
+}
+
+struct Checker<'a> {
+    program: &'a Program,
+    name: &'a str,
+    effect: &'a Effect,
+    /// the stack height relative to where the body started
+    height: isize,
+    /// the variables taken in the body so far
+    vars: HashSet<String>
+}
+impl Checker<'_> {
+    fn take(&mut self, count: usize, pos: &Position) -> Result<(), Error> {
+        self.height -= count as isize;
+        if self.height < -(self.effect.inputs.len() as isize) {
+            let inputs = self.effect.inputs.len();
+            return error_pos!(pos, "{:?} can underflow the stack here, its stack effect {} declares {inputs} input{}",
+                self.name, self.effect, if inputs == 1 { "" } else { "s" })
+        }
+        Ok(())
+    }
+    fn push(&mut self, count: usize) {
+        self.height += count as isize;
+    }
+    /// walks `body` from the current height and whether it ended up at the same height again
+    fn neutral(&mut self, body: &Node) -> Result<bool, Error> {
+        let height = self.height;
+        Ok(self.walk(body)? && self.height == height)
+    }
+    /// follows the node, `false` if its effect isn't known, which ends the check
+    fn walk(&mut self, node: &Node) -> Result<bool, Error> {
+        match &node.node {
+            NodeType::Chunk(nodes) => {
+                for node in nodes {
+                    if !self.walk(node)? { return Ok(false) }
+                }
+            }
+            NodeType::String(_) | NodeType::Char(_) | NodeType::Int(_) | NodeType::Float(_) | NodeType::Boolean(_)
+            | NodeType::Block(_) | NodeType::Ref(_) => self.push(1),
+            NodeType::ID(id) => {
+                let is_self = self.program.macro_name(id) == self.program.macro_name(self.name);
+                match self.program.get_macro(id) {
+                    _ if is_self => {
+                        self.take(self.effect.inputs.len(), &node.pos)?;
+                        self.push(self.effect.outputs.len());
+                    }
+                    Some(macros) => match (&macros.effect, macros.declared_arity()) {
+                        (Some(effect), _) => {
+                            self.take(effect.inputs.len(), &node.pos)?;
+                            self.push(effect.outputs.len());
+                        }
+                        (None, Some((inputs, outputs))) => {
+                            self.take(inputs, &node.pos)?;
+                            self.push(outputs);
+                        }
+                        (None, None) if macros.min_arity() == macros.max_arity() => {
+                            self.take(macros.min_arity(), &node.pos)?;
+                            return Ok(false)
+                        }
+                        (None, None) => return Ok(false)
+                    }
+                    None if self.vars.contains(id) => self.push(1),
+                    None => return Ok(false)
+                }
+            }
+            NodeType::Take(ids) => {
+                self.take(ids.len(), &node.pos)?;
+                self.vars.extend(ids.iter().filter(|id| *id != "_").cloned());
+            }
+            NodeType::CopyTo(ids) => {
+                self.take(1, &node.pos)?;
+                self.push(1);
+                self.vars.extend(ids.iter().cloned());
+            }
+            NodeType::Copy(token) => match &token.instr {
+                Instr::CopyTo(ids) => self.push(ids.len()),
+                _ => self.push(1)
+            }
+            NodeType::Reorder(ids, pushed) => {
+                self.take(ids.len(), &node.pos)?;
+                self.push(pushed.len());
+            }
+            NodeType::If(case_node, else_node) => {
+                self.take(1, &node.pos)?;
+                let height = self.height;
+                match else_node {
+                    Some(else_node) => {
+                        if !self.walk(case_node)? { return Ok(false) }
+                        let case_height = std::mem::replace(&mut self.height, height);
+                        if !self.walk(else_node)? || self.height != case_height { return Ok(false) }
+                    }
+                    None => if !self.neutral(case_node)? { return Ok(false) }
+                }
+            }
+            NodeType::Match(cases, else_node) => {
+                self.take(1, &node.pos)?;
+                let height = self.height;
+                let mut arm_height = None;
+                for body in cases.iter().map(|(_, body)| body).chain(else_node.as_deref()) {
+                    self.height = height;
+                    if !self.walk(body)? || arm_height.is_some_and(|arm_height| arm_height != self.height) { return Ok(false) }
+                    arm_height = Some(self.height);
+                }
+                self.height = arm_height.unwrap_or(height);
+            }
+            NodeType::Repeat(body) => {
+                self.take(1, &node.pos)?;
+                if !self.neutral(body)? { return Ok(false) }
+            }
+            NodeType::While(cond, body) => {
+                let height = self.height;
+                if !self.walk(cond)? { return Ok(false) }
+                self.take(1, &node.pos)?;
+                if !self.walk(body)? || self.height != height { return Ok(false) }
+            }
+            NodeType::ForEach(body) | NodeType::WithLines(body) => {
+                self.take(1, &node.pos)?;
+                let height = self.height;
+                self.push(1);
+                if !self.walk(body)? || self.height != height { return Ok(false) }
+            }
+            // the quote and escape chars are optional, so how many values it takes isn't known
+            NodeType::WithRecords(_) => {
+                self.take(1, &node.pos)?;
+                return Ok(false)
+            }
+            NodeType::Let(body) => if !self.walk(body)? { return Ok(false) }
+            NodeType::Macro(..) | NodeType::Alias(..) => {}
+            NodeType::Include(_) | NodeType::Import(..) => return Ok(false)
+        }
+        Ok(true)
+    }
+}
+
+/// checks that `body` of the macro `name` can't take more values than `effect` declares and, if its
+/// whole effect is known, that it leaves as many as it declares
+pub fn check(program: &Program, name: &str, effect: &Effect, body: &Node) -> Result<(), Error> {
+    let mut checker = Checker { program, name, effect, height: 0, vars: HashSet::new() };
+    if checker.walk(body)? {
+        let left = effect.inputs.len() as isize + checker.height;
+        if left != effect.outputs.len() as isize {
+            return error_pos!(&body.pos, "{name:?} leaves {left} value{} but its stack effect {effect} declares {}",
+                if left == 1 { "" } else { "s" }, effect.outputs.len())
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run::tests::{run, stack};
+    use crate::value::Value;
+
+    #[test]
+    fn matching_effects() {
+        for script in [
+            "macro h [bool] (b -- n) if 1 else 2 end end",
+            "macro f [int] (n -- a b) (x) @x @x end",
+            "macro g [int int] (a b -- b a) (a b -> b a) end",
+            "macro one [] ( -- a) 1 end macro two [] ( -- a b) one one end",
+            // the check stops at a builtin, whatever comes after it
+            "macro s [int] (n -- n) copy * 1 2 3 end",
+        ] {
+            assert!(run(script).is_ok(), "{script}");
+        }
+        assert_eq!(stack("macro h [bool] (b -- n) if 1 else 2 end end false h").unwrap(), vec![Value::Int(2)]);
+    }
+
+    #[test]
+    fn underflow() {
+        let error = run("macro bad [int] (n -- n) (a b) end").err().unwrap();
+        assert_eq!(error.msg(), "\"bad\" can underflow the stack here, its stack effect (n -- n) declares 1 input");
+        assert_eq!(error.pos().unwrap().col.start, 25);
+        assert!(run("macro u [bool] (b -- ) if drop end end").is_err());
+        assert!(run("macro one [] ( -- a) 1 end macro u [] (a b -- ) (x y z) end").is_err());
+    }
+
+    #[test]
+    fn wrong_output_count() {
+        assert_eq!(run("macro two [] ( -- a) 1 2 end").err().unwrap().msg(), "\"two\" leaves 2 values but its stack effect ( -- a) declares 1");
+        assert_eq!(run("macro one [] ( -- a) 1 end macro f [] ( -- a) one one end").err().unwrap().msg(),
+            "\"f\" leaves 2 values but its stack effect ( -- a) declares 1");
+    }
+}
@@ -5,10 +5,28 @@ use crate::lexer::Position;
 #[derive(Debug, Clone, PartialEq)]
 pub struct Error {
     msg: String,
-    pos: Option<Position>
+    pos: Option<Position>,
+    /// the path and text of the included file the error happened in
+    file: Option<Box<(String, String)>>
 }
 impl Error {
-    pub fn new(msg: String, pos: Option<Position>) -> Self { Self { msg, pos } }
+    pub fn new(msg: String, pos: Option<Position>) -> Self { Self { msg, pos, file: None } }
+    pub fn msg(&self) -> &str { &self.msg }
+    pub fn pos(&self) -> Option<&Position> { self.pos.as_ref() }
+    /// marks the error as raised in the file at `path`, unless it already happened in a file it included
+    pub fn in_file(mut self, path: &str, text: &str) -> Self {
+        if self.file.is_none() {
+            self.file = Some(Box::new((path.to_string(), text.to_string())));
+        }
+        self
+    }
+    /// attaches `pos` to errors that were raised without a position, like the ones from builtins
+    pub fn or_pos(mut self, pos: &Position) -> Self {
+        if self.pos.is_none() {
+            self.pos = Some(pos.clone());
+        }
+        self
+    }
     pub fn display(&self, path: &String) -> String {
         let mut err = format!("ERROR: {}", self.msg);
         if let Some(pos) = &self.pos {
@@ -16,7 +34,7 @@ impl Error {
             if let Ok(text) = fs::read_to_string(path) {
                 let lines: Vec<&str> = text.lines().collect();
                 if let Some(slice) = lines.get(pos.ln.clone()) {
-                    for line in slice.to_vec() {
+                    for line in slice {
                         err.push_str(line);
                     }
                 }
@@ -24,17 +42,21 @@ impl Error {
         }
         err
     }
-    pub fn display_text(&self, path: &String, text: String) -> String {
+    pub fn display_text(&self, path: &str, text: String) -> String {
+        let (path, text) = match &self.file {
+            Some(file) => (file.0.as_str(), file.1.clone()),
+            None => (path, text)
+        };
         let mut err = format!("ERROR: {}", self.msg);
         if let Some(pos) = &self.pos {
             err.push_str(" - ");
-            err.push_str(path.as_str());
+            err.push_str(path);
             err.push(':');
             err.push_str(pos.to_string().as_str());
             err.push('\n');
             let lines: Vec<&str> = text.lines().collect();
             if let Some(slice) = lines.get(pos.ln.clone()) {
-                for line in slice.to_vec() {
+                for line in slice {
                     err.push_str(line);
                 }
             }
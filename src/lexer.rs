@@ -30,53 +30,99 @@ impl Display for Position {
     }
 }
 
-pub const SYMBOLS: [char; 7] = ['"', '\'', '(', ')', '{', '}', '@'];
+pub const SYMBOLS: [char; 9] = ['"', '\'', '(', ')', '{', '}', '[', ']', '@'];
+/// the characters operator identifiers like `+` or `<=>` are made of
+pub const OPERATORS: [char; 20] = ['+', '-', '*', '/', '%', '<', '>', '=', '!', '?', '&', '|', '^', '~', ';', ':', '.', ',', '$', '\\'];
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Instr {
     String(String), Char(char), Int(i64), Float(f64), Boolean(bool),
-    ID(String), Take(Vec<String>), CopyTo(Vec<String>), Copy(Box<Token>),
-    End, If, Else, Repeat, Macro
+    /// a `##` comment line, documenting the macro defined after it
+    Doc(String),
+    ID(String), Ref(String), Take(Vec<String>), CopyTo(Vec<String>), Copy(Box<Token>),
+    /// `(a b -> b a)`, the taken names top first and the names to push
+    Reorder(Vec<String>, Vec<String>),
+    /// `(a b -- c)`, the names of the values a macro takes and leaves
+    Effect(Vec<String>, Vec<String>),
+    OpenBracket, CloseBracket,
+    End, If, Else, Match, Case, Repeat, While, Do, ForEach, WithLines, WithRecords, Let, Macro, Alias, Include, Import
 }
 impl Instr {
     pub fn get(id: String, pos: Position) -> Result<Self, Error> {
         match id.as_str() {
             "true" => Ok(Self::Boolean(true)),
             "false" => Ok(Self::Boolean(false)),
+            "nan" => Ok(Self::Float(f64::NAN)),
+            "inf" => Ok(Self::Float(f64::INFINITY)),
+            "-inf" => Ok(Self::Float(f64::NEG_INFINITY)),
             "end" => Ok(Self::End),
             "if" => Ok(Self::If),
             "else" => Ok(Self::Else),
+            "match" => Ok(Self::Match),
+            "case" => Ok(Self::Case),
             "repeat" => Ok(Self::Repeat),
+            "while" => Ok(Self::While),
+            "do" => Ok(Self::Do),
+            "foreach" => Ok(Self::ForEach),
+            "with-lines" => Ok(Self::WithLines),
+            "with-records" => Ok(Self::WithRecords),
+            "let" => Ok(Self::Let),
             "macro" => Ok(Self::Macro),
+            "alias" => Ok(Self::Alias),
+            "include" => Ok(Self::Include),
+            "import" => Ok(Self::Import),
             _ => match id.chars().next() {
-                Some(c) if c.is_digit(10) => match id.parse::<i64>() {
+                Some(c) if c.is_ascii_digit() || (c == '-' && id[1..].starts_with(|c: char| c.is_ascii_digit())) => match id.replace('_', "").parse::<i64>() {
                     Ok(number) => Ok(Self::Int(number)),
-                    Err(_) => match id.parse::<f64>() {
+                    Err(_) => match id.replace('_', "").parse::<f64>() {
                         Ok(number) => Ok(Self::Float(number)),
                         Err(e) => error_pos!(pos, "error occurd while parsing the number {id:?}: {e}")
                     }
                 }
-                Some(_) => Ok(Self::ID(id)),
+                Some(c) if c.is_alphabetic() || c == '_' => Ok(Self::ID(id)),
+                Some('&') if id[1..].starts_with(|c: char| c.is_alphabetic() || c == '_') => Ok(Self::Ref(id[1..].to_string())),
+                Some(_) if id.chars().all(|c| OPERATORS.contains(&c)) => Ok(Self::ID(id)),
+                Some(c) if OPERATORS.contains(&c) => error_pos!(pos, "operator {id:?} may only contain the characters {}",
+                    OPERATORS.iter().collect::<String>()),
+                Some(c) => error_pos!(pos, "unexpected character {c:?}, identifiers start with a letter or '_', operators only contain the characters {}",
+                    OPERATORS.iter().collect::<String>()),
                 None => error_pos!(pos, "empty id")
             }
         }
     }
     pub fn name(&self) -> String {
         match self {
-            Self::String(_) => format!("string"),
-            Self::Char(_) => format!("char"),
-            Self::Int(_) => format!("int"),
-            Self::Float(_) => format!("float"),
-            Self::Boolean(_) => format!("boolean"),
-            Self::ID(_) => format!("identifier"),
-            Self::Take(_) => format!("take-into-identifiers"),
-            Self::CopyTo(_) => format!("copt-to-identifiers"),
+            Self::String(_) => "string".to_string(),
+            Self::Char(_) => "char".to_string(),
+            Self::Int(_) => "int".to_string(),
+            Self::Float(_) => "float".to_string(),
+            Self::Boolean(_) => "boolean".to_string(),
+            Self::Doc(_) => "doc comment".to_string(),
+            Self::ID(_) => "identifier".to_string(),
+            Self::Ref(_) => "macro reference".to_string(),
+            Self::Take(_) => "take-into-identifiers".to_string(),
+            Self::CopyTo(_) => "copt-to-identifiers".to_string(),
             Self::Copy(token) => format!("copy of {}", token.instr.name()),
-            Self::End => format!("end-control-flow instruction"),
-            Self::If => format!("if-control-flow instruction"),
-            Self::Else => format!("else-control-flow instruction"),
-            Self::Repeat => format!("repeat-control-flow instruction"),
-            Self::Macro => format!("macro instruction"),
+            Self::Reorder(..) => "reorder".to_string(),
+            Self::Effect(..) => "stack effect".to_string(),
+            Self::OpenBracket => "opening bracket".to_string(),
+            Self::CloseBracket => "closing bracket".to_string(),
+            Self::End => "end-control-flow instruction".to_string(),
+            Self::If => "if-control-flow instruction".to_string(),
+            Self::Else => "else-control-flow instruction".to_string(),
+            Self::Match => "match-control-flow instruction".to_string(),
+            Self::Case => "case-control-flow instruction".to_string(),
+            Self::Repeat => "repeat-control-flow instruction".to_string(),
+            Self::While => "while-control-flow instruction".to_string(),
+            Self::Do => "do-control-flow instruction".to_string(),
+            Self::ForEach => "foreach-control-flow instruction".to_string(),
+            Self::WithLines => "with-lines-control-flow instruction".to_string(),
+            Self::WithRecords => "with-records-control-flow instruction".to_string(),
+            Self::Let => "let-control-flow instruction".to_string(),
+            Self::Macro => "macro instruction".to_string(),
+            Self::Alias => "alias instruction".to_string(),
+            Self::Include => "include instruction".to_string(),
+            Self::Import => "import instruction".to_string(),
         }
     }
 }
@@ -88,15 +134,32 @@ impl Display for Instr {
             Self::Int(int) => write!(f, "{int:?}"),
             Self::Float(float) => write!(f, "{float:?}"),
             Self::Boolean(boolean) => write!(f, "{boolean:?}"),
+            Self::Doc(doc) => write!(f, "## {doc}"),
             Self::ID(id) => write!(f, "{id}"),
+            Self::Ref(id) => write!(f, "&{id}"),
             Self::Take(ids) => write!(f, "({})", ids.iter().map(|id| id.to_string()).collect::<Vec<String>>().join(" ")),
             Self::CopyTo(ids) => write!(f, "{{{}}}", ids.iter().map(|id| id.to_string()).collect::<Vec<String>>().join(" ")),
             Self::Copy(instr) => write!(f, "@{instr}"),
-            Self::End => write!(f, ";"),
+            Self::Reorder(ids, pushed) => write!(f, "({} -> {})", ids.iter().rev().map(|id| id.to_string()).collect::<Vec<String>>().join(" "), pushed.join(" ")),
+            Self::Effect(inputs, outputs) => write!(f, "({} -- {})", inputs.join(" "), outputs.join(" ")),
+            Self::OpenBracket => write!(f, "["),
+            Self::CloseBracket => write!(f, "]"),
+            Self::End => write!(f, "end"),
             Self::If => write!(f, "if"),
             Self::Else => write!(f, "else"),
+            Self::Match => write!(f, "match"),
+            Self::Case => write!(f, "case"),
             Self::Repeat => write!(f, "repeat"),
+            Self::While => write!(f, "while"),
+            Self::Do => write!(f, "do"),
+            Self::ForEach => write!(f, "foreach"),
+            Self::WithLines => write!(f, "with-lines"),
+            Self::WithRecords => write!(f, "with-records"),
+            Self::Let => write!(f, "let"),
             Self::Macro => write!(f, "macro"),
+            Self::Alias => write!(f, "alias"),
+            Self::Include => write!(f, "include"),
+            Self::Import => write!(f, "import"),
         }
     }
 }
@@ -129,18 +192,23 @@ pub struct Lexer {
 impl Lexer {
     pub fn new(text: String) -> Self { Self { text, idx: 0, ln: 0, col: 0 } }
     pub fn get(&self) -> Option<char> {
-        self.text.get(self.idx..self.idx+1)?.chars().next()
+        self.text.get(self.idx..)?.chars().next()
+    }
+    /// the char after the current one
+    pub fn peek(&self) -> Option<char> {
+        self.text.get(self.idx..)?.chars().nth(1)
     }
     pub fn pos(&self) -> Position {
         Position::new(self.idx..self.idx+1, self.ln..self.ln+1, self.col..self.col+1)
     }
     pub fn advance(&mut self) {
-        self.idx += 1;
-        self.col += 1;
         if self.get() == Some('\n') {
             self.ln += 1;
             self.col = 0;
+        } else {
+            self.col += 1;
         }
+        self.idx += self.get().map_or(1, char::len_utf8);
     }
     pub fn advance_ws(&mut self) {
         while let Some(c) = self.get() {
@@ -155,20 +223,28 @@ impl Lexer {
             Some('"') => {
                 self.advance();
                 let mut string = String::new();
-                while let Some(c) = self.get() {
+                while let Some(mut c) = self.get() {
                     if c == '"' { break }
+                    if c == '\\' && matches!(self.peek(), Some('"' | '\\')) {
+                        self.advance();
+                        c = self.get().unwrap();
+                    }
                     string.push(c);
                     self.advance();
                 }
-                if self.get() == None { return error_pos!(pos, "unclosed string") }
+                if self.get().is_none() { return error_pos!(pos, "unclosed string") }
                 pos.extend(self.pos());
                 self.advance();
                 Ok(Some(Token::new(Instr::String(string), pos)))
             }
             Some('\'') => {
                 self.advance();
-                if let Some(char) = self.get() {
+                if let Some(mut char) = self.get() {
                     self.advance();
+                    if char == '\\' && matches!(self.get(), Some('\'' | '\\')) && self.peek() == Some('\'') {
+                        char = self.get().unwrap();
+                        self.advance();
+                    }
                     if self.get() != Some('\'') { return error_pos!(pos, "unclosed character") }
                     pos.extend(self.pos());
                     self.advance();
@@ -190,11 +266,31 @@ impl Lexer {
                     } else {
                         return error_pos!(pos, "unclosed identifier take")
                     }
+                    self.advance_ws();
                 }
-                if self.get() == None { return error_pos!(pos, "unclosed identifier take") }
+                if self.get().is_none() { return error_pos!(pos, "unclosed identifier take") }
                 pos.extend(self.pos());
                 self.advance();
-                Ok(Some(Token::new(Instr::Take(ids.iter().rev().map(|id| id.clone()).collect()), pos)))
+                if let Some(dashes) = ids.iter().position(|id| id == "--") {
+                    let outputs = ids.split_off(dashes + 1);
+                    ids.pop();
+                    return Ok(Some(Token::new(Instr::Effect(ids, outputs), pos)))
+                }
+                if let Some(arrow) = ids.iter().position(|id| id == "->") {
+                    let pushed = ids.split_off(arrow + 1);
+                    ids.pop();
+                    if pushed.iter().any(|id| id == "_") {
+                        return error_pos!(pos, "the hole _ only discards a value, it can't be pushed by a reorder")
+                    }
+                    if let Some((_, id)) = ids.iter().enumerate().find(|(idx, id)| *id != "_" && ids[..*idx].contains(id)) {
+                        return error_pos!(pos, "{id:?} is taken twice in the reorder")
+                    }
+                    if let Some(id) = pushed.iter().find(|id| !ids.contains(id)) {
+                        return error_pos!(pos, "{id:?} isn't taken before the -> of the reorder")
+                    }
+                    return Ok(Some(Token::new(Instr::Reorder(ids.iter().rev().cloned().collect(), pushed), pos)))
+                }
+                Ok(Some(Token::new(Instr::Take(ids.iter().rev().cloned().collect()), pos)))
             }
             Some('{') => {
                 self.advance();
@@ -209,11 +305,20 @@ impl Lexer {
                     } else {
                         return error_pos!(pos, "unclosed identifier copy")
                     }
+                    self.advance_ws();
                 }
-                if self.get() == None { return error_pos!(pos, "unclosed identifier copy") }
+                if self.get().is_none() { return error_pos!(pos, "unclosed identifier copy") }
                 pos.extend(self.pos());
                 self.advance();
-                Ok(Some(Token::new(Instr::CopyTo(ids.iter().rev().map(|id| id.clone()).collect()), pos)))
+                Ok(Some(Token::new(Instr::CopyTo(ids.iter().rev().cloned().collect()), pos)))
+            }
+            Some('[') => {
+                self.advance();
+                Ok(Some(Token::new(Instr::OpenBracket, pos)))
+            }
+            Some(']') => {
+                self.advance();
+                Ok(Some(Token::new(Instr::CloseBracket, pos)))
             }
             Some('@') => {
                 self.advance();
@@ -221,15 +326,21 @@ impl Lexer {
                     pos.extend(token.pos.clone());
                     Ok(Some(Token::new(Instr::Copy(Box::new(token)), pos)))
                 } else {
-                    return error_pos!(pos, "unexpected end")
+                    error_pos!(pos, "unexpected end")
                 }
             }
             Some('#') => {
+                let mut comment = String::new();
                 while let Some(c) = self.get() {
                     if c == '\n' { self.advance(); break }
+                    comment.push(c);
+                    pos.extend(self.pos());
                     self.advance();
                 }
-                self.next()
+                match comment.strip_prefix("##") {
+                    Some(doc) => Ok(Some(Token::new(Instr::Doc(doc.trim().to_string()), pos))),
+                    None => self.next()
+                }
             }
             Some(c) => {
                 self.advance();
@@ -245,15 +356,92 @@ impl Lexer {
             None => Ok(None)
         }
     }
+    /// all tokens of the text, `##` lines that don't come right before a `macro` are plain comments
     pub fn lex(&mut self) -> Result<Vec<Token>, Error> {
         let mut tokens = vec![];
         while let Some(token) = self.next()? {
             tokens.push(token);
         }
-        Ok(tokens)
+        let mut documented = false;
+        let mut kept = vec![];
+        for token in tokens.into_iter().rev() {
+            match &token.instr {
+                Instr::Doc(_) if !documented => continue,
+                Instr::Doc(_) => {}
+                instr => documented = *instr == Instr::Macro
+            }
+            kept.push(token);
+        }
+        kept.reverse();
+        Ok(kept)
     }
 }
 
 pub fn lex(text: String) -> Result<Vec<Token>, Error> {
     Lexer::new(text).lex()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instrs(text: &str) -> Result<Vec<Instr>, Error> {
+        Ok(lex(text.to_string())?.into_iter().map(|token| token.instr).collect())
+    }
+
+    #[test]
+    fn negative_numbers() {
+        assert_eq!(instrs("-5").unwrap(), vec![Instr::Int(-5)]);
+        assert_eq!(instrs("-2.5").unwrap(), vec![Instr::Float(-2.5)]);
+        assert_eq!(instrs("5 3 -").unwrap(), vec![Instr::Int(5), Instr::Int(3), Instr::ID("-".to_string())]);
+    }
+    #[test]
+    fn operators() {
+        assert_eq!(instrs("<=").unwrap(), vec![Instr::ID("<=".to_string())]);
+        assert_eq!(instrs("<>").unwrap(), vec![Instr::ID("<>".to_string())]);
+    }
+    #[test]
+    fn operator_chars_followed_by_letters() {
+        assert!(instrs("-x").is_err());
+        assert!(instrs("3 -x").is_err());
+    }
+    #[test]
+    fn names_with_operator_chars() {
+        assert_eq!(instrs("a-b").unwrap(), vec![Instr::ID("a-b".to_string())]);
+        assert_eq!(instrs("empty?").unwrap(), vec![Instr::ID("empty?".to_string())]);
+    }
+    #[test]
+    fn references() {
+        assert_eq!(instrs("&name").unwrap(), vec![Instr::Ref("name".to_string())]);
+        assert_eq!(instrs("&&").unwrap(), vec![Instr::ID("&&".to_string())]);
+    }
+    #[test]
+    fn escapes() {
+        assert_eq!(instrs(r#""a\"b\\c\d""#).unwrap(), vec![Instr::String(r#"a"b\c\d"#.to_string())]);
+        assert_eq!(instrs(r"'\'' '\\' '\' '''").unwrap(), vec![Instr::Char('\''), Instr::Char('\\'), Instr::Char('\\'), Instr::Char('\'')]);
+        assert!(instrs(r#""a\""#).is_err());
+    }
+    #[test]
+    fn float_words() {
+        assert!(matches!(instrs("nan").unwrap()[..], [Instr::Float(float)] if float.is_nan()));
+        assert_eq!(instrs("inf -inf").unwrap(), vec![Instr::Float(f64::INFINITY), Instr::Float(f64::NEG_INFINITY)]);
+    }
+    #[test]
+    fn doc_comments_only_before_macros() {
+        assert_eq!(instrs("## a\n## b\nmacro").unwrap(), vec![Instr::Doc("a".to_string()), Instr::Doc("b".to_string()), Instr::Macro]);
+        assert_eq!(instrs("1 ## note\nend").unwrap(), vec![Instr::Int(1), Instr::End]);
+        assert_eq!(instrs("## note").unwrap(), vec![]);
+    }
+    #[test]
+    fn positions_after_line_breaks() {
+        let positions: Vec<String> = lex(String::from("a\n\n  b\nc")).unwrap().iter().map(|token| token.pos.to_string()).collect();
+        assert_eq!(positions, ["1:1", "3:3", "4:1"]);
+    }
+    #[test]
+    fn digit_separators() {
+        assert_eq!(instrs("1_000_000 1_234.567_8 -1_000").unwrap(), vec![Instr::Int(1000000), Instr::Float(1234.5678), Instr::Int(-1000)]);
+        // a leading _ starts an identifier
+        assert_eq!(instrs("_1").unwrap(), vec![Instr::ID("_1".to_string())]);
+        assert!(instrs("1_x").is_err());
+    }
+}
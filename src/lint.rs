@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+
+use crate::lexer::Position;
+use crate::parser::{Node, NodeType};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    msg: String,
+    pos: Position
+}
+impl Warning {
+    pub fn new(msg: String, pos: Position) -> Self { Self { msg, pos } }
+    pub fn msg(&self) -> &str { &self.msg }
+    pub fn display_text(&self, path: &str, text: &str) -> String {
+        let mut warning = format!("WARNING: {} - {path}:{}\n", self.msg, self.pos);
+        let lines: Vec<&str> = text.lines().collect();
+        if let Some(slice) = lines.get(self.pos.ln.clone()) {
+            for line in slice {
+                warning.push_str(line);
+            }
+        }
+        warning
+    }
+}
+
+#[derive(Default)]
+struct Linter {
+    warnings: Vec<Warning>,
+    /// every macro in definition order with the position of its first definition
+    definitions: Vec<(String, Position)>,
+    /// the macros referenced from outside their own body
+    references: HashSet<String>
+}
+impl Linter {
+    fn warn(&mut self, msg: String, pos: &Position) {
+        self.warnings.push(Warning::new(msg, pos.clone()));
+    }
+    /// `current` is the macro whose body is being walked, calls of itself don't count as references
+    fn walk(&mut self, node: &Node, current: Option<&str>) {
+        match &node.node {
+            NodeType::Chunk(nodes) => {
+                for (idx, node) in nodes.iter().enumerate() {
+                    if let (NodeType::If(_, else_node), Some(NodeType::Boolean(cond))) = (&node.node, idx.checked_sub(1).map(|idx| &nodes[idx].node)) {
+                        match (cond, else_node) {
+                            (true, Some(_)) => self.warn(String::from("if on the constant true, the else branch never runs"), &node.pos),
+                            (true, None) => self.warn(String::from("if on the constant true, the condition has no effect"), &node.pos),
+                            (false, _) => self.warn(String::from("if on the constant false, the branch never runs"), &node.pos)
+                        }
+                    }
+                    self.walk(node, current);
+                    let endless = matches!(&node.node, NodeType::While(cond, _) if cond.node == NodeType::Boolean(true));
+                    if endless && idx + 1 < nodes.len() {
+                        self.warn(String::from("unreachable code after an endless while loop"), &nodes[idx + 1].pos);
+                    }
+                }
+            }
+            NodeType::ID(id) | NodeType::Ref(id) if current != Some(id.as_str()) => {
+                self.references.insert(id.clone());
+            }
+            NodeType::If(case_node, else_node) => {
+                self.walk(case_node, current);
+                if let Some(else_node) = else_node {
+                    self.walk(else_node, current);
+                }
+            }
+            NodeType::Match(cases, else_node) => {
+                for (_, body) in cases {
+                    self.walk(body, current);
+                }
+                if let Some(else_node) = else_node {
+                    self.walk(else_node, current);
+                }
+            }
+            NodeType::While(cond, body) => {
+                if cond.node == NodeType::Boolean(false) {
+                    self.warn(String::from("while on the constant false, the body never runs"), &node.pos);
+                }
+                self.walk(cond, current);
+                self.walk(body, current);
+            }
+            NodeType::Repeat(body) | NodeType::ForEach(body) | NodeType::WithLines(body) | NodeType::WithRecords(body) | NodeType::Let(body) |
+            NodeType::Block(body) => self.walk(body, current),
+            NodeType::Macro(name, _, _, guard, _, _, body) => {
+                if !self.definitions.iter().any(|(defined, _)| defined == name) {
+                    self.definitions.push((name.clone(), node.pos.clone()));
+                }
+                if let Some(guard) = guard {
+                    self.walk(guard, Some(name));
+                }
+                self.walk(body, Some(name));
+            }
+            NodeType::Alias(_, target) => {
+                self.references.insert(target.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// finds code that can't have an effect without running the script: `if`s and `while`s on constant
+/// booleans, code after an endless `while true` loop and macros that are never used
+pub fn lint(node: &Node) -> Vec<Warning> {
+    let mut linter = Linter::default();
+    linter.walk(node, None);
+    for (name, pos) in linter.definitions.iter() {
+        if !linter.references.contains(name) {
+            linter.warnings.push(Warning::new(format!("macro {name:?} is defined but never used"), pos.clone()));
+        }
+    }
+    linter.warnings.sort_by_key(|warning| warning.pos.idx.start);
+    linter.warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer, parser};
+
+    fn warnings(text: &str) -> Vec<String> {
+        let node = parser::parse(lexer::lex(text.to_string()).unwrap()).unwrap();
+        lint(&node).iter().map(|warning| warning.msg().to_string()).collect()
+    }
+
+    #[test]
+    fn constant_conditions() {
+        assert_eq!(warnings("true if 1 else 2 end true if 1 end false if 1 end"), [
+            "if on the constant true, the else branch never runs",
+            "if on the constant true, the condition has no effect",
+            "if on the constant false, the branch never runs"
+        ]);
+        assert_eq!(warnings("while false do 1 end"), ["while on the constant false, the body never runs"]);
+        assert!(warnings("1 2 = if 1 end while 1 2 = do end").is_empty());
+    }
+
+    #[test]
+    fn unreachable_code() {
+        assert_eq!(warnings("while true do end 1"), ["unreachable code after an endless while loop"]);
+        assert!(warnings("1 while true do end").is_empty());
+    }
+
+    #[test]
+    fn unused_macros() {
+        assert_eq!(warnings("macro f [] 1 end macro g [] g end"), ["macro \"f\" is defined but never used", "macro \"g\" is defined but never used"]);
+        assert!(warnings("macro f [] 1 end macro g [] f end g").is_empty());
+        assert!(warnings("macro f [] 1 end alias h f").is_empty());
+    }
+}
@@ -1,12 +1,27 @@
 #![allow(unused)]
-use std::{env, process::exit, io::{stdout, Write, stdin}, fs};
+use std::{env, process::exit, io::{stdout, Write, stdin}, fs, sync::atomic::{AtomicBool, Ordering}, path::PathBuf, time::Instant};
 use run::Program;
+use config::Config;
 
 mod error;
 mod lexer;
 mod parser;
 mod value;
 mod run;
+mod builtins;
+mod rng;
+mod doc;
+mod lint;
+mod config;
+mod session;
+mod pragma;
+mod effect;
+#[cfg(feature = "html-entities")]
+mod entities;
+#[cfg(feature = "decimal")]
+mod decimal;
+#[cfg(feature = "plugins")]
+mod plugin;
 
 #[macro_export]
 macro_rules! error_pos {
@@ -27,42 +42,312 @@ macro_rules! error_no_pos {
     };
 }
 
-fn run(program: &mut Program, path: &String, text: String) {
+static COLOR: AtomicBool = AtomicBool::new(false);
+static SHOW_TYPES: AtomicBool = AtomicBool::new(false);
+
+fn display_stack(program: &Program) -> String {
+    if SHOW_TYPES.load(Ordering::Relaxed) { program.stack().display_typed() } else { program.stack().to_string() }
+}
+
+fn paint(text: String) -> String {
+    match text.strip_prefix("ERROR:") {
+        Some(rest) if COLOR.load(Ordering::Relaxed) => format!("\x1b[1;31mERROR:\x1b[0m{rest}"),
+        _ => match text.strip_prefix("WARNING:") {
+            Some(rest) if COLOR.load(Ordering::Relaxed) => format!("\x1b[1;33mWARNING:\x1b[0m{rest}"),
+            _ => text
+        }
+    }
+}
+
+fn run(program: &mut Program, path: &str, text: String) {
+    program.steps = 0;
     match lexer::lex(text.clone()) {
         Ok(tokens) => match parser::parse(tokens) {
             Ok(nodes) => match program.run(nodes) {
-                Ok(_) => println!("{}", program.stack),
-                Err(e) => { eprintln!("{}\n{}", program.stack, e.display_text(path, text)) }
+                Ok(_) => println!("{}", display_stack(program)),
+                Err(e) => { eprintln!("{}\n{}", display_stack(program), paint(e.display_text(path, text))) }
+            }
+            Err(e) => { eprintln!("{}", paint(e.display_text(path, text))) }
+        }
+        Err(e) => { eprintln!("{}", paint(e.display_text(path, text))) }
+    }
+}
+
+/// runs a `:command` of the REPL, returns false if the REPL should exit
+fn command(program: &mut Program, input: &str) -> bool {
+    let mut words = input.split_whitespace();
+    let rest = |cmd: &str| input.trim_start()[cmd.len()..].trim().to_string();
+    match words.next() {
+        Some("type") => {
+            let text = rest("type");
+            let mut program = program.fork();
+            let result = lexer::lex(text.clone())
+                .and_then(parser::parse)
+                .and_then(|node| program.run(node));
+            match result {
+                Ok(()) => match program.stack().peek() {
+                    Some(value) => println!("{}", value.typ()),
+                    None => println!("<empty>")
+                }
+                Err(e) => eprintln!("{}", paint(e.display_text("<stdin>", text)))
+            }
+        }
+        Some("time") => {
+            let start = Instant::now();
+            run(program, "<stdin>", rest("time"));
+            println!("took {:?}", start.elapsed());
+        }
+        Some("quit") => return false,
+        Some("doc") => match words.next() {
+            Some(id) => {
+                if let Some(doc) = program.doc(id) {
+                    println!("{doc}");
+                }
+                print!("{}", program.display_macro(id));
+            }
+            None => eprintln!("usage: :doc <name>")
+        }
+        Some("reset") => program.reset(),
+        Some("limit") => match words.next() {
+            Some("off" | "0") => program.step_limit = None,
+            Some(limit) => match limit.replace('_', "").parse() {
+                Ok(limit) => program.step_limit = Some(limit),
+                Err(_) => eprintln!("usage: :limit <steps>|off")
+            }
+            None => match program.step_limit {
+                Some(limit) => println!("step limit {limit}"),
+                None => println!("no step limit")
+            }
+        }
+        Some("types") => {
+            let show_types = !SHOW_TYPES.load(Ordering::Relaxed);
+            SHOW_TYPES.store(show_types, Ordering::Relaxed);
+            println!("showing types {}", if show_types { "on" } else { "off" });
+        }
+        Some(cmd) => eprintln!("unknown command :{cmd}"),
+        None => eprintln!("expected command after ':'")
+    }
+    true
+}
+
+fn load(program: &mut Program, path: &str) {
+    let Some(file) = program.resolve(path) else {
+        eprintln!("couldn't find the file {path:?}"); exit(1)
+    };
+    let text = match fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(e) => { eprintln!("error occurd while reading the file {path:?}: {e}"); exit(1) }
+    };
+    let result = lexer::lex(text.clone())
+        .and_then(parser::parse)
+        .and_then(|node| pragma::scoped(program, &text, |program| program.run(node)));
+    if let Err(e) = result {
+        eprintln!("{}", paint(e.display_text(path, text)));
+        exit(1)
+    }
+}
+
+fn doc<'a>(mut args: impl Iterator<Item = &'a String>) {
+    let Some(path) = args.next() else {
+        eprintln!("usage: str doc <file.str> [-o <docs.md>]"); exit(1)
+    };
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = args.next(),
+            _ => { eprintln!("unexpected argument {arg:?}"); exit(1) }
+        }
+    }
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => { eprintln!("error occurd while reading the file {path:?}: {e}"); exit(1) }
+    };
+    let docs = lexer::lex(text.clone())
+        .and_then(parser::parse)
+        .and_then(|node| doc::markdown(path, &node));
+    match docs {
+        Ok(docs) => match output {
+            Some(output) => if let Err(e) = fs::write(output, docs) {
+                eprintln!("error occurd while writing the file {output:?}: {e}"); exit(1)
             }
-            Err(e) => { eprintln!("{}", e.display_text(path, text)) }
+            None => print!("{docs}")
         }
-        Err(e) => { eprintln!("{}", e.display_text(path, text)) }
+        Err(e) => { eprintln!("{}", paint(e.display_text(path, text))); exit(1) }
+    }
+}
+
+/// prints the warnings of a script and exits with 1 if there are any
+fn lint<'a>(mut args: impl Iterator<Item = &'a String>) {
+    let (Some(path), None) = (args.next(), args.next()) else {
+        eprintln!("usage: str lint <file.str>"); exit(1)
+    };
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => { eprintln!("error occurd while reading the file {path:?}: {e}"); exit(1) }
+    };
+    match lexer::lex(text.clone()).and_then(parser::parse) {
+        Ok(node) => {
+            let warnings = lint::lint(&node);
+            for warning in warnings.iter() {
+                eprintln!("{}", paint(warning.display_text(path, &text)));
+            }
+            if !warnings.is_empty() { exit(1) }
+        }
+        Err(e) => { eprintln!("{}", paint(e.display_text(path, text))); exit(1) }
     }
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let mut args = args.iter();
-    args.next();
-    match args.next() {
+    let mut args = args.iter().skip(1).peekable();
+    let config = Config::load();
+    COLOR.store(config.color, Ordering::Relaxed);
+    match args.peek().map(|arg| arg.as_str()) {
+        Some("doc") => { args.next(); return doc(args) }
+        Some("lint") => { args.next(); return lint(args) }
+        _ => {}
+    }
+    let mut preloads: Vec<String> = match env::var_os("STR_PRELUDE") {
+        Some(paths) => env::split_paths(&paths).map(|path| path.to_string_lossy().to_string()).collect(),
+        None => config.prelude.clone()
+    };
+    let mut path = None;
+    let mut session = None;
+    let mut float_nan = false;
+    let mut strict_index = false;
+    let mut allow_env = false;
+    let mut strict_vars = false;
+    let mut copy_vars = false;
+    let mut trace_origins = false;
+    let mut emit = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--float-nan" => float_nan = true,
+            "--strict-index" => strict_index = true,
+            "--allow-env" => allow_env = true,
+            "--strict-vars" => strict_vars = true,
+            "--copy-vars" => copy_vars = true,
+            "--trace-origins" => trace_origins = true,
+            "--emit" => match args.next().map(|arg| arg.as_str()) {
+                Some("pretty-ast") => emit = Some("pretty-ast"),
+                _ => { eprintln!("expected pretty-ast after --emit"); exit(1) }
+            }
+            "--show-types" => SHOW_TYPES.store(true, Ordering::Relaxed),
+            "--session" => match args.next() {
+                Some(file) => session = Some(file),
+                None => { eprintln!("expected a file after --session"); exit(1) }
+            }
+            "--preload" => match args.next() {
+                Some(preload) => preloads.push(preload.clone()),
+                None => { eprintln!("expected a file after --preload"); exit(1) }
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => { eprintln!("unexpected argument {arg:?}"); exit(1) }
+        }
+    }
+    if let (Some(_), Some(path)) = (session, path) {
+        eprintln!("--session only works in the REPL, it can't be used with the file {path:?}"); exit(1)
+    }
+    if let Some("pretty-ast") = emit {
+        let Some(path) = path else {
+            eprintln!("--emit needs a file"); exit(1)
+        };
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => { eprintln!("error occurd while reading the file {path:?}: {e}"); exit(1) }
+        };
+        match lexer::lex(text.clone()).and_then(parser::parse) {
+            Ok(node) => print!("{}", node.pretty()),
+            Err(e) => { eprintln!("{}", paint(e.display_text(path, text))); exit(1) }
+        }
+        return
+    }
+    let mut program = Program::std_program();
+    program.float_nan = float_nan;
+    program.strict_index = strict_index;
+    program.allow_env = allow_env;
+    program.strict_vars = strict_vars;
+    program.copy_vars = copy_vars;
+    if trace_origins {
+        program.stack_mut().trace_origins();
+    }
+    if let Some(paths) = env::var_os("STR_PATH") {
+        program.search_path.extend(env::split_paths(&paths));
+    }
+    program.search_path.extend(config.path.iter().map(PathBuf::from));
+    for preload in preloads.iter() {
+        load(&mut program, preload);
+    }
+    match path {
         Some(path) => match fs::read_to_string(path) {
             Ok(text) => {
-                let mut program = Program::std_program();
-                run(&mut program, path, text);
+                if let Err(e) = pragma::apply(&mut program, &text) {
+                    eprintln!("{}", paint(e.display_text(path, text))); exit(1)
+                }
+                run(&mut program, path, text)
             }
             Err(e) => { eprintln!("error occurd while reading the file {path:?}: {e}"); exit(1) }
         }
         None => {
-            let mut program = Program::std_program();
+            if let Some(file) = session {
+                if let Ok(text) = fs::read_to_string(file) {
+                    if let Err(e) = session::load(&mut program, &text) {
+                        eprintln!("error occurd while loading the session {file:?}: {e}"); exit(1)
+                    }
+                }
+            }
+            program.step_limit = config.limit;
             let path = &"<stdin>".to_string();
             loop {
                 let mut input = String::new();
                 print!("> ");
                 let _ = stdout().flush();
-                let _ = stdin().read_line(&mut input);
+                if let Ok(0) | Err(_) = stdin().read_line(&mut input) { println!(); break }
+                if let Some(input) = input.trim().strip_prefix(':') {
+                    if !command(&mut program, input) { break }
+                    continue
+                }
                 run(&mut program, path, input);
                 println!();
             }
+            if let Some(file) = session {
+                if let Err(e) = fs::write(file, session::save(&program)) {
+                    eprintln!("error occurd while saving the session {file:?}: {e}"); exit(1)
+                }
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run::tests::temp_file;
+
+    #[test]
+    fn preload_defines_macros_with_scoped_pragmas() {
+        let path = temp_file("prelude.str", "#!str: copy-vars\nmacro greet [] \"hi\" end");
+        let mut program = Program::std_program();
+        load(&mut program, &path);
+        assert!(program.get_macro("greet").is_some());
+        // the pragma only applies while the prelude runs
+        assert!(!program.copy_vars);
+    }
+
+    #[test]
+    fn type_command_leaves_the_session_alone() {
+        let mut program = Program::std_program();
+        assert!(command(&mut program, "type macro f [] 1 end 1 2 +"));
+        assert!(command(&mut program, "type 1 0 /"));
+        assert!(program.stack().as_slice().is_empty());
+        assert!(program.get_macro("f").is_none());
+    }
+
+    #[test]
+    fn time_command_runs_in_the_session() {
+        let mut program = Program::std_program();
+        assert!(command(&mut program, "time 1 2 +"));
+        assert_eq!(program.stack().as_slice(), [value::Value::Int(3)]);
+        assert!(!command(&mut program, "quit"));
+    }
+}
@@ -1,15 +1,58 @@
+use std::fmt::Display;
+
 use crate::error;
 use crate::error::Error;
 use crate::error_pos;
-use crate::value::Type;
+use crate::value::{self, Type, Value};
 use crate::lexer::{Token, Position, Instr};
+use crate::effect::Effect;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum NodeType {
     Chunk(Vec<Node>),
     String(String), Char(char), Int(i64), Float(f64), Boolean(bool),
-    ID(String), Take(Vec<String>), CopyTo(Vec<String>), Copy(Box<Token>),
-    If(Box<Node>, Option<Box<Node>>), Repeat(Box<Node>), Macro(String, Vec<Type>, Box<Node>)
+    ID(String), Ref(String), Take(Vec<String>), CopyTo(Vec<String>), Copy(Box<Token>), Reorder(Vec<String>, Vec<String>),
+    If(Box<Node>, Option<Box<Node>>), Match(Vec<(Case, Node)>, Option<Box<Node>>), Repeat(Box<Node>), While(Box<Node>, Box<Node>), ForEach(Box<Node>), WithLines(Box<Node>), WithRecords(Box<Node>), Let(Box<Node>), Block(Box<Node>), Macro(String, Vec<Type>, Option<Vec<Type>>, Option<Box<Node>>, Option<Effect>, Option<String>, Box<Node>),
+    Alias(String, String), Include(String), Import(String, String)
+}
+/// what a `case` of a `match` compares the value against
+#[derive(Debug, Clone, PartialEq)]
+pub enum Case {
+    Value(Value), Type(Type)
+}
+impl Case {
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            Self::Value(case) => case == value,
+            Self::Type(typ) => &value.typ() == typ
+        }
+    }
+}
+/// the string as a literal, with backslashes and `"` escaped
+fn quote(string: &str) -> String {
+    format!("\"{}\"", string.replace('\\', "\\\\").replace('"', "\\\""))
+}
+/// the char as a literal, `'` and backslashes are escaped
+fn char_literal(char: char) -> String {
+    match char {
+        '\'' | '\\' => format!("'\\{char}'"),
+        _ => format!("'{char}'")
+    }
+}
+/// the float as a literal, `nan`, `inf` and `-inf` for the values without digits
+fn float_literal(float: f64) -> String {
+    if float.is_nan() { String::from("nan") } else { format!("{float:?}") }
+}
+impl Display for Case {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Value(Value::String(string)) => write!(f, "{}", quote(string)),
+            Self::Value(Value::Char(char)) => write!(f, "{}", char_literal(*char)),
+            Self::Value(Value::Float(float)) => write!(f, "{}", float_literal(*float)),
+            Self::Value(value) => write!(f, "{value:?}"),
+            Self::Type(typ) => write!(f, "{typ}")
+        }
+    }
 }
 #[derive(Debug, Clone, PartialEq)]
 pub struct Node {
@@ -18,6 +61,151 @@ pub struct Node {
 }
 impl Node {
     pub fn new(node: NodeType, pos: Position) -> Self { Self { node, pos } }
+    /// an indented tree of the node and its children, one per line with its position
+    pub fn pretty(&self) -> String {
+        let mut text = String::new();
+        self.pretty_into(0, &mut text);
+        text
+    }
+    fn pretty_into(&self, depth: usize, text: &mut String) {
+        let ids = |ids: &Vec<String>| ids.iter().rev().cloned().collect::<Vec<String>>().join(" ");
+        let label = match &self.node {
+            NodeType::Chunk(nodes) => format!("Chunk ({} nodes)", nodes.len()),
+            NodeType::String(string) => format!("String {string:?}"),
+            NodeType::Char(char) => format!("Char {char:?}"),
+            NodeType::Int(int) => format!("Int {int}"),
+            NodeType::Float(float) => format!("Float {float:?}"),
+            NodeType::Boolean(boolean) => format!("Boolean {boolean}"),
+            NodeType::ID(id) => format!("ID {id}"),
+            NodeType::Ref(id) => format!("Ref {id}"),
+            NodeType::Take(take) => format!("Take ({})", ids(take)),
+            NodeType::CopyTo(copy) => format!("CopyTo {{{}}}", ids(copy)),
+            NodeType::Reorder(take, pushed) => format!("Reorder ({} -> {})", ids(take), pushed.join(" ")),
+            NodeType::Copy(token) => format!("Copy {token}"),
+            NodeType::If(_, None) => String::from("If"),
+            NodeType::If(_, Some(_)) => String::from("If with else"),
+            NodeType::Match(cases, _) => format!("Match ({} cases)", cases.len()),
+            NodeType::Repeat(_) => String::from("Repeat"),
+            NodeType::While(..) => String::from("While"),
+            NodeType::ForEach(_) => String::from("ForEach"),
+            NodeType::WithLines(_) => String::from("WithLines"),
+            NodeType::WithRecords(_) => String::from("WithRecords"),
+            NodeType::Let(_) => String::from("Let"),
+            NodeType::Block(_) => String::from("Block"),
+            NodeType::Macro(name, types, outputs, _, effect, doc, _) => {
+                let mut label = format!("Macro {name} {}", value::signature(types, outputs.as_deref()));
+                if let Some(effect) = effect {
+                    label.push_str(&format!(" {effect}"));
+                }
+                if let Some(doc) = doc {
+                    label.push_str(&format!(" {doc:?}"));
+                }
+                label
+            }
+            NodeType::Alias(name, target) => format!("Alias {name} -> {target}"),
+            NodeType::Include(path) => format!("Include {path:?}"),
+            NodeType::Import(path, name) => format!("Import {path:?} as {name}"),
+        };
+        text.push_str(&format!("{}{label} @ {}\n", "  ".repeat(depth), self.pos));
+        match &self.node {
+            NodeType::Chunk(nodes) => {
+                for node in nodes {
+                    node.pretty_into(depth + 1, text);
+                }
+            }
+            NodeType::If(case_node, else_node) => {
+                case_node.pretty_into(depth + 1, text);
+                if let Some(else_node) = else_node {
+                    text.push_str(&format!("{}else\n", "  ".repeat(depth)));
+                    else_node.pretty_into(depth + 1, text);
+                }
+            }
+            NodeType::Match(cases, else_node) => {
+                for (case, body) in cases {
+                    text.push_str(&format!("{}case {case}\n", "  ".repeat(depth)));
+                    body.pretty_into(depth + 1, text);
+                }
+                if let Some(else_node) = else_node {
+                    text.push_str(&format!("{}else\n", "  ".repeat(depth)));
+                    else_node.pretty_into(depth + 1, text);
+                }
+            }
+            NodeType::While(cond, body) => {
+                cond.pretty_into(depth + 1, text);
+                text.push_str(&format!("{}do\n", "  ".repeat(depth)));
+                body.pretty_into(depth + 1, text);
+            }
+            NodeType::Repeat(body) | NodeType::ForEach(body) | NodeType::WithLines(body) | NodeType::WithRecords(body) | NodeType::Let(body) |
+            NodeType::Block(body) => body.pretty_into(depth + 1, text),
+            NodeType::Macro(_, _, _, guard, _, _, body) => {
+                if let Some(guard) = guard {
+                    text.push_str(&format!("{}when\n", "  ".repeat(depth)));
+                    guard.pretty_into(depth + 1, text);
+                    text.push_str(&format!("{}body\n", "  ".repeat(depth)));
+                }
+                body.pretty_into(depth + 1, text)
+            }
+            _ => {}
+        }
+    }
+}
+/// prints the node back as source code that parses to the same node
+impl Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ids = |ids: &Vec<String>| ids.iter().rev().map(|id| id.to_string()).collect::<Vec<String>>().join(" ");
+        match &self.node {
+            NodeType::Chunk(nodes) => write!(f, "{}", nodes.iter().map(|node| node.to_string()).collect::<Vec<String>>().join(" ")),
+            NodeType::String(string) => write!(f, "{}", quote(string)),
+            NodeType::Char(char) => write!(f, "{}", char_literal(*char)),
+            NodeType::Int(int) => write!(f, "{int}"),
+            NodeType::Float(float) => write!(f, "{}", float_literal(*float)),
+            NodeType::Boolean(boolean) => write!(f, "{boolean}"),
+            NodeType::ID(id) => write!(f, "{id}"),
+            NodeType::Ref(id) => write!(f, "&{id}"),
+            NodeType::Take(take) => write!(f, "({})", ids(take)),
+            NodeType::CopyTo(copy) => write!(f, "{{{}}}", ids(copy)),
+            NodeType::Reorder(take, pushed) => write!(f, "({} -> {})", ids(take), pushed.join(" ")),
+            NodeType::Copy(token) => match &token.instr {
+                Instr::CopyTo(copy) => write!(f, "@{{{}}}", ids(copy)),
+                instr => write!(f, "@{instr}")
+            }
+            NodeType::If(case, None) => write!(f, "if {case} end"),
+            NodeType::If(case, Some(else_case)) => write!(f, "if {case} else {else_case} end"),
+            NodeType::Match(cases, else_node) => {
+                write!(f, "match ")?;
+                for (case, body) in cases {
+                    write!(f, "case {case} {body} ")?;
+                }
+                if let Some(else_node) = else_node {
+                    write!(f, "else {else_node} ")?;
+                }
+                write!(f, "end")
+            }
+            NodeType::Repeat(body) => write!(f, "repeat {body} end"),
+            NodeType::While(cond, body) => write!(f, "while {cond} do {body} end"),
+            NodeType::ForEach(body) => write!(f, "foreach {body} end"),
+            NodeType::WithLines(body) => write!(f, "with-lines {body} end"),
+            NodeType::WithRecords(body) => write!(f, "with-records {body} end"),
+            NodeType::Let(body) => write!(f, "let {body} end"),
+            NodeType::Block(body) => write!(f, "[{body}]"),
+            NodeType::Macro(name, types, outputs, guard, effect, doc, body) => {
+                for line in doc.iter().flat_map(|doc| doc.lines()) {
+                    writeln!(f, "## {line}")?;
+                }
+                write!(f, "macro {name} {} ", value::signature(types, outputs.as_deref()))?;
+                if let Some(guard) = guard {
+                    write!(f, "when [{guard}] ")?;
+                }
+                if let Some(effect) = effect {
+                    write!(f, "{effect} ")?;
+                }
+                write!(f, "{body} end")
+            }
+            NodeType::Alias(name, target) => write!(f, "alias {name} {target}"),
+            NodeType::Include(path) => write!(f, "include \"{path}\""),
+            NodeType::Import(path, name) => write!(f, "import \"{path}\" as {name}"),
+        }
+    }
 }
 
 pub struct Parser {
@@ -35,6 +223,51 @@ impl Parser {
     pub fn advance(&mut self) {
         self.idx += 1;
     }
+    /// takes a string that ends the line of a macro's signature as its doc, if the body goes on
+    /// below it, any other string is part of the body
+    fn doc_string(&mut self) -> Option<String> {
+        let signature = &self.tokens.get(self.idx.checked_sub(1)?)?.pos;
+        let Token { instr: Instr::String(doc), pos } = self.get()? else { return None };
+        let next = self.tokens.get(self.idx + 1)?;
+        if pos.ln.start + 1 != signature.ln.end || next.pos.ln.start < pos.ln.end || next.instr == Instr::End {
+            return None
+        }
+        let doc = doc.clone();
+        self.advance();
+        Some(doc)
+    }
+    /// the nodes up to the next `end`, extending `pos` over them
+    fn body(&mut self, pos: &mut Position) -> Result<Box<Node>, Error> {
+        let mut nodes = vec![];
+        while let Some(token) = self.get() {
+            if token.instr == Instr::End { self.advance(); break }
+            if let Some(node) = self.next()? {
+                pos.extend(node.pos.clone());
+                nodes.push(node);
+            }
+        }
+        Ok(if nodes.len() == 1 {
+            Box::new(nodes[0].clone())
+        } else {
+            Box::new(Node::new(NodeType::Chunk(nodes), pos.clone()))
+        })
+    }
+    /// the nodes of a `match` arm, up to the next `case`, `else` or `end`
+    fn arm(&mut self, pos: &mut Position) -> Result<Node, Error> {
+        let mut nodes = vec![];
+        while let Some(token) = self.get() {
+            if matches!(token.instr, Instr::Case | Instr::Else | Instr::End) { break }
+            if let Some(node) = self.next()? {
+                pos.extend(node.pos.clone());
+                nodes.push(node);
+            }
+        }
+        Ok(if nodes.len() == 1 {
+            nodes[0].clone()
+        } else {
+            Node::new(NodeType::Chunk(nodes), pos.clone())
+        })
+    }
     pub fn next(&mut self) -> Result<Option<Node>, Error> {
         match self.get() {
             Some(token) => {
@@ -45,10 +278,29 @@ impl Parser {
                     Instr::Int(int) => { self.advance(); Ok(Some(Node::new(NodeType::Int(int), pos))) }
                     Instr::Float(float) => { self.advance(); Ok(Some(Node::new(NodeType::Float(float), pos))) }
                     Instr::Boolean(boolean) => { self.advance(); Ok(Some(Node::new(NodeType::Boolean(boolean), pos))) }
+                    Instr::Doc(_) => {
+                        let mut lines = vec![];
+                        while let Some(Token { instr: Instr::Doc(line), .. }) = self.get() {
+                            lines.push(line.clone());
+                            self.advance();
+                        }
+                        if self.get().map(|token| &token.instr) != Some(&Instr::Macro) {
+                            return self.next()
+                        }
+                        match self.next()? {
+                            Some(Node { node: NodeType::Macro(name, types, outputs, guard, effect, None, body), pos }) =>
+                                Ok(Some(Node::new(NodeType::Macro(name, types, outputs, guard, effect, Some(lines.join("\n")), body), pos))),
+                            Some(Node { node: NodeType::Macro(name, ..), pos }) =>
+                                error_pos!(&pos, "macro {name:?} has both ## comments and a doc string"),
+                            node => Ok(node)
+                        }
+                    }
                     Instr::ID(id) => { self.advance(); Ok(Some(Node::new(NodeType::ID(id), pos))) }
+                    Instr::Ref(id) => { self.advance(); Ok(Some(Node::new(NodeType::Ref(id), pos))) }
                     Instr::Take(ids) => { self.advance(); Ok(Some(Node::new(NodeType::Take(ids), pos))) }
                     Instr::Copy(ids) => { self.advance(); Ok(Some(Node::new(NodeType::Copy(ids), pos))) }
                     Instr::CopyTo(instr) => { self.advance(); Ok(Some(Node::new(NodeType::CopyTo(instr), pos))) }
+                    Instr::Reorder(ids, pushed) => { self.advance(); Ok(Some(Node::new(NodeType::Reorder(ids, pushed), pos))) }
                     Instr::If => {
                         self.advance();
                         let mut nodes = vec![];
@@ -87,9 +339,164 @@ impl Parser {
                         };
                         Ok(Some(Node::new(NodeType::If(chunk, else_node), pos)))
                     }
+                    Instr::Match => {
+                        self.advance();
+                        let mut cases = vec![];
+                        let mut else_node = None;
+                        loop {
+                            let Some(token) = self.get().cloned() else {
+                                return error_pos!(pos, "unclosed match")
+                            };
+                            match token.instr {
+                                Instr::End => { self.advance(); break }
+                                Instr::Case if else_node.is_none() => {
+                                    self.advance();
+                                    let case = match self.get().map(|token| token.instr.clone()) {
+                                        Some(Instr::String(string)) => Case::Value(Value::String(string)),
+                                        Some(Instr::Char(char)) => Case::Value(Value::Char(char)),
+                                        Some(Instr::Int(int)) => Case::Value(Value::Int(int)),
+                                        Some(Instr::Float(float)) => Case::Value(Value::Float(float)),
+                                        Some(Instr::Boolean(boolean)) => Case::Value(Value::Boolean(boolean)),
+                                        Some(Instr::ID(id)) => match Type::get(&id) {
+                                            Some(typ) => Case::Type(typ),
+                                            None => return error_pos!(self.pos().unwrap(), "unknown type {id:?}")
+                                        }
+                                        _ => return error_pos!(&token.pos, "expected a literal or type name after case")
+                                    };
+                                    self.advance();
+                                    cases.push((case, self.arm(&mut pos)?));
+                                }
+                                Instr::Else if else_node.is_none() => {
+                                    self.advance();
+                                    else_node = Some(Box::new(self.arm(&mut pos)?));
+                                }
+                                _ => return error_pos!(&token.pos, "expected {}end of the match, got {}",
+                                    if else_node.is_none() { "case, else or " } else { "" }, token.instr.name())
+                            }
+                        }
+                        Ok(Some(Node::new(NodeType::Match(cases, else_node), pos)))
+                    }
                     Instr::Repeat => {
+                        self.advance();
+                        let body = self.body(&mut pos)?;
+                        Ok(Some(Node::new(NodeType::Repeat(body), pos)))
+                    }
+                    Instr::While => {
+                        self.advance();
+                        let mut nodes = vec![];
+                        loop {
+                            let Some(token) = self.get() else {
+                                return error_pos!(pos, "expected do after the while condition")
+                            };
+                            if token.instr == Instr::Do { self.advance(); break }
+                            if token.instr == Instr::End { return error_pos!(&token.pos, "expected do after the while condition") }
+                            if let Some(node) = self.next()? {
+                                pos.extend(node.pos.clone());
+                                nodes.push(node);
+                            }
+                        }
+                        let cond = if nodes.len() == 1 {
+                            Box::new(nodes[0].clone())
+                        } else {
+                            Box::new(Node::new(NodeType::Chunk(nodes), pos.clone()))
+                        };
+                        let body = self.body(&mut pos)?;
+                        Ok(Some(Node::new(NodeType::While(cond, body), pos)))
+                    }
+                    Instr::ForEach => {
+                        self.advance();
+                        let body = self.body(&mut pos)?;
+                        Ok(Some(Node::new(NodeType::ForEach(body), pos)))
+                    }
+                    Instr::WithLines => {
+                        self.advance();
+                        let body = self.body(&mut pos)?;
+                        Ok(Some(Node::new(NodeType::WithLines(body), pos)))
+                    }
+                    Instr::WithRecords => {
+                        self.advance();
+                        let body = self.body(&mut pos)?;
+                        Ok(Some(Node::new(NodeType::WithRecords(body), pos)))
+                    }
+                    Instr::Let => {
+                        self.advance();
+                        let body = self.body(&mut pos)?;
+                        Ok(Some(Node::new(NodeType::Let(body), pos)))
+                    }
+                    Instr::OpenBracket => {
                         self.advance();
                         let mut nodes = vec![];
+                        loop {
+                            let Some(token) = self.get() else {
+                                return error_pos!(pos, "unclosed quotation")
+                            };
+                            if token.instr == Instr::CloseBracket {
+                                pos.extend(token.pos.clone());
+                                self.advance();
+                                break
+                            }
+                            if let Some(node) = self.next()? {
+                                pos.extend(node.pos.clone());
+                                nodes.push(node);
+                            }
+                        }
+                        let body = if nodes.len() == 1 {
+                            Box::new(nodes[0].clone())
+                        } else {
+                            Box::new(Node::new(NodeType::Chunk(nodes), pos.clone()))
+                        };
+                        Ok(Some(Node::new(NodeType::Block(body), pos)))
+                    }
+                    Instr::Macro => {
+                        self.advance();
+                        let Some(token) = self.get() else {
+                            return error_pos!(pos, "expected macro name")
+                        };
+                        let Instr::ID(name) = token.instr.clone() else {
+                            return error_pos!(&token.pos, "expected macro name, got {}", token.instr.name())
+                        };
+                        self.advance();
+                        let mut types = vec![];
+                        let mut outputs: Option<Vec<Type>> = None;
+                        if self.get().map(|token| &token.instr) == Some(&Instr::OpenBracket) {
+                            self.advance();
+                            loop {
+                                let Some(token) = self.get() else {
+                                    return error_pos!(pos, "unclosed macro signature")
+                                };
+                                match &token.instr {
+                                    Instr::CloseBracket => { self.advance(); break }
+                                    Instr::ID(id) if id == "--" && outputs.is_none() => outputs = Some(vec![]),
+                                    Instr::ID(id) => match Type::get(id) {
+                                        Some(typ) => match &mut outputs {
+                                            Some(outputs) => outputs.push(typ),
+                                            None => types.push(typ)
+                                        }
+                                        None => return error_pos!(&token.pos, "unknown type {id:?}")
+                                    }
+                                    _ => return error_pos!(&token.pos, "expected type, got {}", token.instr.name())
+                                }
+                                self.advance();
+                            }
+                        }
+                        let mut guard = None;
+                        if let Some(Token { instr: Instr::ID(id), pos: when_pos }) = self.get() {
+                            if id == "when" {
+                                let when_pos = when_pos.clone();
+                                self.advance();
+                                match self.next()? {
+                                    Some(Node { node: NodeType::Block(body), .. }) => guard = Some(body),
+                                    _ => return error_pos!(when_pos, "expected a guard block like [0 >=] after when")
+                                }
+                            }
+                        }
+                        let mut effect = None;
+                        if let Some(Token { instr: Instr::Effect(inputs, outputs), .. }) = self.get() {
+                            effect = Some(Effect { inputs: inputs.clone(), outputs: outputs.clone() });
+                            self.advance();
+                        }
+                        let doc = self.doc_string();
+                        let mut nodes = vec![];
                         while let Some(token) = self.get() {
                             if token.instr == Instr::End { self.advance(); break }
                             if let Some(node) = self.next()? {
@@ -102,7 +509,61 @@ impl Parser {
                         } else {
                             Box::new(Node::new(NodeType::Chunk(nodes), pos.clone()))
                         };
-                        Ok(Some(Node::new(NodeType::Repeat(chunk), pos)))
+                        Ok(Some(Node::new(NodeType::Macro(name, types, outputs, guard, effect, doc, chunk), pos)))
+                    }
+                    Instr::Alias => {
+                        self.advance();
+                        let mut names = vec![];
+                        for expected in ["alias name", "macro name"] {
+                            let Some(token) = self.get() else {
+                                return error_pos!(pos, "expected {expected}")
+                            };
+                            let Instr::ID(name) = token.instr.clone() else {
+                                return error_pos!(&token.pos, "expected {expected}, got {}", token.instr.name())
+                            };
+                            pos.extend(token.pos.clone());
+                            names.push(name);
+                            self.advance();
+                        }
+                        let target = names.pop().unwrap();
+                        let name = names.pop().unwrap();
+                        Ok(Some(Node::new(NodeType::Alias(name, target), pos)))
+                    }
+                    Instr::Include => {
+                        self.advance();
+                        let Some(token) = self.get() else {
+                            return error_pos!(pos, "expected file path")
+                        };
+                        let Instr::String(path) = token.instr.clone() else {
+                            return error_pos!(&token.pos, "expected file path, got {}", token.instr.name())
+                        };
+                        pos.extend(token.pos.clone());
+                        self.advance();
+                        Ok(Some(Node::new(NodeType::Include(path), pos)))
+                    }
+                    Instr::Import => {
+                        self.advance();
+                        let Some(token) = self.get() else {
+                            return error_pos!(pos, "expected file path")
+                        };
+                        let Instr::String(path) = token.instr.clone() else {
+                            return error_pos!(&token.pos, "expected file path, got {}", token.instr.name())
+                        };
+                        self.advance();
+                        match self.get() {
+                            Some(Token { instr: Instr::ID(id), .. }) if id == "as" => self.advance(),
+                            Some(token) => return error_pos!(&token.pos, "expected as after the file path, got {}", token.instr.name()),
+                            None => return error_pos!(pos, "expected as after the file path")
+                        }
+                        let Some(token) = self.get() else {
+                            return error_pos!(pos, "expected module name")
+                        };
+                        let Instr::ID(name) = token.instr.clone() else {
+                            return error_pos!(&token.pos, "expected module name, got {}", token.instr.name())
+                        };
+                        pos.extend(token.pos.clone());
+                        self.advance();
+                        Ok(Some(Node::new(NodeType::Import(path, name), pos)))
                     }
                     _ => error_pos!(&token.pos, "unexpected {}", token.instr)
                 }
@@ -111,7 +572,7 @@ impl Parser {
         }
     }
     pub fn parse(&mut self) -> Result<Node, Error> {
-        if self.tokens.len() == 0 { return Ok(Node::new(NodeType::Chunk(vec![]), Position::zero())) }
+        if self.tokens.is_empty() { return Ok(Node::new(NodeType::Chunk(vec![]), Position::zero())) }
         let mut nodes = vec![];
         let mut pos = self.pos().unwrap().clone();
         while let Some(node) = self.next()? {
@@ -124,4 +585,74 @@ impl Parser {
 
 pub fn parse(tokens: Vec<Token>) -> Result<Node, Error> {
     Parser::new(tokens).parse()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lexer, parser};
+    use crate::run::tests::{run, stack};
+    use crate::value::Value;
+
+    #[test]
+    fn macro_body_of_only_a_string() {
+        assert_eq!(stack(r#"macro hi [] "hello" end hi 1"#).unwrap(), vec![Value::String("hello".to_string()), Value::Int(1)]);
+        assert_eq!(run(r#"macro hi [] "hello" end"#).unwrap().doc("hi"), None);
+    }
+    #[test]
+    fn macro_body_starting_with_a_string() {
+        assert_eq!(stack(r#"macro q [str] "!" + end "hi" q"#).unwrap(), vec![Value::String("hi!".to_string())]);
+    }
+    #[test]
+    fn doc_comments() {
+        let program = run("## greets\n## twice\nmacro hi [] \"hello\" end").unwrap();
+        assert_eq!(program.doc("hi"), Some("greets\ntwice"));
+    }
+    #[test]
+    fn doc_comments_before_end_and_else() {
+        assert_eq!(stack("true if\n 1\n ## note\nend").unwrap(), vec![Value::Int(1)]);
+        assert_eq!(stack("false if\n 1\n ## note\nelse\n 2\n ## note\nend").unwrap(), vec![Value::Int(2)]);
+        assert_eq!(stack("3 repeat\n 1\n ## note\nend").unwrap(), vec![Value::Int(1); 3]);
+        assert_eq!(run("## a\n1 drop\nmacro f [] 1 end").unwrap().doc("f"), None);
+    }
+    #[test]
+    fn doc_strings() {
+        let program = run("macro inc [int] \"adds one\"\n  1 +\nend 1 inc").unwrap();
+        assert_eq!(program.doc("inc"), Some("adds one"));
+        assert_eq!(program.stack.as_slice(), &[Value::Int(2)]);
+        let program = run("macro hi [] when [true] \"greets\"\n  \"hello\"\nend hi").unwrap();
+        assert_eq!(program.doc("hi"), Some("greets"));
+        assert_eq!(program.stack.as_slice(), &[Value::String("hello".to_string())]);
+    }
+    #[test]
+    fn strings_that_are_not_docs() {
+        // more body on the signature line, the string on its own line or nothing but `end` after it
+        assert_eq!(stack("macro q [str] \"!\" +\nend \"hi\" q").unwrap(), vec![Value::String("hi!".to_string())]);
+        assert_eq!(run("macro q [str]\n  \"!\" +\nend").unwrap().doc("q"), None);
+        assert_eq!(stack("macro hi [] \"hello\"\nend hi").unwrap(), vec![Value::String("hello".to_string())]);
+    }
+    #[test]
+    fn doc_comments_and_doc_string() {
+        assert!(run("## greets\nmacro hi [] \"greets\"\n  \"hello\"\nend").is_err());
+    }
+    #[test]
+    fn display_keeps_docs() {
+        let node = super::parse(lexer::lex("## greets\n## twice\nmacro hi [] \"hello\" end".to_string()).unwrap()).unwrap();
+        assert_eq!(super::parse(lexer::lex(node.to_string()).unwrap()).unwrap().to_string(), node.to_string());
+        assert_eq!(run(&node.to_string()).unwrap().doc("hi"), Some("greets\ntwice"));
+    }
+
+    #[test]
+    fn pretty_ast() {
+        let node = parser::parse(lexer::lex(String::from("1 (a b)\nif \"x\" else [2] end")).unwrap()).unwrap();
+        assert_eq!(node.pretty(), "\
+Chunk (3 nodes) @ 1:1
+  Int 1 @ 1:1
+  Take (a b) @ 1:3
+  If with else @ 2:1
+    String \"x\" @ 2:4
+  else
+    Block @ 2:13
+      Int 2 @ 2:14
+");
+    }
+}
@@ -0,0 +1,127 @@
+//! native macro packs loaded from dynamic libraries with `load-plugin`
+//!
+//! a plugin exports two C functions:
+//!
+//! ```c
+//! // called once after loading, calls `reg(registry, name, arity, func)` for every macro it provides
+//! void str_plugin_register(void *registry,
+//!     void (*reg)(void *registry, const char *name, size_t arity,
+//!                 char *(*func)(const char *const *args, size_t argc)));
+//! // frees a string returned by one of the registered functions
+//! void str_plugin_free(char *string);
+//! ```
+//!
+//! a registered macro takes `arity` values of any type from the stack, which are passed
+//! as UTF-8 strings (bottom first), and pushes the returned string. returning `NULL` fails the macro.
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+
+use crate::error;
+use crate::error::Error;
+use crate::error_no_pos;
+
+pub type PluginFn = unsafe extern "C" fn(args: *const *const c_char, argc: usize) -> *mut c_char;
+pub type FreeFn = unsafe extern "C" fn(string: *mut c_char);
+pub type RegisterFn = unsafe extern "C" fn(registry: *mut c_void, name: *const c_char, arity: usize, func: PluginFn);
+type EntryFn = unsafe extern "C" fn(registry: *mut c_void, register: RegisterFn);
+
+const RTLD_NOW: c_int = 2;
+
+#[cfg_attr(target_os = "linux", link(name = "dl"))]
+extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlerror() -> *mut c_char;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NativeMacro {
+    pub arity: usize,
+    func: PluginFn,
+    free: FreeFn
+}
+impl NativeMacro {
+    pub fn call(&self, args: Vec<String>) -> Result<String, Error> {
+        let args: Vec<CString> = args.into_iter()
+            .map(|arg| CString::new(arg.replace('\0', "")).unwrap())
+            .collect();
+        let ptrs: Vec<*const c_char> = args.iter().map(|arg| arg.as_ptr()).collect();
+        // SAFETY: the plugin promises `func` reads `argc` valid C strings and returns a C string or null
+        let result = unsafe { (self.func)(ptrs.as_ptr(), ptrs.len()) };
+        if result.is_null() {
+            return error_no_pos!("native macro failed")
+        }
+        // SAFETY: non-null results are NUL terminated strings owned by the plugin until freed
+        let string = unsafe { CStr::from_ptr(result) }.to_string_lossy().to_string();
+        unsafe { (self.free)(result) };
+        Ok(string)
+    }
+}
+
+unsafe extern "C" fn register(registry: *mut c_void, name: *const c_char, arity: usize, func: PluginFn) {
+    let registry = &mut *(registry as *mut Vec<(String, usize, PluginFn)>);
+    if name.is_null() { return }
+    registry.push((CStr::from_ptr(name).to_string_lossy().to_string(), arity, func));
+}
+
+fn last_error() -> String {
+    // SAFETY: dlerror returns null or a NUL terminated message
+    let error = unsafe { dlerror() };
+    if error.is_null() { String::from("unknown error") } else { unsafe { CStr::from_ptr(error) }.to_string_lossy().to_string() }
+}
+
+/// opens the library and collects the macros it registers, the library stays loaded for the rest of the process
+pub fn load(path: &str) -> Result<Vec<(String, NativeMacro)>, Error> {
+    let Ok(c_path) = CString::new(path) else { return error_no_pos!("invalid plugin path {path:?}") };
+    // SAFETY: loading a library runs its initializers, which is the point of a plugin
+    let handle = unsafe { dlopen(c_path.as_ptr(), RTLD_NOW) };
+    if handle.is_null() {
+        return error_no_pos!("couldn't load plugin {path:?}: {}", last_error())
+    }
+    let symbol = |name: &CStr| unsafe { dlsym(handle, name.as_ptr()) };
+    let (entry, free) = (symbol(c"str_plugin_register"), symbol(c"str_plugin_free"));
+    if entry.is_null() || free.is_null() {
+        return error_no_pos!("plugin {path:?} doesn't export str_plugin_register and str_plugin_free")
+    }
+    // SAFETY: the exported symbols are documented to have these signatures
+    let (entry, free) = unsafe { (std::mem::transmute::<*mut c_void, EntryFn>(entry), std::mem::transmute::<*mut c_void, FreeFn>(free)) };
+    let mut registry: Vec<(String, usize, PluginFn)> = vec![];
+    unsafe { entry(&mut registry as *mut _ as *mut c_void, register) };
+    Ok(registry.into_iter().map(|(name, arity, func)| (name, NativeMacro { arity, func, free })).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// joins the args with `+`, or fails for no args
+    unsafe extern "C" fn join(args: *const *const c_char, argc: usize) -> *mut c_char {
+        if argc == 0 { return std::ptr::null_mut() }
+        let args: Vec<String> = (0..argc).map(|idx| CStr::from_ptr(*args.add(idx)).to_string_lossy().to_string()).collect();
+        CString::new(args.join("+")).unwrap().into_raw()
+    }
+    unsafe extern "C" fn free(string: *mut c_char) {
+        drop(CString::from_raw(string));
+    }
+    unsafe extern "C" fn entry(registry: *mut c_void, reg: RegisterFn) {
+        reg(registry, c"join".as_ptr(), 2, join);
+        reg(registry, std::ptr::null(), 1, join);
+    }
+
+    #[test]
+    fn call_passes_args_and_frees_the_result() {
+        let native = NativeMacro { arity: 2, func: join, free };
+        assert_eq!(native.call(vec!["a".to_string(), "b\0c".to_string()]).unwrap(), "a+bc");
+        assert_eq!(native.call(vec![]).unwrap_err().msg(), "native macro failed");
+    }
+    #[test]
+    fn register_skips_unnamed_macros() {
+        let mut registry: Vec<(String, usize, PluginFn)> = vec![];
+        unsafe { entry(&mut registry as *mut _ as *mut c_void, register) };
+        assert_eq!(registry.iter().map(|(name, arity, _)| (name.as_str(), *arity)).collect::<Vec<_>>(), vec![("join", 2)]);
+    }
+    #[test]
+    fn load_errors() {
+        assert!(load("/nonexistent/plugin.so").unwrap_err().msg().starts_with("couldn't load plugin"));
+        assert!(load("a\0b").unwrap_err().msg().starts_with("invalid plugin path"));
+    }
+}
@@ -0,0 +1,114 @@
+//! per-file options written in comment lines at the top of a script
+//!
+//! ```text
+//! #!str: copy-vars strict-index
+//! #!str: limit=100000
+//! ```
+//!
+//! they apply while the file runs, an included or preloaded file doesn't change the options of the
+//! file that loads it
+use crate::error;
+use crate::error::Error;
+use crate::error_pos;
+use crate::lexer::Position;
+use crate::run::Program;
+
+/// the program options pragmas can change
+struct Options {
+    float_nan: bool,
+    strict_index: bool,
+    strict_vars: bool,
+    copy_vars: bool,
+    step_limit: Option<u64>
+}
+impl Options {
+    fn of(program: &Program) -> Self {
+        Self {
+            float_nan: program.float_nan, strict_index: program.strict_index, strict_vars: program.strict_vars,
+            copy_vars: program.copy_vars, step_limit: program.step_limit
+        }
+    }
+    fn restore(self, program: &mut Program) {
+        program.float_nan = self.float_nan;
+        program.strict_index = self.strict_index;
+        program.strict_vars = self.strict_vars;
+        program.copy_vars = self.copy_vars;
+        program.step_limit = self.step_limit;
+    }
+}
+
+/// applies the pragmas in the comment lines before the first line of code
+pub fn apply(program: &mut Program, text: &str) -> Result<(), Error> {
+    for (ln, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() { continue }
+        let Some(comment) = line.strip_prefix('#') else { break };
+        let Some(options) = comment.strip_prefix("!str:") else { continue };
+        let pos = Position::new(0..1, ln..ln + 1, 0..1);
+        for option in options.split_whitespace() {
+            match option.split_once('=') {
+                None if option == "float-nan" => program.float_nan = true,
+                None if option == "strict-index" => program.strict_index = true,
+                None if option == "strict-vars" => program.strict_vars = true,
+                None if option == "copy-vars" => program.copy_vars = true,
+                Some(("limit", "off" | "0")) => program.step_limit = None,
+                Some(("limit", limit)) => match limit.replace('_', "").parse() {
+                    Ok(limit) => program.step_limit = Some(limit),
+                    Err(_) => return error_pos!(pos, "invalid step limit {limit:?}")
+                }
+                Some(("precision", _)) => return error_pos!(pos, "there is no precision pragma, floats are always printed in full"),
+                _ => return error_pos!(pos, "unknown pragma {option:?}")
+            }
+        }
+    }
+    Ok(())
+}
+
+/// runs `f` with the pragmas of `text` applied, the options are restored afterwards
+pub fn scoped<T>(program: &mut Program, text: &str, f: impl FnOnce(&mut Program) -> Result<T, Error>) -> Result<T, Error> {
+    let options = Options::of(program);
+    let result = apply(program, text).and_then(|_| f(program));
+    options.restore(program);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_from_leading_comments() {
+        let mut program = Program::std_program();
+        apply(&mut program, "# a script\n\n#!str: copy-vars strict-index\n#!str: limit=100_000\n1 (x)").unwrap();
+        assert!(program.copy_vars && program.strict_index && !program.strict_vars && !program.float_nan);
+        assert_eq!(program.step_limit, Some(100000));
+        apply(&mut program, "#!str: limit=off").unwrap();
+        assert_eq!(program.step_limit, None);
+    }
+
+    #[test]
+    fn only_before_the_first_code() {
+        let mut program = Program::std_program();
+        apply(&mut program, "1\n#!str: copy-vars").unwrap();
+        assert!(!program.copy_vars);
+    }
+
+    #[test]
+    fn invalid_pragmas() {
+        let mut program = Program::std_program();
+        let error = apply(&mut program, "#!str: float-nan\n#!str: nope").err().unwrap();
+        assert_eq!(error.msg(), "unknown pragma \"nope\"");
+        assert_eq!(error.pos().unwrap().ln.start, 1);
+        assert_eq!(apply(&mut program, "#!str: limit=lots").err().unwrap().msg(), "invalid step limit \"lots\"");
+        assert!(apply(&mut program, "#!str: precision=3").err().unwrap().msg().starts_with("there is no precision pragma"));
+    }
+
+    #[test]
+    fn scoped_restores_the_options() {
+        let mut program = Program::std_program();
+        let copy_vars = scoped(&mut program, "#!str: copy-vars", |program| Ok(program.copy_vars)).unwrap();
+        assert!(copy_vars && !program.copy_vars);
+        assert!(scoped::<()>(&mut program, "#!str: strict-vars", |_| error_pos!(Position::new(0..1, 0..1, 0..1), "failed")).is_err());
+        assert!(!program.strict_vars);
+    }
+}
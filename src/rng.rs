@@ -0,0 +1,38 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// xorshift64* generator, small and deterministic for a given seed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rng {
+    state: u64
+}
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self { state: 0 };
+        rng.seed(seed);
+        rng
+    }
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or(0);
+        Self::new(nanos)
+    }
+    pub fn seed(&mut self, seed: u64) {
+        // splitmix64 step so that small seeds still give a well mixed, non-zero state
+        let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        self.state = (z ^ (z >> 31)).max(1);
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+    /// uniform integer in `min..=max`
+    pub fn range(&mut self, min: i64, max: i64) -> i64 {
+        let (min, max) = if min <= max { (min, max) } else { (max, min) };
+        let span = max.wrapping_sub(min) as u64;
+        if span == u64::MAX { return self.next_u64() as i64 }
+        min.wrapping_add((self.next_u64() % (span + 1)) as i64)
+    }
+}
@@ -1,26 +1,102 @@
-use std::{fmt::{Display, Debug}, collections::HashMap, hash::Hash};
+use std::{fmt::{Display, Debug}, collections::HashMap, hash::Hash, path::{Path, PathBuf}, fs::{self, File}, io::{BufRead, BufReader}};
 
-use crate::{lexer::{Instr, Position, Token}, error::{Error}, parser::{Node, NodeType}};
+use crate::{lexer::{self, Instr, Position, Token}, error::{Error}, parser::{self, Case, Node, NodeType}};
 use crate::error;
 use crate::error_pos;
-use crate::value::{Type, Value};
+use crate::value::{self, Type, Value};
+use crate::rng::Rng;
+use crate::pragma;
+use crate::effect::{self, Effect};
+use crate::builtins::{self, Feature};
+#[cfg(feature = "plugins")]
+use crate::plugin;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Stack {
-    stack: Vec<Value>
+    stack: Vec<Value>,
+    /// where each value was pushed, only kept while origins are traced
+    origins: Option<Vec<Option<Position>>>,
+    /// the position of the node that is running, recorded as the origin of pushed values
+    origin: Option<Position>
 }
 impl Stack {
-    pub fn new() -> Self { Self { stack: vec![] } }
+    pub fn new() -> Self { Self { stack: vec![], origins: None, origin: None } }
     pub fn push(&mut self, value: Value) {
         self.stack.push(value);
+        if let Some(origins) = &mut self.origins {
+            origins.push(self.origin.clone());
+        }
     }
     pub fn pop(&mut self) -> Option<Value> {
+        if let Some(origins) = &mut self.origins {
+            origins.pop();
+        }
         self.stack.pop()
     }
+    /// pops the top value together with the position it was pushed at, if that is known
+    pub fn pop_with_origin(&mut self) -> Option<(Value, Option<Position>)> {
+        let origin = self.origins.as_mut().and_then(|origins| origins.pop()).flatten();
+        Some((self.stack.pop()?, origin))
+    }
+    /// starts recording where values are pushed, the values already on the stack have no origin
+    pub fn trace_origins(&mut self) {
+        self.origins = Some(vec![None; self.stack.len()]);
+    }
+    pub fn tracing(&self) -> bool { self.origins.is_some() }
+    /// every value with the position it was pushed at, `None` if it isn't known
+    pub fn with_origins(&self) -> impl DoubleEndedIterator<Item = (&Value, Option<&Position>)> + ExactSizeIterator {
+        self.stack.iter().enumerate().map(|(idx, value)| {
+            (value, self.origins.as_ref().and_then(|origins| origins.get(idx)?.as_ref()))
+        })
+    }
     pub fn peek(&self) -> Option<&Value> {
         self.stack.last()
     }
+    pub fn iter(&self) -> std::slice::Iter<'_, Value> {
+        self.stack.iter()
+    }
+    /// the value at `idx` counted from the bottom of the stack
+    pub fn get(&self, idx: usize) -> Option<&Value> {
+        self.stack.get(idx)
+    }
+    pub fn as_slice(&self) -> &[Value] {
+        &self.stack
+    }
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+    /// keeps the bottom `len` values
+    pub fn truncate(&mut self, len: usize) {
+        self.stack.truncate(len);
+        if let Some(origins) = &mut self.origins {
+            origins.truncate(len);
+        }
+    }
     pub fn len(&self) -> usize { self.stack.len() }
+    /// every value followed by its type, like `"abc":str 3:int`
+    pub fn display_typed(&self) -> String {
+        self.stack.iter().map(|v| format!("{v:?}:{}", v.typ())).collect::<Vec<String>>().join(" ")
+    }
+    /// the top `n` values with their types, long values are truncated
+    pub fn display_top(&self, n: usize) -> String {
+        if self.stack.is_empty() { return String::from("<empty>") }
+        let mut values = vec![];
+        if self.len() > n { values.push(String::from("...")) }
+        for (value, origin) in self.with_origins().skip(self.len().saturating_sub(n)) {
+            let mut string = format!("{value:?}");
+            if string.chars().count() > 24 {
+                string = string.chars().take(21).collect::<String>() + "...";
+            }
+            values.push(format!("{string}:{}{}", value.typ(), origin.map_or(String::new(), |pos| format!(" (from {pos})"))));
+        }
+        values.join(" ")
+    }
+}
+impl From<Vec<Value>> for Stack {
+    /// a stack with the last value on top
+    fn from(stack: Vec<Value>) -> Self {
+        Self { stack, ..Self::new() }
+    }
 }
 impl Display for Stack {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -28,65 +104,326 @@ impl Display for Stack {
     }
 }
 
+/// `, pushed at <ln>:<col>` for values whose origin is traced, for error messages
+fn pushed_at(origin: Option<Position>) -> String {
+    origin.map_or(String::new(), |pos| format!(", pushed at {pos}"))
+}
+
+/// renames the macros called or defined in `node` that are keys of `names`
+fn rename(node: &mut Node, names: &HashMap<String, String>) {
+    let rename_id = |id: &mut String| if let Some(name) = names.get(id) {
+        *id = name.clone();
+    };
+    match &mut node.node {
+        NodeType::Chunk(nodes) => {
+            for node in nodes {
+                rename(node, names);
+            }
+        }
+        NodeType::ID(id) | NodeType::Ref(id) => rename_id(id),
+        NodeType::Macro(id, _, _, guard, _, _, body) => {
+            rename_id(id);
+            if let Some(guard) = guard {
+                rename(guard, names);
+            }
+            rename(body, names);
+        }
+        NodeType::Alias(alias, target) => {
+            rename_id(alias);
+            rename_id(target);
+        }
+        NodeType::If(case_node, else_node) => {
+            rename(case_node, names);
+            if let Some(else_node) = else_node {
+                rename(else_node, names);
+            }
+        }
+        NodeType::Match(cases, else_node) => {
+            for (_, body) in cases {
+                rename(body, names);
+            }
+            if let Some(else_node) = else_node {
+                rename(else_node, names);
+            }
+        }
+        NodeType::While(cond, body) => {
+            rename(cond, names);
+            rename(body, names);
+        }
+        NodeType::Repeat(body) | NodeType::ForEach(body) | NodeType::WithLines(body) | NodeType::WithRecords(body) | NodeType::Let(body) |
+        NodeType::Block(body) => rename(body, names),
+        _ => {}
+    }
+}
+
+pub type Operation = fn(&mut Program) -> Result<(), Error>;
+
+#[derive(Clone)]
 pub enum MacroType {
-    Macro(Node), Operation(fn(&mut Program) -> Result<(), Error>)
+    Macro(Node), Operation(Operation),
+    #[cfg(feature = "plugins")]
+    Native(plugin::NativeMacro)
 }
 
+/// an overload that only runs if its guard block leaves true for the arguments
+#[derive(Clone)]
+pub struct Guarded {
+    pub types: Vec<Type>,
+    pub outputs: Option<Vec<Type>>,
+    pub guard: Node,
+    pub body: Node
+}
+
+#[derive(Clone)]
 pub struct MacroOverload {
-    macros: HashMap<Vec<Type>, MacroType>
+    macros: HashMap<Vec<Type>, MacroType>,
+    /// tried in definition order before the overloads without a guard
+    guarded: Vec<Guarded>,
+    /// the types the unguarded overloads declare to leave, like `[str -- int bool]`
+    outputs: HashMap<Vec<Type>, Vec<Type>>,
+    pub doc: Option<String>,
+    /// the stack effect annotation all user overloads share, if they do
+    pub effect: Option<Effect>
+}
+/// whether the top of the stack has the `types`
+fn matches_stack(types: &[Type], stack: &Stack) -> bool {
+    stack.len() >= types.len() && types.iter().rev().enumerate().all(|(idx, typ)| &stack.stack[stack.len() - 1 - idx].typ() == typ)
 }
 impl MacroOverload {
-    pub fn new() -> Self { Self { macros: HashMap::new() } }
+    pub fn new() -> Self { Self { macros: HashMap::new(), guarded: vec![], outputs: HashMap::new(), doc: None, effect: None } }
     pub fn from(args: Vec<Type>, macro_type: MacroType) -> Self {
         let mut macros = HashMap::new();
         macros.insert(args, macro_type);
-        Self { macros }
+        Self { macros, guarded: vec![], outputs: HashMap::new(), doc: None, effect: None }
     }
     pub fn get(&self, stack: &Stack) -> Option<&MacroType> {
-        'macros: for (types, macro_type) in self.macros.iter() {
-            if stack.len() >= types.len() {
-                for (idx, typ) in types.iter().rev().enumerate() {
-                    if &stack.stack[stack.len() - 1 - idx].typ() != typ {
-                        continue 'macros;
-                    }
-                }
-                return Some(macro_type)
-            }
-        }
-        None
+        self.macros.iter().find(|(types, _)| matches_stack(types, stack)).map(|(_, macro_type)| macro_type)
+    }
+    /// the guarded overloads whose types fit the stack, in the order they have to be tried
+    pub fn guarded(&self, stack: &Stack) -> Vec<Guarded> {
+        self.guarded.iter().filter(|guarded| matches_stack(&guarded.types, stack)).cloned().collect()
+    }
+    /// the overloads defined with `macro`, as opposed to builtins, with their declared outputs and guards
+    pub fn user_macros(&self) -> impl Iterator<Item = (&Vec<Type>, Option<&Vec<Type>>, Option<&Node>, &Node)> {
+        self.guarded.iter().map(|guarded| (&guarded.types, guarded.outputs.as_ref(), Some(&guarded.guard), &guarded.body))
+            .chain(self.macros.iter().filter_map(|(types, macro_type)| match macro_type {
+                MacroType::Macro(node) => Some((types, self.outputs.get(types), None, node)),
+                _ => None
+            }))
     }
-    pub fn def(&mut self, args: Vec<Type>, macro_type: MacroType) -> Option<MacroType> {
-        self.macros.insert(args, macro_type)
+    fn arities(&self) -> impl Iterator<Item = usize> + '_ {
+        self.macros.keys().chain(self.guarded.iter().map(|guarded| &guarded.types)).map(|types| types.len())
     }
-    pub fn display(&self, id: &String) -> String {
-        let mut string = String::new();
-        for (types, macro_type) in self.macros.iter() {
-            string.push('[');
-            string.push_str(types.iter().map(|typ| typ.to_string()).collect::<Vec<String>>().join(" ").as_str());
-            string.push_str("] ");
-            string.push_str(id.as_str());
-            string.push('\n');
+    /// the fewest values any of the overloads takes from the stack
+    pub fn min_arity(&self) -> usize {
+        self.arities().min().unwrap_or(0)
+    }
+    /// the most values any of the overloads takes from the stack
+    pub fn max_arity(&self) -> usize {
+        self.arities().max().unwrap_or(0)
+    }
+    /// how many values the overloads take and leave, if all of them declare their outputs and agree
+    pub fn declared_arity(&self) -> Option<(usize, usize)> {
+        let mut arities = self.macros.keys().map(|types| self.outputs.get(types).map(|outputs| (types.len(), outputs.len())))
+            .chain(self.guarded.iter().map(|guarded| guarded.outputs.as_ref().map(|outputs| (guarded.types.len(), outputs.len()))));
+        let first = arities.next()??;
+        arities.all(|arity| arity == Some(first)).then_some(first)
+    }
+    /// whether an overload overlapping `args` is a builtin or native macro, `any` overlaps every type
+    pub fn is_builtin(&self, args: &Vec<Type>) -> bool {
+        self.macros.iter().any(|(types, macro_type)| types == args && !matches!(macro_type, MacroType::Macro(_)))
+    }
+    /// adds an overload, replacing every one whose types overlap `args` (`any` overlaps every type)
+    pub fn def(&mut self, args: Vec<Type>, macro_type: MacroType) -> Vec<MacroType> {
+        let overlapping: Vec<Vec<Type>> = self.macros.keys().filter(|types| **types == args).cloned().collect();
+        let old = overlapping.iter().filter_map(|types| self.macros.remove(types)).collect();
+        self.outputs.retain(|types, _| *types != args);
+        self.macros.insert(args, macro_type);
+        old
+    }
+    /// declares what the unguarded overload taking `args` leaves on the stack
+    pub fn declare(&mut self, args: Vec<Type>, outputs: Vec<Type>) {
+        self.outputs.insert(args, outputs);
+    }
+    /// adds a guarded overload, replacing the one with the same types and the same guard
+    pub fn def_guarded(&mut self, guarded: Guarded) {
+        let same = |other: &Guarded| other.types == guarded.types && other.guard.to_string() == guarded.guard.to_string();
+        match self.guarded.iter_mut().find(|other| same(other)) {
+            Some(other) => *other = guarded,
+            None => self.guarded.push(guarded)
         }
-        string
     }
+    pub fn display(&self, id: &str) -> String {
+        let mut lines: Vec<String> = self.macros.keys()
+            .map(|args| format!("{} {id}\n", value::signature(args, self.outputs.get(args).map(|outputs| outputs.as_slice()))))
+            .collect();
+        lines.sort();
+        let guarded = self.guarded.iter()
+            .map(|guarded| format!("{} {id} when [{}]\n", value::signature(&guarded.types, guarded.outputs.as_deref()), guarded.guard));
+        guarded.chain(lines).collect()
+    }
+}
+
+/// a state saved by `checkpoint`, which `rollback` restores
+#[derive(Clone)]
+pub struct Checkpoint {
+    pub stack: Stack,
+    /// only saved by `checkpoint-vars`
+    pub vars: Option<HashMap<String, Value>>
 }
 
+#[derive(Clone)]
 pub struct Program {
     pub vars: HashMap<String, Value>,
     pub macros: HashMap<String, MacroOverload>,
-    pub stack: Stack
+    /// other names for macros, mapped to the name the overloads are stored under
+    pub aliases: HashMap<String, String>,
+    pub(crate) stack: Stack,
+    pub search_path: Vec<PathBuf>,
+    /// float division and modulo by zero give inf/NaN instead of an error
+    pub float_nan: bool,
+    /// `.` and `remove` error on out-of-range indices instead of wrapping them around
+    pub strict_index: bool,
+    /// builtins like `expand-env` may read environment variables
+    pub allow_env: bool,
+    /// using a variable after it was moved onto the stack names the place it was moved at
+    pub strict_vars: bool,
+    /// using a variable by its name copies it like `@name` instead of moving it off the variables
+    pub copy_vars: bool,
+    /// where each variable was moved onto the stack, only kept with `strict_vars`
+    moved: HashMap<String, Position>,
+    /// the builtin groups this program was created with
+    pub features: Vec<Feature>,
+    /// the most nodes a run may execute before it's stopped, `None` for no limit
+    pub step_limit: Option<u64>,
+    /// nodes executed since this was last set to 0, checked against `step_limit`
+    pub steps: u64,
+    /// the files being included right now, to catch files that include themselves
+    includes: Vec<PathBuf>,
+    /// for each `let` block and macro call being run, the outer values of the variables set in it,
+    /// restored when it ends, `None` for variables that didn't exist outside
+    scopes: Vec<HashMap<String, Option<Value>>>,
+    /// the states saved by `checkpoint`, the latest last
+    pub(crate) checkpoints: Vec<Checkpoint>,
+    pub(crate) rng: Rng
 }
 impl Program {
-    pub fn new() -> Self { Self { vars: HashMap::new(), macros: HashMap::new(), stack: Stack::new() } }
-    pub fn display_macro(&self, id: &String) -> String {
-        if let Some(macro_overload) = self.macros.get(id) {
+    pub fn new() -> Self {
+        Self {
+            vars: HashMap::new(), macros: HashMap::new(), aliases: HashMap::new(), stack: Stack::new(),
+            search_path: vec![], float_nan: false, strict_index: false, allow_env: false, strict_vars: false, copy_vars: false, moved: HashMap::new(),
+            features: vec![], step_limit: None, steps: 0, includes: vec![], scopes: vec![], checkpoints: vec![], rng: Rng::from_time()
+        }
+    }
+    pub fn stack(&self) -> &Stack {
+        &self.stack
+    }
+    pub fn stack_mut(&mut self) -> &mut Stack {
+        &mut self.stack
+    }
+    /// pushes an input for the next run onto the stack
+    pub fn push_value(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+    /// sets a variable the next run can read with its name or `@name`
+    pub fn set_var(&mut self, name: &str, value: Value) {
+        self.assign(name.to_string(), value);
+    }
+    fn assign(&mut self, id: String, value: Value) {
+        self.moved.remove(&id);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.entry(id.clone()).or_insert_with(|| self.vars.get(&id).cloned());
+        }
+        self.vars.insert(id, value);
+    }
+    /// runs `f` in a new scope, the variables set in it are reset to their outer values afterwards
+    fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<T, Error> {
+        self.scopes.push(HashMap::new());
+        let result = f(self);
+        for (id, value) in self.scopes.pop().unwrap_or_default() {
+            self.moved.remove(&id);
+            match value {
+                Some(value) => self.vars.insert(id, value),
+                None => self.vars.remove(&id)
+            };
+        }
+        result
+    }
+    /// the error for an id that is neither a macro nor a variable, naming where it was moved in strict mode
+    fn unknown_id(&self, id: &str, pos: &Position) -> Result<(), Error> {
+        match self.moved.get(id) {
+            Some(moved) => error_pos!(pos, "variable {id:?} was already moved onto the stack at {moved}, use @{id} there to copy it instead"),
+            None => error_pos!(pos, "unknown id {id:?}")
+        }
+    }
+    /// empties the stack and returns what was on it, the top value last
+    pub fn take_results(&mut self) -> Vec<Value> {
+        let results = self.stack.as_slice().to_vec();
+        self.stack.clear();
+        results
+    }
+    /// clears the stack, the variables and every user defined macro, keeping the builtins and settings
+    pub fn reset(&mut self) {
+        let tracing = self.stack.tracing();
+        self.stack = Stack::new();
+        if tracing {
+            self.stack.trace_origins();
+        }
+        self.vars.clear();
+        self.moved.clear();
+        self.scopes.clear();
+        self.checkpoints.clear();
+        self.macros.clear();
+        self.aliases.clear();
+        for builtin in builtins::all(&self.features) {
+            builtin.register(&mut self.macros);
+        }
+    }
+    /// an independent copy to run code on without changing this program, with its own step count
+    ///
+    /// the random generator is copied as well, so the fork draws the numbers this program would draw next
+    pub fn fork(&self) -> Self {
+        Self { steps: 0, ..self.clone() }
+    }
+    /// finds a script by trying `path` as given, then relative to each directory in the search path
+    pub fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let path = Path::new(path);
+        if path.is_file() || path.is_absolute() {
+            return path.is_file().then(|| path.to_path_buf())
+        }
+        self.search_path.iter().map(|dir| dir.join(path)).find(|path| path.is_file())
+    }
+    /// reseeds the generator behind `rand-int`, `shuffle` and `uuid` for reproducible runs
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng.seed(seed);
+    }
+    /// the name the overloads of `id` are stored under, following aliases
+    pub fn macro_name<'a>(&'a self, id: &'a str) -> &'a str {
+        self.aliases.get(id).map(|name| name.as_str()).unwrap_or(id)
+    }
+    pub fn get_macro(&self, id: &str) -> Option<&MacroOverload> {
+        self.macros.get(self.macro_name(id))
+    }
+    pub fn display_macro(&self, id: &str) -> String {
+        if let Some(macro_overload) = self.get_macro(id) {
             macro_overload.display(id)
         } else {
             String::from("no definition found")
         }
     }
+    pub fn doc(&self, id: &str) -> Option<&str> {
+        self.get_macro(id)?.doc.as_deref()
+    }
     pub fn run(&mut self, node: Node) -> Result<(), Error> {
+        if !self.stack.tracing() { return self.eval(node) }
+        let outer = self.stack.origin.replace(node.pos.clone());
+        let result = self.eval(node);
+        self.stack.origin = outer;
+        result
+    }
+    fn eval(&mut self, node: Node) -> Result<(), Error> {
         let mut idx = 0;
+        self.step(&node.pos)?;
         match node.node {
             NodeType::Chunk(nodes) => {
                 for node in nodes {
@@ -98,19 +435,35 @@ impl Program {
             NodeType::Int(int) => self.stack.push(Value::Int(int)),
             NodeType::Float(float) => self.stack.push(Value::Float(float)),
             NodeType::Boolean(boolean) => self.stack.push(Value::Boolean(boolean)),
+            NodeType::Block(body) => self.stack.push(Value::Block(body)),
             NodeType::Take(ids) => {
                 for id in ids {
                     if let Some(value) = self.stack.pop() {
-                        self.vars.insert(id, value);
+                        if id != "_" {
+                            self.assign(id, value);
+                        }
                     } else {
                         return error_pos!(&node.pos, "cannot take value to {id:?} due to stack underflow")
                     }
                 }
             }
+            NodeType::Reorder(ids, pushed) => {
+                if self.stack.len() < ids.len() {
+                    return error_pos!(&node.pos, "cannot reorder {} values due to stack underflow", ids.len())
+                }
+                let values: HashMap<String, Value> = ids.into_iter().map(|id| (id, self.stack.pop().unwrap())).collect();
+                for id in pushed {
+                    self.stack.push(values[&id].clone());
+                }
+            }
+            NodeType::Ref(id) => match self.get_macro(&id) {
+                Some(_) => self.stack.push(Value::Block(Box::new(Node::new(NodeType::ID(id), node.pos)))),
+                None => return error_pos!(&node.pos, "cannot reference {id:?}, it isn't defined as a macro")
+            }
             NodeType::CopyTo(ids) => {
                 for id in ids {
-                    if let Some(value) = self.stack.peek() {
-                        self.vars.insert(id, value.clone());
+                    if let Some(value) = self.stack.peek().cloned() {
+                        self.assign(id, value);
                     } else {
                         return error_pos!(&node.pos, "cannot take value to {id:?} due to stack underflow")
                     }
@@ -119,556 +472,950 @@ impl Program {
             NodeType::Copy(token) => match &token.instr {
                 Instr::ID(id) => match self.vars.get(id) {
                     Some(value) => self.stack.push(value.clone()),
-                    None => match self.macros.get(id) {
+                    None => match self.get_macro(id) {
                         Some(_) => return error_pos!(&token.pos, "cannot copy a macro, {id:?} is defined as a macro"),
-                        None => return error_pos!(&token.pos, "unknown id {id:?}")
+                        None => return self.unknown_id(id, &token.pos)
                     }
                 }
                 Instr::CopyTo(ids) => {
                     for id in ids.iter().rev() {
                         match self.vars.get(id) {
                             Some(value) => self.stack.push(value.clone()),
-                            None => match self.macros.get(id) {
+                            None => match self.get_macro(id) {
                                 Some(_) => return error_pos!(&token.pos, "cannot copy a macro, {id:?} is defined as a macro"),
-                                None => return error_pos!(&token.pos, "unknown id {id:?}")
+                                None => return self.unknown_id(id, &token.pos)
                             }
                         }
                     }
                 }
                 _ => return error_pos!(&token.pos, "expected identifier or copy-to-indentifiers, got {}", token.instr.name())
             }
-            NodeType::ID(id) => match self.macros.get(&id) {
-                Some(macros) => match macros.get(&self.stack) {
-                    Some(macro_type) => match macro_type {
-                        MacroType::Macro(node) => self.run(node.clone())?,
-                        MacroType::Operation(func) => func(self)?,
+            NodeType::ID(id) if self.get_macro(&id).is_some() => match self.select(&id)? {
+                Some(macro_type) => match macro_type {
+                    MacroType::Macro(body) => self.run_macro(&id, body)?,
+                    MacroType::Operation(func) => func(self).map_err(|e| e.or_pos(&node.pos))?,
+                    #[cfg(feature = "plugins")]
+                    MacroType::Native(native) => {
+                        let mut args = vec![];
+                        for _ in 0..native.arity {
+                            args.push(self.stack.pop().unwrap().to_string());
+                        }
+                        args.reverse();
+                        match native.call(args) {
+                            Ok(string) => self.stack.push(Value::String(string)),
+                            Err(_) => return error_pos!(&node.pos, "native macro {id:?} failed")
+                        }
                     }
-                    None => return error_pos!(&node.pos,
-                        "no macro definition {id:?} found with current stack, following macros are defined:\n{}\n", self.display_macro(&id))
                 }
-                None => match self.vars.remove(&id) {
-                    Some(value) => self.stack.push(value),
-                    None => return error_pos!(&node.pos, "unknown id {id:?}")
+                None => {
+                    let macros = self.get_macro(&id).unwrap();
+                    if self.stack.len() < macros.min_arity() {
+                        let (arity, len) = (macros.min_arity(), self.stack.len());
+                        return error_pos!(&node.pos,
+                            "{id:?} needs {arity} value{} but the stack has {len}, following macros are defined:\n{}\n",
+                            if arity == 1 { "" } else { "s" }, self.display_macro(&id))
+                    }
+                    return error_pos!(&node.pos,
+                        "no macro definition {id:?} found with current stack\nstack: {}\nfollowing macros are defined:\n{}\n",
+                        self.stack.display_top(macros.max_arity().max(1)), self.display_macro(&id))
                 }
             }
-            NodeType::If(case_node, else_node) => {
-                let Some(cond) = self.stack.pop() else {
-                    return error_pos!(&node.pos, "couldn't perform if-control-flow operation due to stack underflow");
-                };
-                if let Value::Boolean(cond) = cond {
-                    if cond {
-                        self.run(*case_node);
-                    } else if let Some(else_node) = else_node {
-                        self.run(*else_node);
+            NodeType::ID(id) if self.copy_vars => match self.vars.get(&id) {
+                Some(value) => self.stack.push(value.clone()),
+                None => return self.unknown_id(&id, &node.pos)
+            }
+            NodeType::ID(id) => match self.vars.remove(&id) {
+                Some(value) => {
+                    self.stack.push(value);
+                    if self.strict_vars {
+                        self.moved.insert(id, node.pos);
                     }
-                } else {
-                    return error_pos!(&node.pos, "expected a boolean value on top of the stack, got {}", cond.typ())
                 }
+                None => return self.unknown_id(&id, &node.pos)
+            }
+            NodeType::If(case_node, else_node) => {
+                if let Some(branch) = self.branch(&node.pos, *case_node, else_node)? {
+                    self.run(branch)?;
+                }
+            }
+            NodeType::Match(cases, else_node) => {
+                let arm = self.arm(&node.pos, cases, else_node)?;
+                self.run(arm)?;
             }
             NodeType::Repeat(body) => {
-                let Some(count) = self.stack.pop() else {
-                    return error_pos!(&node.pos, "couldn't perform if-control-flow operation due to stack underflow");
+                let Some((count, origin)) = self.stack.pop_with_origin() else {
+                    return error_pos!(&node.pos, "couldn't perform repeat-control-flow operation due to stack underflow");
                 };
                 if let Value::Int(count) = count {
+                    if self.fused_repeat(count, &body) { return Ok(()) }
                     for _ in 0..count {
-                        self.run(*body.clone());
+                        self.run(*body.clone())?;
                     }
                 } else {
-                    return error_pos!(&node.pos, "expected a boolean value on top of the stack, got {}", count.typ())
+                    return error_pos!(&node.pos, "expected an int value on top of the stack, got {}{}", count.typ(), pushed_at(origin))
                 }
             }
-            NodeType::Macro(name, types, body) => todo!("macro definition"),
+            NodeType::While(cond, body) => loop {
+                self.run(*cond.clone())?;
+                match self.stack.pop_with_origin() {
+                    Some((Value::Boolean(true), _)) => self.run(*body.clone())?,
+                    Some((Value::Boolean(false), _)) => break,
+                    Some((value, origin)) => return error_pos!(&node.pos, "expected a boolean value on top of the stack, got {}{}", value.typ(), pushed_at(origin)),
+                    None => return error_pos!(&node.pos, "couldn't perform while-control-flow operation due to stack underflow")
+                }
+            }
+            NodeType::ForEach(body) => {
+                let string = match self.stack.pop_with_origin() {
+                    Some((Value::String(string), _)) => string,
+                    Some((value, origin)) => return error_pos!(&node.pos, "expected a string on top of the stack, got {}{}", value.typ(), pushed_at(origin)),
+                    None => return error_pos!(&node.pos, "couldn't perform foreach-control-flow operation due to stack underflow")
+                };
+                for char in string.chars() {
+                    self.stack.push(Value::Char(char));
+                    self.run(*body.clone())?;
+                }
+            }
+            NodeType::Let(body) => self.scoped(|program| program.run(*body))?,
+            NodeType::WithLines(body) => {
+                let path = match self.stack.pop_with_origin() {
+                    Some((Value::String(path), _)) => path,
+                    Some((value, origin)) => return error_pos!(&node.pos, "expected a file path on top of the stack, got {}{}", value.typ(), pushed_at(origin)),
+                    None => return error_pos!(&node.pos, "couldn't perform with-lines-control-flow operation due to stack underflow")
+                };
+                let file = match File::open(&path) {
+                    Ok(file) => file,
+                    Err(e) => return error_pos!(&node.pos, "error occurd while opening the file {path:?}: {e}")
+                };
+                for line in BufReader::new(file).lines() {
+                    match line {
+                        Ok(line) => self.stack.push(Value::String(line)),
+                        Err(e) => return error_pos!(&node.pos, "error occurd while reading the file {path:?}: {e}")
+                    }
+                    self.run(*body.clone())?;
+                }
+            }
+            NodeType::WithRecords(body) => {
+                let mut chars = vec![];
+                while let (true, Some(Value::Char(c))) = (chars.len() < 2, self.stack.peek()) {
+                    chars.insert(0, *c);
+                    self.stack.pop();
+                }
+                let (quote, escape) = (*chars.first().unwrap_or(&'"'), chars.get(1).copied());
+                let path = match self.stack.pop_with_origin() {
+                    Some((Value::String(path), _)) => path,
+                    Some((value, origin)) => return error_pos!(&node.pos, "expected a file path below the quote and escape chars, got {}{}", value.typ(), pushed_at(origin)),
+                    None => return error_pos!(&node.pos, "couldn't perform with-records-control-flow operation due to stack underflow")
+                };
+                let file = match File::open(&path) {
+                    Ok(file) => file,
+                    Err(e) => return error_pos!(&node.pos, "error occurd while opening the file {path:?}: {e}")
+                };
+                let mut record: Option<String> = None;
+                for line in BufReader::new(file).lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(e) => return error_pos!(&node.pos, "error occurd while reading the file {path:?}: {e}")
+                    };
+                    let quoted = builtins::csv_quoted(&line, quote, escape, record.is_some());
+                    let line = match record.take() {
+                        Some(record) => record + "\n" + &line,
+                        None => line
+                    };
+                    if quoted {
+                        record = Some(line);
+                    } else {
+                        self.stack.push(Value::String(line));
+                        self.run(*body.clone())?;
+                    }
+                }
+                if record.is_some() {
+                    return error_pos!(&node.pos, "unclosed quote at the end of the file {path:?}")
+                }
+            }
+            NodeType::Macro(name, types, outputs, guard, effect, doc, body) => {
+                let name = self.macro_name(&name).to_string();
+                if self.macros.get(&name).is_some_and(|macro_overload| macro_overload.is_builtin(&types)) {
+                    return error_pos!(&node.pos, "cannot redefine the builtin {name:?} taking [{}]",
+                        types.iter().map(|typ| typ.to_string()).collect::<Vec<String>>().join(" "))
+                }
+                if let Some(effect) = &effect {
+                    effect::check(self, &name, effect, &body)?;
+                }
+                let macro_overload = self.macros.entry(name).or_insert_with(MacroOverload::new);
+                let first = macro_overload.arities().next().is_none();
+                macro_overload.effect = effect.filter(|effect| first || macro_overload.effect.as_ref() == Some(effect));
+                match guard {
+                    Some(guard) => macro_overload.def_guarded(Guarded { types, outputs, guard: *guard, body: *body }),
+                    None => {
+                        macro_overload.def(types.clone(), MacroType::Macro(*body));
+                        if let Some(outputs) = outputs {
+                            macro_overload.declare(types, outputs);
+                        }
+                    }
+                }
+                if doc.is_some() {
+                    macro_overload.doc = doc;
+                }
+            }
+            NodeType::Alias(name, target) => {
+                let target = self.macro_name(&target).to_string();
+                if !self.macros.contains_key(&target) {
+                    return error_pos!(&node.pos, "cannot alias {name:?} to {target:?}, no such macro is defined")
+                }
+                if self.macros.contains_key(&name) {
+                    return error_pos!(&node.pos, "cannot alias {name:?}, it is already defined as a macro")
+                }
+                self.aliases.insert(name, target);
+            }
+            NodeType::Include(path) => {
+                let (file, text) = self.source(&path, &node.pos)?;
+                self.includes.push(file);
+                let result = lexer::lex(text.clone())
+                    .and_then(parser::parse)
+                    .and_then(|node| pragma::scoped(self, &text, |program| program.run(node)));
+                self.includes.pop();
+                result.map_err(|e| e.in_file(&path, &text))?;
+            }
+            NodeType::Import(path, name) => {
+                let (file, text) = self.source(&path, &node.pos)?;
+                let mut module = Self {
+                    search_path: self.search_path.clone(), float_nan: self.float_nan, strict_index: self.strict_index,
+                    allow_env: self.allow_env, strict_vars: self.strict_vars, copy_vars: self.copy_vars,
+                    step_limit: self.step_limit, steps: self.steps, includes: self.includes.clone(),
+                    ..Self::with_features(&self.features)
+                };
+                module.includes.push(file);
+                let result = lexer::lex(text.clone())
+                    .and_then(parser::parse)
+                    .and_then(|node| pragma::scoped(&mut module, &text, |module| module.run(node)));
+                self.steps = module.steps;
+                result.map_err(|e| e.in_file(&path, &text))?;
+                self.export(module, &name);
+            }
         }
         Ok(())
     }
-    pub fn std_program() -> Self {
-        let mut macros = HashMap::new();
-        // LEN
-        let mut stack_len = MacroOverload::new();
-        stack_len.def(vec![], MacroType::Operation(_stack_len));
-        macros.insert(String::from("LEN"), stack_len);
-        // len
-        let mut len = MacroOverload::new();
-        len.def(vec![Type::String], MacroType::Operation(_len));
-        macros.insert(String::from("len"), len);
-        // drop
-        let mut drop = MacroOverload::new();
-        drop.def(vec![Type::Any], MacroType::Operation(_drop));
-        macros.insert(String::from("drop"), drop);
-        // copy
-        let mut copy = MacroOverload::new();
-        copy.def(vec![Type::Any], MacroType::Operation(_copy));
-        macros.insert(String::from("copy"), copy);
-        // swap
-        let mut swap = MacroOverload::new();
-        swap.def(vec![Type::Any, Type::Any], MacroType::Operation(_swap));
-        macros.insert(String::from("swap"), swap);
-        // over
-        let mut over = MacroOverload::new();
-        over.def(vec![Type::Any, Type::Any], MacroType::Operation(_over));
-        macros.insert(String::from("over"), over);
-        // +
-        let mut add = MacroOverload::new();
-        add.def(vec![Type::Int, Type::Int], MacroType::Operation(_add));
-        add.def(vec![Type::Float, Type::Float], MacroType::Operation(_add));
-        add.def(vec![Type::Int, Type::Float], MacroType::Operation(_add));
-        add.def(vec![Type::Float, Type::Int], MacroType::Operation(_add));
-        add.def(vec![Type::String, Type::String], MacroType::Operation(_add));
-        add.def(vec![Type::String, Type::Char], MacroType::Operation(_add));
-        macros.insert(String::from("+"), add);
-        // -
-        let mut sub = MacroOverload::new();
-        sub.def(vec![Type::Int, Type::Int], MacroType::Operation(_sub));
-        sub.def(vec![Type::Float, Type::Float], MacroType::Operation(_sub));
-        sub.def(vec![Type::Int, Type::Float], MacroType::Operation(_sub));
-        sub.def(vec![Type::Float, Type::Int], MacroType::Operation(_sub));
-        macros.insert(String::from("-"), sub);
-        // *
-        let mut mult = MacroOverload::new();
-        mult.def(vec![Type::Int, Type::Int], MacroType::Operation(_mult));
-        mult.def(vec![Type::Float, Type::Float], MacroType::Operation(_mult));
-        mult.def(vec![Type::Int, Type::Float], MacroType::Operation(_mult));
-        mult.def(vec![Type::Float, Type::Int], MacroType::Operation(_mult));
-        mult.def(vec![Type::String, Type::Int], MacroType::Operation(_mult));
-        mult.def(vec![Type::Char, Type::Int], MacroType::Operation(_mult));
-        macros.insert(String::from("*"), mult);
-        // /
-        let mut div = MacroOverload::new();
-        div.def(vec![Type::Int, Type::Int], MacroType::Operation(_div));
-        div.def(vec![Type::Float, Type::Float], MacroType::Operation(_div));
-        div.def(vec![Type::Int, Type::Float], MacroType::Operation(_div));
-        div.def(vec![Type::Float, Type::Int], MacroType::Operation(_div));
-        macros.insert(String::from("/"), div);
-        // %
-        let mut module = MacroOverload::new();
-        module.def(vec![Type::Int, Type::Int], MacroType::Operation(_module));
-        module.def(vec![Type::Float, Type::Float], MacroType::Operation(_module));
-        module.def(vec![Type::Int, Type::Float], MacroType::Operation(_module));
-        module.def(vec![Type::Float, Type::Int], MacroType::Operation(_module));
-        macros.insert(String::from("%"), module);
-        // and
-        let mut and = MacroOverload::new();
-        and.def(vec![Type::Boolean, Type::Boolean], MacroType::Operation(_and));
-        macros.insert(String::from("and"), and);
-        // or
-        let mut or = MacroOverload::new();
-        or.def(vec![Type::Boolean, Type::Boolean], MacroType::Operation(_or));
-        macros.insert(String::from("or"), or);
-        // not
-        let mut not = MacroOverload::new();
-        not.def(vec![Type::Boolean], MacroType::Operation(_not));
-        macros.insert(String::from("not"), not);
-        // =
-        let mut eq = MacroOverload::new();
-        eq.def(vec![Type::Any, Type::Any], MacroType::Operation(_eq));
-        macros.insert(String::from("="), eq);
-        // !=
-        let mut ne = MacroOverload::new();
-        ne.def(vec![Type::Any, Type::Any], MacroType::Operation(_ne));
-        macros.insert(String::from("!="), ne);
-        // >
-        let mut lt = MacroOverload::new();
-        lt.def(vec![Type::Int, Type::Int], MacroType::Operation(_lt));
-        lt.def(vec![Type::Float, Type::Float], MacroType::Operation(_lt));
-        lt.def(vec![Type::Int, Type::Float], MacroType::Operation(_lt));
-        lt.def(vec![Type::Float, Type::Int], MacroType::Operation(_lt));
-        macros.insert(String::from("<"), lt);
-        // <
-        let mut gt = MacroOverload::new();
-        gt.def(vec![Type::Int, Type::Int], MacroType::Operation(_gt));
-        gt.def(vec![Type::Float, Type::Float], MacroType::Operation(_gt));
-        gt.def(vec![Type::Int, Type::Float], MacroType::Operation(_gt));
-        gt.def(vec![Type::Float, Type::Int], MacroType::Operation(_gt));
-        macros.insert(String::from(">"), gt);
-        // <=
-        let mut le = MacroOverload::new();
-        le.def(vec![Type::Int, Type::Int], MacroType::Operation(_le));
-        le.def(vec![Type::Float, Type::Float], MacroType::Operation(_le));
-        le.def(vec![Type::Int, Type::Float], MacroType::Operation(_le));
-        le.def(vec![Type::Float, Type::Int], MacroType::Operation(_le));
-        macros.insert(String::from("<="), le);
-        // >=
-        let mut ge = MacroOverload::new();
-        ge.def(vec![Type::Int, Type::Int], MacroType::Operation(_ge));
-        ge.def(vec![Type::Float, Type::Float], MacroType::Operation(_ge));
-        ge.def(vec![Type::Int, Type::Float], MacroType::Operation(_ge));
-        ge.def(vec![Type::Float, Type::Int], MacroType::Operation(_ge));
-        macros.insert(String::from(">="), ge);
-
-        // .
-        let mut index = MacroOverload::new();
-        index.def(vec![Type::String, Type::Int], MacroType::Operation(_index));
-        index.def(vec![Type::String, Type::Int, Type::Int], MacroType::Operation(_index_range));
-        macros.insert(String::from("."), index);
-        // rev
-        let mut rev = MacroOverload::new();
-        rev.def(vec![Type::String], MacroType::Operation(_rev));
-        macros.insert(String::from("rev"), rev);
-        // pos
-        let mut pos = MacroOverload::new();
-        pos.def(vec![Type::String, Type::String], MacroType::Operation(_pos));
-        pos.def(vec![Type::String, Type::Char], MacroType::Operation(_pos));
-        macros.insert(String::from("pos"), pos);
-        // remove
-        let mut remove = MacroOverload::new();
-        remove.def(vec![Type::String, Type::Int], MacroType::Operation(_remove));
-        macros.insert(String::from("remove"), remove);
-        // count
-        let mut count = MacroOverload::new();
-        count.def(vec![Type::String, Type::Char], MacroType::Operation(_count));
-        count.def(vec![Type::String, Type::String], MacroType::Operation(_count));
-        macros.insert(String::from("count"), count);
-        // split
-        let mut split = MacroOverload::new();
-        split.def(vec![Type::String, Type::Char], MacroType::Operation(_split));
-        split.def(vec![Type::String, Type::String], MacroType::Operation(_split));
-        macros.insert(String::from("split"), split);
-        // join
-        let mut join = MacroOverload::new();
-        join.def(vec![Type::Char], MacroType::Operation(_join));
-        join.def(vec![Type::String], MacroType::Operation(_join));
-        macros.insert(String::from("join"), join);
-
-        Self { vars: HashMap::new(), macros, stack: Stack::new() }
-    }
-}
-
-fn _stack_len(program: &mut Program) -> Result<(), Error> {
-    program.stack.push(Value::Int(program.stack.len() as i64));
-    Ok(())
-}
-fn _len(program: &mut Program) -> Result<(), Error> {
-    let a = program.stack.pop().unwrap();
-    match a {
-        Value::String(string) => program.stack.push(Value::Int(string.len() as i64)),
-        _ => panic!("type checking error!!!")
-    }
-    Ok(())
-}
-fn _drop(program: &mut Program) -> Result<(), Error> {
-    program.stack.pop();
-    Ok(())
-}
-fn _copy(program: &mut Program) -> Result<(), Error> {
-    let a = program.stack.peek().unwrap();
-    program.stack.push(a.clone());
-    Ok(())
-}
-fn _swap(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    program.stack.push(b);
-    program.stack.push(a);
-    Ok(())
-}
-fn _over(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    let c = a.clone();
-    program.stack.push(a);
-    program.stack.push(b);
-    program.stack.push(c);
-    Ok(())
-}
-fn _add(program: &mut Program) -> Result<(), Error> {
-    let (mut b, mut a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a.clone(), b) {
-        (Value::Int(v1), Value::Int(v2)) => program.stack.push(Value::Int(v1 + v2)),
-        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Float(v1 + v2)),
-        (Value::Int(int), Value::Float(float)) |
-        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Float(int as f64 + float)),
-        (Value::String(v1), Value::String(v2)) => program.stack.push(Value::String(v1 + &v2)),
-        (Value::String(mut v1), Value::Char(v2)) => {
-            v1.push(v2);
-            program.stack.push(Value::String(v1));
-        }
-        _ => panic!("type checking error!!!")
-    }
-    Ok(())
-}
-fn _sub(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::Int(v1), Value::Int(v2)) => program.stack.push(Value::Int(v1 - v2)),
-        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Float(v1 - v2)),
-        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Float(int as f64 - float)),
-        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Float(float - int as f64)),
-        _ => panic!("type checking error!!!")
-    }
-    Ok(())
-}
-fn _mult(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::Int(v1), Value::Int(v2)) => program.stack.push(Value::Int(v1 * v2)),
-        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Float(v1 * v2)),
-        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Float(int as f64 * float)),
-        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Float(float * int as f64)),
-        (Value::String(s), Value::Int(rep)) => program.stack.push(Value::String(s.repeat(rep.max(0) as usize))),
-        (Value::Char(c), Value::Int(rep)) => program.stack.push(Value::String(c.to_string().repeat(rep.max(0) as usize))),
-        _ => panic!("type checking error!!!")
-    }
-    Ok(())
-}
-fn _div(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::Int(v1), Value::Int(v2)) => program.stack.push(Value::Float(v1 as f64 / v2 as f64)),
-        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Float(v1 / v2)),
-        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Float(int as f64 / float)),
-        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Float(float / int as f64)),
-        _ => panic!("type checking error!!!")
-    }
-    Ok(())
-}
-fn _module(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::Int(v1), Value::Int(v2)) => program.stack.push(Value::Int(v1 % v2)),
-        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Float(v1 % v2)),
-        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Float(int as f64 % float)),
-        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Float(float % int as f64)),
-        _ => panic!("type checking error!!!")
-    }
-    Ok(())
-}
-fn _pow(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::Int(v1), Value::Int(v2)) => program.stack.push(Value::Int(v1.pow(v2.max(0) as u32))),
-        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Float(v1.powf(v2))),
-        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Float((int as f64).powf(float))),
-        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Float((float as f64).powi(int.max(0) as i32))),
-        _ => panic!("type checking error!!!")
-    }
-    Ok(())
-}
-fn _and(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::Boolean(v1), Value::Boolean(v2)) => program.stack.push(Value::Boolean(v1 && v2)),
-        _ => panic!("type checking error!!!")
-    }
-    Ok(())
-}
-fn _or(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::Boolean(v1), Value::Boolean(v2)) => program.stack.push(Value::Boolean(v1 || v2)),
-        _ => panic!("type checking error!!!")
+    /// finds, reads and checks a file for `include` and `import`
+    fn source(&self, path: &str, pos: &Position) -> Result<(PathBuf, String), Error> {
+        let Some(file) = self.resolve(path) else {
+            return error_pos!(pos, "couldn't find the file {path:?}")
+        };
+        let file = file.canonicalize().unwrap_or(file);
+        if self.includes.contains(&file) {
+            return error_pos!(pos, "{path:?} is already being included, files may not include themselves")
+        }
+        match fs::read_to_string(&file) {
+            Ok(text) => Ok((file, text)),
+            Err(e) => error_pos!(pos, "error occurd while reading the file {path:?}: {e}")
+        }
     }
-    Ok(())
-}
-fn _not(program: &mut Program) -> Result<(), Error> {
-    let a = program.stack.pop().unwrap();
-    match a {
-        Value::Boolean(v) => program.stack.push(Value::Boolean(!v)),
-        _ => panic!("type checking error!!!")
+    /// adds the macros and aliases the imported `module` defined under the prefix `name:`, the calls
+    /// between them are renamed as well so they don't pick up macros of this program
+    fn export(&mut self, module: Self, name: &str) {
+        let builtins: Vec<&str> = builtins::all(&self.features).map(|builtin| builtin.name).collect();
+        let exported: HashMap<String, String> = module.macros.keys().chain(module.aliases.keys())
+            .filter(|id| !builtins.contains(&id.as_str()))
+            .map(|id| (id.clone(), format!("{name}:{id}")))
+            .collect();
+        for (id, macro_overload) in module.macros.iter() {
+            let Some(prefixed) = exported.get(id) else { continue };
+            let mut exported_overload = MacroOverload::new();
+            for (types, outputs, guard, body) in macro_overload.user_macros() {
+                let mut body = body.clone();
+                rename(&mut body, &exported);
+                match guard {
+                    Some(guard) => {
+                        let mut guard = guard.clone();
+                        rename(&mut guard, &exported);
+                        exported_overload.def_guarded(Guarded { types: types.clone(), outputs: outputs.cloned(), guard, body });
+                    }
+                    None => {
+                        exported_overload.def(types.clone(), MacroType::Macro(body));
+                        if let Some(outputs) = outputs {
+                            exported_overload.declare(types.clone(), outputs.clone());
+                        }
+                    }
+                }
+            }
+            exported_overload.doc = macro_overload.doc.clone();
+            exported_overload.effect = macro_overload.effect.clone();
+            self.macros.insert(prefixed.clone(), exported_overload);
+        }
+        for (alias, target) in module.aliases.iter() {
+            if let (Some(alias), Some(target)) = (exported.get(alias), exported.get(target)) {
+                self.aliases.insert(alias.clone(), target.clone());
+            }
+        }
     }
-    Ok(())
-}
-fn _eq(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    program.stack.push(Value::Boolean(a == b));
-    Ok(())
-}
-fn _ne(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    program.stack.push(Value::Boolean(a != b));
-    Ok(())
-}
-fn _lt(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::Int(v1), Value::Int(v2)) => program.stack.push(Value::Boolean(v1 < v2)),
-        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Boolean(v1 < v2)),
-        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Boolean((int as f64) < float)),
-        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Boolean(float < int as f64)),
-        _ => panic!("type checking error!!!")
-    }
-    Ok(())
-}
-fn _gt(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::Int(v1), Value::Int(v2)) => program.stack.push(Value::Boolean(v1 > v2)),
-        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Boolean(v1 > v2)),
-        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Boolean(int as f64 > float)),
-        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Boolean(float > int as f64)),
-        _ => panic!("type checking error!!!")
-    }
-    Ok(())
-}
-fn _le(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::Int(v1), Value::Int(v2)) => program.stack.push(Value::Boolean(v1 <= v2)),
-        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Boolean(v1 <= v2)),
-        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Boolean(int as f64 <= float)),
-        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Boolean(float <= int as f64)),
-        _ => panic!("type checking error!!!")
-    }
-    Ok(())
-}
-fn _ge(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::Int(v1), Value::Int(v2)) => program.stack.push(Value::Boolean(v1 >= v2)),
-        (Value::Float(v1), Value::Float(v2)) => program.stack.push(Value::Boolean(v1 >= v2)),
-        (Value::Int(int), Value::Float(float)) => program.stack.push(Value::Boolean(int as f64 >= float)),
-        (Value::Float(float), Value::Int(int)) => program.stack.push(Value::Boolean(float >= int as f64)),
-        _ => panic!("type checking error!!!")
-    }
-    Ok(())
-}
-fn _index(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    if let (Value::String(string), Value::Int(idx)) = (a, b) {
-        let idx = if idx < 0 {
-            string.len() - idx.abs() as usize % string.len()
-        } else {
-            idx.abs() as usize % string.len()
-        };
-        program.stack.push(Value::Char(string[idx..idx+1].chars().next().unwrap()));
-        Ok(())
-    } else {
-        panic!("type checking error!!!")
+    /// counts a node against the step limit
+    fn step(&mut self, pos: &Position) -> Result<(), Error> {
+        self.steps += 1;
+        match self.step_limit {
+            Some(limit) if self.steps > limit =>
+                error_pos!(pos, "step limit of {limit} reached, the program may be stuck in an endless loop"),
+            _ => Ok(())
+        }
     }
-}
-fn _index_range(program: &mut Program) -> Result<(), Error> {
-    let (c, b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    if let (Value::String(string), Value::Int(start), Value::Int(end)) = (a, b, c) {
-        let start = if start < 0 {
-            string.len() - start.abs() as usize % string.len()
-        } else {
-            start.abs() as usize % string.len()
+    /// runs all iterations of a `repeat` whose body only applies a builtin to a literal, like
+    /// `"ab" +` or `2 *`, as a single operation, returns false if the body has to be interpreted
+    ///
+    /// the steps of the iterations are still counted, a loop that would hit the step limit or
+    /// overflow is interpreted, so it stops with the same error at the same iteration as without
+    /// this shortcut
+    fn fused_repeat(&mut self, count: i64, body: &Node) -> bool {
+        let NodeType::Chunk(nodes) = &body.node else { return false };
+        let [arg, Node { node: NodeType::ID(id), .. }] = nodes.as_slice() else { return false };
+        if count <= 0 || self.stack.tracing() { return false }
+        let steps = 3 * count as u64;
+        if self.step_limit.is_some_and(|limit| self.steps + steps > limit) { return false }
+        let arg = match &arg.node {
+            NodeType::String(string) => Value::String(string.clone()),
+            NodeType::Char(char) => Value::Char(*char),
+            NodeType::Int(int) => Value::Int(*int),
+            _ => return false
         };
-        let end = if end < 0 {
-            string.len() - end.abs() as usize % string.len()
-        } else {
-            end.abs() as usize % string.len()
+        let Some(top) = self.stack.peek() else { return false };
+        let name = self.macro_name(id);
+        if !self.macros.get(name).is_some_and(|macros| macros.is_builtin(&vec![top.typ(), arg.typ()])) { return false }
+        let result = match (name, top, arg) {
+            ("+", Value::String(string), Value::String(arg)) => Some(Value::String(string.clone() + &arg.repeat(count as usize))),
+            ("+", Value::String(string), Value::Char(arg)) => Some(Value::String(string.clone() + &arg.to_string().repeat(count as usize))),
+            ("+", Value::Int(int), Value::Int(arg)) => arg.checked_mul(count).and_then(|sum| int.checked_add(sum)).map(Value::Int),
+            ("*", Value::Int(int), Value::Int(arg)) => u32::try_from(count).ok()
+                .and_then(|count| arg.checked_pow(count)).and_then(|product| int.checked_mul(product)).map(Value::Int),
+            _ => None
         };
-        program.stack.push(Value::String(string[start..end].to_string()));
-        Ok(())
-    } else {
-        panic!("type checking error!!!")
+        let Some(result) = result else { return false };
+        self.stack.pop();
+        self.stack.push(result);
+        self.steps += steps;
+        true
     }
-}
-fn _rev(program: &mut Program) -> Result<(), Error> {
-    if let Value::String(string) = program.stack.pop().unwrap() {
-        program.stack.push(Value::String(string.chars().rev().collect()));
-        Ok(())
-    } else {
-        panic!("type checking error!!!")
-    }
-}
-fn _pos(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::String(string), Value::Char(char)) => {
-            match string.find(char) {
-                Some(index) => {
-                    program.stack.push(Value::Int(index as i64));
-                    program.stack.push(Value::Boolean(true));
-                }
-                None => program.stack.push(Value::Boolean(false))
+    /// the overload of the macro `id` that fits the stack, the guarded ones are tried first
+    fn select(&mut self, id: &str) -> Result<Option<MacroType>, Error> {
+        let Some(macros) = self.get_macro(id) else { return Ok(None) };
+        let guarded = macros.guarded(&self.stack);
+        let macro_type = macros.get(&self.stack).cloned();
+        for guarded in guarded {
+            if self.guard(guarded.types.len(), guarded.guard)? {
+                return Ok(Some(MacroType::Macro(guarded.body)))
             }
         }
-        (Value::String(string), Value::String(sub)) => {
-            match string.find(&sub) {
-                Some(index) => {
-                    program.stack.push(Value::Int(index as i64));
-                    program.stack.push(Value::Boolean(true));
-                }
-                None => program.stack.push(Value::Boolean(false))
-            }
+        Ok(macro_type)
+    }
+    /// runs a guard on copies of the top `arity` values, which stay as they are
+    fn guard(&mut self, arity: usize, guard: Node) -> Result<bool, Error> {
+        let len = self.stack.len();
+        for idx in len - arity..len {
+            let value = self.stack.get(idx).unwrap().clone();
+            self.stack.push(value);
+        }
+        let pos = guard.pos.clone();
+        self.scoped(|program| program.run(guard))?;
+        // a guard that took more than its copies left nothing of its own
+        let result = if self.stack.len() > len { self.stack.pop_with_origin() } else { None };
+        self.stack.truncate(len);
+        match result {
+            Some((Value::Boolean(result), _)) => Ok(result),
+            Some((value, origin)) => error_pos!(pos, "expected the guard to leave a boolean, got {}{}", value.typ(), pushed_at(origin)),
+            None => error_pos!(pos, "the guard left no value on the stack")
         }
-        _ => panic!("type checking error!!!")
     }
-    Ok(())
-}
-fn _remove(program: &mut Program) -> Result<(), Error> {
-    let (b, mut a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::String(mut string), Value::Int(idx)) => {
-            let idx = if idx < 0 {
-                string.len() - idx.abs() as usize % string.len()
-            } else {
-                idx.abs() as usize % string.len()
-            };
-            program.stack.push(Value::Char(string.remove(idx)));
+    /// pops the condition of an `if`, returns the branch that has to run
+    fn branch(&mut self, pos: &Position, case_node: Node, else_node: Option<Box<Node>>) -> Result<Option<Node>, Error> {
+        match self.stack.pop_with_origin() {
+            Some((Value::Boolean(true), _)) => Ok(Some(case_node)),
+            Some((Value::Boolean(false), _)) => Ok(else_node.map(|node| *node)),
+            Some((cond, origin)) => error_pos!(pos, "expected a boolean value on top of the stack, got {}{}", cond.typ(), pushed_at(origin)),
+            None => error_pos!(pos, "couldn't perform if-control-flow operation due to stack underflow")
         }
-        _ => panic!("type checking error!!!")
     }
-    Ok(())
-}
-fn _count(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::String(string), Value::Char(count_char)) => {
-            let mut count: usize = 0;
-            for char in string.chars() {
-                if char == count_char {
-                    count += 1;
+    /// pops the value of a `match` and picks the first case it matches, or the else branch
+    fn arm(&mut self, pos: &Position, cases: Vec<(Case, Node)>, else_node: Option<Box<Node>>) -> Result<Node, Error> {
+        let Some((value, origin)) = self.stack.pop_with_origin() else {
+            return error_pos!(pos, "couldn't perform match-control-flow operation due to stack underflow")
+        };
+        match (cases.into_iter().find(|(case, _)| case.matches(&value)), else_node) {
+            (Some((_, body)), _) => Ok(body),
+            (None, Some(else_node)) => Ok(*else_node),
+            (None, None) => error_pos!(pos, "no case matches {value:?}{}", pushed_at(origin))
+        }
+    }
+    /// runs the body of the macro `id` in its own scope, so the variables it takes don't change
+    /// the caller's
+    ///
+    /// a call of `id` in tail position replaces the body instead of nesting another `run`,
+    /// so recursive macros don't overflow the stack no matter how deep they go
+    fn run_macro(&mut self, id: &str, mut body: Node) -> Result<(), Error> {
+        self.scoped(|program| {
+            while let Some(next) = program.run_tail(id, body)? {
+                body = next;
+            }
+            Ok(())
+        })
+    }
+    /// runs `node` except for a call of the macro `id` in tail position, whose body is returned instead
+    fn run_tail(&mut self, id: &str, node: Node) -> Result<Option<Node>, Error> {
+        match node.node {
+            NodeType::Chunk(mut nodes) => {
+                self.step(&node.pos)?;
+                let Some(last) = nodes.pop() else { return Ok(None) };
+                for node in nodes {
+                    self.run(node)?;
                 }
+                self.run_tail(id, last)
             }
-            program.stack.push(Value::Int(count as i64));
-        }
-        (Value::String(string), Value::String(count_string)) => {
-            let mut count: usize = 0;
-            for idx in 0..string.len() {
-                if string.get(idx..idx+count_string.len()) == Some(count_string.as_str()) {
-                    count += 1;
+            NodeType::Match(cases, else_node) => {
+                self.step(&node.pos)?;
+                let arm = self.arm(&node.pos, cases, else_node)?;
+                self.run_tail(id, arm)
+            }
+            NodeType::If(case_node, else_node) => {
+                self.step(&node.pos)?;
+                match self.branch(&node.pos, *case_node, else_node)? {
+                    Some(branch) => self.run_tail(id, branch),
+                    None => Ok(None)
+                }
+            }
+            NodeType::ID(ref call) if self.macro_name(call) == self.macro_name(id) => {
+                match self.select(call)? {
+                    Some(MacroType::Macro(body)) => {
+                        self.step(&node.pos)?;
+                        Ok(Some(body))
+                    }
+                    _ => self.run(node).map(|_| None)
                 }
             }
-            program.stack.push(Value::Int(count as i64));
+            _ => self.run(node).map(|_| None)
         }
-        _ => panic!("type checking error!!!")
     }
-    Ok(())
+    pub fn std_program() -> Self {
+        Self::with_features(Feature::ALL)
+    }
+    /// a program with the core builtins plus the given feature groups
+    pub fn with_features(features: &[Feature]) -> Self {
+        let mut program = Self { features: features.to_vec(), ..Self::new() };
+        program.reset();
+        program
+    }
 }
-fn _split(program: &mut Program) -> Result<(), Error> {
-    let (b, a) = (program.stack.pop().unwrap(), program.stack.pop().unwrap());
-    match (a, b) {
-        (Value::String(string), Value::Char(pattern)) => {
-            let mut parts: Vec<&str> = string.split(pattern).collect();
-            let len = parts.len();
-            for part in parts {
-                program.stack.push(Value::String(part.to_string()));
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// runs `text` in a fresh program with every builtin
+    pub(crate) fn run(text: &str) -> Result<Program, Error> {
+        run_in(Program::std_program(), text)
+    }
+    /// runs `text` in `program`, for programs with options set
+    pub(crate) fn run_in(mut program: Program, text: &str) -> Result<Program, Error> {
+        program.run(parser::parse(lexer::lex(text.to_string())?)?)?;
+        Ok(program)
+    }
+    /// the stack `text` leaves
+    pub(crate) fn stack(text: &str) -> Result<Vec<Value>, Error> {
+        Ok(run(text)?.stack.as_slice().to_vec())
+    }
+
+    #[test]
+    fn def_replaces_every_overlapping_overload() {
+        let program = run("macro f [int] 1 end macro f [str] 2 end macro f [any] 3 end").unwrap();
+        assert_eq!(program.macros["f"].macros.len(), 1);
+        assert_eq!(stack("macro f [int] 1 end macro f [str] 2 end macro f [any] 3 end 'c' f 0 f \"s\" f").unwrap(),
+            vec![Value::Char('c'), Value::Int(3), Value::Int(0), Value::Int(3), Value::String("s".to_string()), Value::Int(3)]);
+        assert_eq!(stack("macro f [any] 3 end macro f [int] 1 end 0 f").unwrap(), vec![Value::Int(0), Value::Int(1)]);
+        assert!(stack("macro f [any] 3 end macro f [int] 1 end \"s\" f").is_err());
+    }
+    #[test]
+    fn def_keeps_other_overloads() {
+        assert_eq!(stack("macro f [int] 1 end macro f [str] 2 end macro f [int] 3 end 0 f \"s\" f").unwrap(),
+            vec![Value::Int(0), Value::Int(3), Value::String("s".to_string()), Value::Int(2)]);
+    }
+    #[test]
+    fn def_overlapping_a_builtin() {
+        assert!(run("macro len [str] 0 end").is_err());
+        assert!(run("macro len [any] 0 end").is_err());
+        assert!(run("macro rev [int] 0 end").is_ok());
+    }
+
+    /// the body of `repeat body end`
+    fn repeat_body(body: &str) -> Node {
+        let node = parser::parse(lexer::lex(format!("repeat {body} end")).unwrap()).unwrap();
+        match node.node {
+            NodeType::Chunk(mut nodes) => match nodes.pop().unwrap().node {
+                NodeType::Repeat(body) => *body,
+                _ => unreachable!()
             }
-            program.stack.push(Value::Int(len as i64));
+            _ => unreachable!()
         }
-        (Value::String(string), Value::String(pattern)) => {
-            let mut parts: Vec<&str> = string.split(pattern.as_str()).collect();
-            let len = parts.len();
-            for part in parts {
-                program.stack.push(Value::String(part.to_string()));
+    }
+    /// runs `count repeat body end` after `setup` once as a script and once by running the body
+    /// `count` times, checks that both leave the same stack and variables and returns whether the
+    /// script took the fused path
+    fn repeat_both_ways(setup: &str, count: i64, body: &str) -> bool {
+        let fused = run(&format!("{setup} {count} repeat {body} end"));
+        let mut naive = run(setup).unwrap();
+        let naive = (0..count).try_for_each(|_| naive.run(repeat_body(body))).map(|_| naive);
+        match (fused, naive) {
+            (Ok(fused), Ok(naive)) => {
+                assert_eq!(fused.stack.as_slice(), naive.stack.as_slice());
+                assert_eq!(fused.vars, naive.vars);
             }
-            program.stack.push(Value::Int(len as i64));
+            (Err(fused), Err(naive)) => assert_eq!(fused.msg(), naive.msg()),
+            (fused, naive) => panic!("fused {:?}, naive {:?}", fused.err(), naive.err())
         }
-        _ => panic!("type checking error!!!")
+        run(setup).unwrap().fused_repeat(count, &repeat_body(body))
     }
-    Ok(())
-}
-fn _join(program: &mut Program) -> Result<(), Error> {
-    let a = program.stack.pop().unwrap();
-    let len = program.stack.len();
-    let mut strings = vec![];
-    for _ in 0..len {
-        let Some(value) = program.stack.pop() else { break };
-        if let Value::String(value) = value {
-            strings.push(value);
-        } else {
-            strings.push(value.to_string())
-        }
+    #[test]
+    fn fused_repeat_matches_loop() {
+        assert!(repeat_both_ways(r#""""#, 1000, r#""ab" +"#));
+        assert!(repeat_both_ways(r#""x""#, 50, "'é' +"));
+        assert!(repeat_both_ways("7", 100, "3 +"));
+        assert!(repeat_both_ways("-7", 100, "-3 +"));
+        assert!(repeat_both_ways("1", 10, "2 *"));
+        assert!(repeat_both_ways("5", 3, "0 *"));
     }
-    let strings: Vec<String> = strings.iter().rev().map(|s| s.clone()).collect();
-    match a {
-        Value::Char(char) => {
-            program.stack.push(Value::String(strings.join(char.to_string().as_str())));
-        }
-        Value::String(string) => {
-            program.stack.push(Value::String(strings.join(string.as_str())));
+    #[test]
+    fn fused_repeat_falls_back() {
+        assert!(!repeat_both_ways("0", 5, "1 + 2 +"));
+        assert!(!repeat_both_ways("0", 0, "1 +"));
+        assert!(!repeat_both_ways("0", -3, "1 +"));
+        assert!(!repeat_both_ways("2 (x) 0", 4, "@x +"));
+        assert!(!repeat_both_ways("macro add [int int] + end 0", 5, "2 add"));
+        assert!(!repeat_both_ways(r#""a""#, 5, "1 +"));
+        assert!(!repeat_both_ways("1", 5, "2.5 *"));
+        assert!(!repeat_both_ways("1", 3, "x"));
+    }
+    #[test]
+    fn fused_repeat_falls_back_on_overflow() {
+        assert!(!repeat_both_ways("9223372036854775800", 10, "1 +"));
+        assert!(!repeat_both_ways("2", 70, "2 *"));
+        assert!(!run("1").unwrap().fused_repeat(5_000_000_000, &repeat_body("1 *")));
+        let error = run("9223372036854775806 5 repeat 1 + end").err().unwrap();
+        assert_eq!(error.msg(), "9223372036854775807 + 1 overflows an int");
+        assert_eq!(error.pos().map(|pos| pos.col.clone()), Some(31..32));
+        let mut program = run("9223372036854775806").unwrap();
+        let naive = (0..5).try_for_each(|_| program.run(repeat_body("1 +"))).err().unwrap();
+        assert_eq!(naive.msg(), error.msg());
+    }
+    #[test]
+    fn fused_repeat_falls_back_near_step_limit() {
+        let mut program = run("0").unwrap();
+        program.step_limit = Some(10);
+        assert!(!program.fused_repeat(100, &repeat_body("1 +")));
+        program.step_limit = None;
+        let steps = program.steps;
+        assert!(program.fused_repeat(100, &repeat_body("1 +")));
+        assert_eq!(program.stack.as_slice(), &[Value::Int(100)]);
+        assert_eq!(program.steps - steps, 300);
+    }
+    /// a file in the temp directory with `text`, named after the test so tests don't share it
+    pub(crate) fn temp_file(name: &str, text: &str) -> String {
+        let path = std::env::temp_dir().join(format!("str-{}-{name}", std::process::id()));
+        fs::write(&path, text).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+    #[test]
+    fn with_records_joins_quoted_lines() {
+        let path = temp_file("records.csv", "a,b\r\n\"c\nd\",e\nf\n");
+        assert_eq!(stack(&format!("{path:?} with-records end")).unwrap(),
+            vec![Value::String("a,b".to_string()), Value::String("\"c\nd\",e".to_string()), Value::String("f".to_string())]);
+        let path = temp_file("quote.csv", "'x\ny'\n\"z\n");
+        assert_eq!(stack(&format!("{path:?} '\\'' with-records end")).unwrap(),
+            vec![Value::String("'x\ny'".to_string()), Value::String("\"z".to_string())]);
+        let path = temp_file("escape.csv", "\"a\\\"\nb\"\n");
+        assert_eq!(stack(&format!("{path:?} '\"' '\\\\' with-records end")).unwrap(), vec![Value::String("\"a\\\"\nb\"".to_string())]);
+    }
+    #[test]
+    fn with_records_unclosed_quote() {
+        let path = temp_file("unclosed.csv", "a\n\"b\nc\n");
+        let error = run(&format!("{path:?} with-records end")).err().unwrap();
+        assert!(error.msg().starts_with("unclosed quote"));
+        assert!(run("\"does-not-exist.csv\" with-records end").is_err());
+    }
+    #[test]
+    fn include_searches_the_search_path() {
+        let path = temp_file("searched.str", "macro searched [] 7 end");
+        let name = Path::new(&path).file_name().unwrap().to_string_lossy().into_owned();
+        let mut program = Program::std_program();
+        assert_eq!(program.resolve(&name), None);
+        program.search_path.push(std::env::temp_dir());
+        assert_eq!(program.resolve(&name), Some(PathBuf::from(&path)));
+        program.run(parser::parse(lexer::lex(format!("include {name:?} searched")).unwrap()).unwrap()).unwrap();
+        assert_eq!(program.stack.as_slice(), &[Value::Int(7)]);
+        assert!(run(&format!("include {name:?}")).is_err());
+    }
+    #[test]
+    fn no_overload_error_shows_the_stack() {
+        let error = run("1 2 rev").err().unwrap();
+        assert_eq!(error.msg(), "no macro definition \"rev\" found with current stack\nstack: ... 2:int\nfollowing macros are defined:\n[str] rev\n\n");
+        assert_eq!(error.pos().map(|pos| pos.col.clone()), Some(4..7));
+        let error = run(&format!("{:?} 1.5 .", "x".repeat(30))).err().unwrap();
+        assert!(error.msg().contains(&format!("stack: \"{}...:str 1.5:float\n", "x".repeat(20))), "{}", error.msg());
+    }
+    #[test]
+    fn display_typed() {
+        let program = run(r#""abc" 3 true 1.5 'c'"#).unwrap();
+        assert_eq!(program.stack().display_typed(), r#""abc":str 3:int true:bool 1.5:float 'c':char"#);
+        assert_eq!(Stack::new().display_typed(), "");
+    }
+    #[test]
+    fn underflow_names_the_arity() {
+        let error = run(r#""x" swap"#).err().unwrap();
+        assert_eq!(error.msg(), "\"swap\" needs 2 values but the stack has 1, following macros are defined:\n[any any] swap\n\n");
+        assert_eq!(run("drop").err().unwrap().msg().lines().next(), Some("\"drop\" needs 1 value but the stack has 0, following macros are defined:"));
+        // enough values of the wrong types is a no-overload error instead
+        assert!(run("1 2 rev").err().unwrap().msg().starts_with("no macro definition"));
+    }
+    #[test]
+    fn reset_keeps_builtins_and_settings() {
+        let mut program = Program::std_program();
+        program.strict_index = true;
+        let mut program = run_in(program, "1 (x) 2 macro mine [] 1 end macro len [int] 0 end alias l len checkpoint").unwrap();
+        let builtins = Program::std_program().macros.len();
+        program.reset();
+        assert_eq!((program.stack.len(), program.vars.len(), program.checkpoints.len()), (0, 0, 0));
+        assert!(program.get_macro("mine").is_none() && program.get_macro("l").is_none());
+        assert_eq!(program.macros.len(), builtins);
+        assert!(program.strict_index);
+        // the user overload of a builtin is gone, the builtin one stays
+        let program = run_in(program, r#""ab" len"#).unwrap();
+        assert_eq!(program.stack.as_slice(), &[Value::Int(2)]);
+        assert!(run_in(program, "5 len").is_err());
+    }
+    #[test]
+    fn alias_shares_the_overloads() {
+        assert_eq!(stack("alias dup copy 1 dup").unwrap(), vec![Value::Int(1), Value::Int(1)]);
+        assert_eq!(stack("macro f [] 1 end alias g f macro f [] 2 end g").unwrap(), vec![Value::Int(2)]);
+        assert_eq!(stack(r#"macro f [int] 1 end alias g f macro g [str] 3 end "x" f"#).unwrap(), vec![Value::String("x".to_string()), Value::Int(3)]);
+        assert_eq!(stack("alias a copy alias b a 1 b").unwrap(), vec![Value::Int(1), Value::Int(1)]);
+    }
+    #[test]
+    fn alias_errors() {
+        assert_eq!(run("alias x nothing").err().unwrap().msg(), "cannot alias \"x\" to \"nothing\", no such macro is defined");
+        assert_eq!(run("alias copy swap").err().unwrap().msg(), "cannot alias \"copy\", it is already defined as a macro");
+    }
+    #[test]
+    fn user_defined_operators() {
+        assert_eq!(stack(r#"macro <> [str str] " " swap + + end "a" "b" <>"#).unwrap(), vec![Value::String("a b".to_string())]);
+        // operators are postfix macros like any other, without precedence
+        assert_eq!(stack(r#"macro ;; [int int] + end alias +++ ;; 1 2 3 ;; +++"#).unwrap(), vec![Value::Int(6)]);
+        assert_eq!(stack("macro ! [bool] not end true !").unwrap(), vec![Value::Boolean(false)]);
+    }
+    #[test]
+    fn step_limit_stops_endless_loops() {
+        let mut program = Program::std_program();
+        program.step_limit = Some(1000);
+        let error = run_in(program, "while true do end").err().unwrap();
+        assert_eq!(error.msg(), "step limit of 1000 reached, the program may be stuck in an endless loop");
+        let mut program = Program::std_program();
+        program.step_limit = Some(1000);
+        assert!(run_in(program, "0 10 repeat 1 + end").is_ok());
+        // without a limit long loops run to the end
+        assert_eq!(stack("0 20000 repeat 1 + copy drop end").unwrap(), vec![Value::Int(20000)]);
+    }
+    #[test]
+    fn with_lines_runs_the_body_for_every_line() {
+        let path = temp_file("lines.txt", "a\nbb\r\nccc");
+        assert_eq!(stack(&format!("{path:?} with-lines len end")).unwrap(), vec![Value::Int(1), Value::Int(2), Value::Int(3)]);
+        let path = temp_file("no-lines.txt", "");
+        assert_eq!(stack(&format!("{path:?} with-lines 1 end")).unwrap(), vec![]);
+    }
+    #[test]
+    fn with_lines_errors() {
+        assert_eq!(run("1 with-lines end").err().unwrap().msg(), "expected a file path on top of the stack, got int");
+        assert_eq!(run("with-lines end").err().unwrap().msg(), "couldn't perform with-lines-control-flow operation due to stack underflow");
+        assert!(run("\"does-not-exist.txt\" with-lines end").err().unwrap().msg().starts_with("error occurd while opening the file"));
+    }
+    #[test]
+    fn fork_copies_without_sharing() {
+        let mut program = run("macro f [] 1 end 5 (x)").unwrap();
+        program.steps = 10;
+        let fork = run_in(program.fork(), "f @x 2 (x) macro g [] 2 end").unwrap();
+        assert_eq!(fork.stack.as_slice(), [Value::Int(1), Value::Int(5)]);
+        assert_eq!(fork.vars.get("x"), Some(&Value::Int(2)));
+        assert!(fork.get_macro("g").is_some() && program.get_macro("g").is_none());
+        assert_eq!(program.vars.get("x"), Some(&Value::Int(5)));
+        assert_eq!(program.steps, 10);
+    }
+    #[cfg(feature = "rand")]
+    #[test]
+    fn fork_draws_the_next_random_numbers() {
+        let program = run("3 seed").unwrap();
+        let drawn = run_in(program.fork(), "0 1000 rand-int").unwrap().stack.as_slice().to_vec();
+        assert_eq!(run_in(program, "0 1000 rand-int").unwrap().stack.as_slice(), drawn);
+    }
+    #[test]
+    fn user_macros_dispatch_on_types_and_recurse() {
+        if cfg!(feature = "math") {
+            assert_eq!(stack("macro fact [int] copy 1 > if copy 1 - fact * end end 5 fact").unwrap(), vec![Value::Int(120)]);
         }
-        _ => panic!("type checking error!!!")
+        assert_eq!(stack("macro kind [int] drop \"int\" end macro kind [str] drop \"str\" end 1 kind \"a\" kind").unwrap(),
+            vec![Value::String("int".to_string()), Value::String("str".to_string())]);
+        assert_eq!(run("macro f [nope] 1 end").err().unwrap().msg(), "unknown type \"nope\"");
+    }
+    #[test]
+    fn redefining_a_builtin_points_at_the_definition() {
+        let error = run("  macro len [str] 0 end").err().unwrap();
+        assert_eq!(error.msg(), "cannot redefine the builtin \"len\" taking [str]");
+        assert_eq!(error.pos().unwrap().col.start, 2);
+    }
+    #[test]
+    fn while_loops_until_the_condition_is_false() {
+        assert_eq!(stack("0 while copy 3 = not do 1 + end").unwrap(), vec![Value::Int(3)]);
+        assert_eq!(stack("while false do 1 end 2").unwrap(), vec![Value::Int(2)]);
+    }
+    #[test]
+    fn while_errors_point_at_the_loop() {
+        let error = run("0 while 1 do end").err().unwrap();
+        assert_eq!(error.msg(), "expected a boolean value on top of the stack, got int");
+        assert_eq!(error.pos().unwrap().col.start, 2);
+        assert_eq!(run("while do end").err().unwrap().msg(), "couldn't perform while-control-flow operation due to stack underflow");
+    }
+    #[cfg(feature = "math")]
+    #[test]
+    fn tail_calls_run_in_constant_stack_space() {
+        assert_eq!(stack("macro down [int] copy 0 = not if 1 - down end end 100000 down").unwrap(), vec![Value::Int(0)]);
+        // a call that isn't the last thing the macro does still nests
+        assert_eq!(stack("macro fact [int] copy 1 > if copy 1 - fact * end end 10 fact").unwrap(), vec![Value::Int(3628800)]);
+    }
+    #[test]
+    fn tail_calls_select_the_overload_again() {
+        assert_eq!(stack("macro f [str] len f end macro f [int] 1 + end \"abc\" f").unwrap(), vec![Value::Int(4)]);
+        let mut program = Program::std_program();
+        program.step_limit = Some(1000);
+        let error = run_in(program, "macro forever [] forever end forever").err().unwrap();
+        assert!(error.msg().starts_with("step limit of 1000 reached"));
+    }
+    #[test]
+    fn foreach_pushes_every_char() {
+        assert_eq!(stack("\"héllo\" foreach end").unwrap(), "héllo".chars().map(Value::Char).collect::<Vec<_>>());
+        assert_eq!(stack("\"ab\" foreach copy end").unwrap(), vec![Value::Char('a'), Value::Char('a'), Value::Char('b'), Value::Char('b')]);
+        assert_eq!(stack("\"\" foreach 1 end").unwrap(), vec![]);
+    }
+    #[test]
+    fn foreach_errors() {
+        assert_eq!(run("1 foreach end").err().unwrap().msg(), "expected a string on top of the stack, got int");
+        assert_eq!(run("foreach end").err().unwrap().msg(), "couldn't perform foreach-control-flow operation due to stack underflow");
+    }
+    #[test]
+    fn blocks_run_when_called() {
+        assert_eq!(stack("[\"a\" \"b\" +] call").unwrap(), vec![Value::String("ab".to_string())]);
+        assert_eq!(stack("[] call 5 [[1]] call call").unwrap(), vec![Value::Int(5), Value::Int(1)]);
+        assert_eq!(stack("macro twice [block] (f) @f call @f call end 0 [1 +] twice").unwrap(), vec![Value::Int(2)]);
+        let stack = stack("[1 2 +]").unwrap();
+        assert_eq!(stack.len(), 1);
+        assert_eq!(stack[0].to_string(), "[1 2 +]");
+    }
+    #[test]
+    fn block_errors() {
+        assert_eq!(run("[1").err().unwrap().msg(), "unclosed quotation");
+        assert!(run("1 call").err().unwrap().msg().starts_with("no macro definition \"call\" found"));
+        // errors inside a block point at the code in the block
+        let error = run("[\"a\" 1 +] call").err().unwrap();
+        assert_eq!(error.pos().unwrap().col.start, 7);
+    }
+    #[test]
+    fn traced_origins_show_up_in_errors() {
+        let mut program = Program::std_program();
+        program.stack.trace_origins();
+        let error = run_in(program, "1 \"x\" if 1 end").err().unwrap();
+        assert_eq!(error.msg(), "expected a boolean value on top of the stack, got str, pushed at 1:3");
+        assert_eq!(run("1 \"x\" if 1 end").err().unwrap().msg(), "expected a boolean value on top of the stack, got str");
+        let mut program = Program::std_program();
+        program.stack.trace_origins();
+        let program = run_in(program, "1 \"a\" 2 (x) @x").unwrap();
+        let origins: Vec<_> = program.stack.with_origins().map(|(_, pos)| pos.map(|pos| pos.col.start)).collect();
+        assert_eq!(origins, [Some(0), Some(2), Some(12)]);
+    }
+    #[test]
+    fn stack_api() {
+        let mut stack = Stack::from(vec![Value::Int(1), Value::Int(2), Value::Int(3)]);
+        assert_eq!(stack.peek(), Some(&Value::Int(3)));
+        assert_eq!(stack.get(0), Some(&Value::Int(1)));
+        assert_eq!(stack.get(3), None);
+        assert_eq!(stack.iter().count(), 3);
+        stack.truncate(5);
+        assert_eq!(stack.len(), 3);
+        stack.trace_origins();
+        stack.push(Value::Int(4));
+        stack.truncate(2);
+        assert_eq!(stack.as_slice(), [Value::Int(1), Value::Int(2)]);
+        // the origins are truncated along with the values
+        assert_eq!(stack.with_origins().len(), 2);
+        stack.clear();
+        assert_eq!(stack.len(), 0);
+        assert_eq!(stack.pop_with_origin(), None);
+    }
+    #[test]
+    fn stack_accessors() {
+        let mut program = Program::std_program();
+        *program.stack_mut() = Stack::from(vec![Value::String("a".to_string())]);
+        let program = run_in(program, "\"b\" +").unwrap();
+        assert_eq!(program.stack().to_string(), "\"ab\"");
+    }
+    #[test]
+    fn include_runs_in_the_same_program() {
+        let path = temp_file("included.str", "macro twice [str] copy + end 5 (n)");
+        assert_eq!(stack(&format!("include {path:?} \"ab\" twice n")).unwrap(), vec![Value::String("abab".to_string()), Value::Int(5)]);
+    }
+    #[test]
+    fn include_errors_name_the_included_file() {
+        let path = temp_file("broken.str", "1\n1 \"a\" if end");
+        let text = format!("include {path:?}");
+        let error = run(&text).err().unwrap();
+        assert_eq!(error.msg(), "expected a boolean value on top of the stack, got str");
+        assert!(error.display_text("main.str", text).starts_with(&format!("ERROR: expected a boolean value on top of the stack, got str - {path}:2:")));
+        assert_eq!(run("include \"does-not-exist.str\"").err().unwrap().msg(), "couldn't find the file \"does-not-exist.str\"");
+    }
+    #[test]
+    fn include_cycles_are_errors() {
+        let path = temp_file("cycle.str", "");
+        fs::write(&path, format!("include {path:?}")).unwrap();
+        let error = run(&format!("include {path:?}")).err().unwrap();
+        assert_eq!(error.msg(), format!("{path:?} is already being included, files may not include themselves"));
+    }
+    #[test]
+    fn import_prefixes_the_module_macros() {
+        let path = temp_file("module.str", "macro shout [str] \"!\" + end\nmacro twice [str] shout shout end\nalias yell shout\n5 (x)");
+        assert_eq!(stack(&format!("import {path:?} as u \"a\" u:twice \"b\" u:yell")).unwrap(),
+            vec![Value::String("a!!".to_string()), Value::String("b!".to_string())]);
+        // calls inside the module keep using its own macros
+        assert_eq!(stack(&format!("macro shout [str] \"?\" + end import {path:?} as u \"a\" u:twice")).unwrap(), vec![Value::String("a!!".to_string())]);
+        assert_eq!(run(&format!("import {path:?} as u \"a\" twice")).err().unwrap().msg(), "unknown id \"twice\"");
     }
-    Ok(())
-}
\ No newline at end of file
+    #[test]
+    fn import_isolates_variables() {
+        let path = temp_file("vars-module.str", "5 (x)");
+        assert_eq!(run(&format!("import {path:?} as u x")).err().unwrap().msg(), "unknown id \"x\"");
+        assert_eq!(stack(&format!("1 (x) import {path:?} as u x")).unwrap(), vec![Value::Int(1)]);
+        assert_eq!(run(&format!("import {path:?}")).err().unwrap().msg(), "expected as after the file path");
+    }
+    #[test]
+    fn seeding_from_the_host() {
+        let mut program = Program::std_program();
+        program.push_value(Value::String("a".to_string()));
+        program.set_var("suffix", Value::String("b".to_string()));
+        let mut program = run_in(program, "@suffix + suffix +").unwrap();
+        assert_eq!(program.stack.as_slice(), [Value::String("abb".to_string())]);
+        assert_eq!(program.vars.get("suffix"), None);
+        // a variable set by the host can be used again after it was moved
+        program.set_var("suffix", Value::Int(1));
+        assert_eq!(run_in(program, "suffix").unwrap().stack.as_slice()[1], Value::Int(1));
+    }
+    #[test]
+    fn strict_vars_point_at_the_move() {
+        let strict = || {
+            let mut program = Program::std_program();
+            program.strict_vars = true;
+            program
+        };
+        let error = run_in(strict(), "1 (x) x\n x").err().unwrap();
+        assert_eq!(error.msg(), "variable \"x\" was already moved onto the stack at 1:7, use @x there to copy it instead");
+        assert_eq!(run("1 (x) x x").err().unwrap().msg(), "unknown id \"x\"");
+        // setting the variable again makes it usable
+        assert_eq!(run_in(strict(), "1 (x) x 2 (x) x").unwrap().stack.as_slice(), [Value::Int(1), Value::Int(2)]);
+        assert_eq!(run_in(strict(), "y").err().unwrap().msg(), "unknown id \"y\"");
+    }
+    #[test]
+    fn copy_vars_keep_the_variable() {
+        let mut program = Program::std_program();
+        program.copy_vars = true;
+        let program = run_in(program, "1 (x) x x").unwrap();
+        assert_eq!(program.stack.as_slice(), [Value::Int(1), Value::Int(1)]);
+        assert_eq!(program.vars.get("x"), Some(&Value::Int(1)));
+        // a pragma turns it on for its own file only
+        let path = temp_file("copy-vars.str", "#!str: copy-vars\n1 (x) x x");
+        assert_eq!(run(&format!("include {path:?}")).unwrap().stack.as_slice(), [Value::Int(1), Value::Int(1)]);
+        assert_eq!(run(&format!("include {path:?} 2 (y) y y")).err().unwrap().msg(), "unknown id \"y\"");
+    }
+    #[test]
+    fn reorders() {
+        assert_eq!(stack("1 2 3 (a b c -> c a b) 4 (x -> x x)").unwrap(),
+            [3, 1, 2, 4, 4].map(Value::Int).to_vec());
+        let program = run("1 2 (a b -> b a)").unwrap();
+        assert_eq!(program.stack.as_slice(), [Value::Int(2), Value::Int(1)]);
+        assert!(program.vars.is_empty());
+        assert_eq!(run("1 (a b -> b a)").err().unwrap().msg(), "cannot reorder 2 values due to stack underflow");
+        assert_eq!(run("1 2 (a b -> c)").err().unwrap().msg(), "\"c\" isn't taken before the -> of the reorder");
+        assert_eq!(run("1 2 (a a -> a)").err().unwrap().msg(), "\"a\" is taken twice in the reorder");
+    }
+    #[test]
+    fn guards_pick_the_overload() {
+        assert_eq!(stack("macro sign [int] when [0 =] drop \"zero\" end macro sign [int] drop \"other\" end 0 sign 5 sign").unwrap(),
+            vec![Value::String("zero".to_string()), Value::String("other".to_string())]);
+        // the guard reads copies in its own scope
+        assert_eq!(stack("macro f [int] when [(n) true] \"x\" end 1 (n) 0 f n").unwrap(),
+            vec![Value::Int(0), Value::String("x".to_string()), Value::Int(1)]);
+        assert!(run("macro f [int] when [0 =] \"zero\" end 5 f").err().unwrap().msg().starts_with("no macro definition \"f\" found"));
+    }
+    #[test]
+    fn guard_errors() {
+        assert_eq!(run("macro f [int] when [1] \"x\" end 0 f").err().unwrap().msg(), "expected the guard to leave a boolean, got int");
+        assert_eq!(run("macro f [int] when [drop] \"x\" end 0 f").err().unwrap().msg(), "the guard left no value on the stack");
+        assert_eq!(run("macro f [int] when [drop drop true] \"x\" end 1 0 f").err().unwrap().msg(), "the guard left no value on the stack");
+    }
+    #[test]
+    fn macro_references() {
+        assert_eq!(stack("\"abc\" &len apply macro f [] 1 end &f (g) @g apply g apply").unwrap(), [3, 1, 1].map(Value::Int).to_vec());
+        assert_eq!(stack("&len").unwrap()[0].to_string(), "[len]");
+        assert_eq!(run("&nope").err().unwrap().msg(), "cannot reference \"nope\", it isn't defined as a macro");
+        assert!(run("1 &len apply").err().unwrap().msg().starts_with("no macro definition \"len\" found"));
+    }
+    #[test]
+    fn match_runs_the_first_matching_case() {
+        let result = |text: &str| stack(text).unwrap();
+        let string = |string: &str| vec![Value::String(string.to_string())];
+        assert_eq!(result("2 match case 1 \"one\" case 2 \"two\" else \"many\" end"), string("two"));
+        assert_eq!(result("5 match case 1 \"one\" else \"many\" end"), string("many"));
+        assert_eq!(result("\"a\" match case int \"int\" case str \"string\" end"), string("string"));
+        assert_eq!(result("1.0 match case 1 \"int\" case float \"float\" end"), string("float"));
+        assert_eq!(result("1 match case 1 \"first\" case 1 \"second\" end"), string("first"));
+    }
+    #[test]
+    fn match_errors() {
+        assert_eq!(run("5 match case \"x\" 1 end").err().unwrap().msg(), "no case matches 5");
+        assert_eq!(run("match case 1 2 end").err().unwrap().msg(), "couldn't perform match-control-flow operation due to stack underflow");
+        assert_eq!(run("1 match case foo 1 end").err().unwrap().msg(), "unknown type \"foo\"");
+        assert_eq!(run("1 match 2 case 1 1 end").err().unwrap().msg(), "expected case, else or end of the match, got int");
+    }
+    #[test]
+    fn holes_discard_values() {
+        let program = run("1 2 3 (a _ b) a b").unwrap();
+        assert_eq!(program.stack.as_slice(), [Value::Int(1), Value::Int(3)]);
+        assert!(program.vars.is_empty());
+        assert_eq!(stack("1 2 (_ _) 3 4 (a _ -> a)").unwrap(), vec![Value::Int(3)]);
+        assert_eq!(run("1 (_) _").err().unwrap().msg(), "unknown id \"_\"");
+        assert_eq!(run("(_)").err().unwrap().msg(), "cannot take value to \"_\" due to stack underflow");
+        assert_eq!(run("1 (a -> _)").err().unwrap().msg(), "the hole _ only discards a value, it can't be pushed by a reorder");
+    }
+    #[test]
+    fn declared_outputs_are_displayed() {
+        let program = run("macro p [str -- int bool] drop 1 true end macro p [int] end macro q [ -- str] \"a\" end").unwrap();
+        assert_eq!(program.display_macro("p"), "[int] p\n[str -- int bool] p\n");
+        assert_eq!(program.display_macro("q"), "[ -- str] q\n");
+        assert_eq!(stack("macro p [str -- int bool] drop 1 true end \"a\" p").unwrap(), vec![Value::Int(1), Value::Boolean(true)]);
+        assert_eq!(run("macro p [str -- nope] end").err().unwrap().msg(), "unknown type \"nope\"");
+    }
+    #[test]
+    fn let_scopes_variables() {
+        assert_eq!(stack("1 (x) let 2 (x) x end x").unwrap(), vec![Value::Int(2), Value::Int(1)]);
+        assert_eq!(stack("let 2 (x) let 3 (x) end x end 1 (x) let @x end").unwrap(), vec![Value::Int(2), Value::Int(1)]);
+        assert_eq!(run("let 5 (y) end y").err().unwrap().msg(), "unknown id \"y\"");
+        // an error inside the block still ends the scope
+        let mut program = run("1 (x)").unwrap();
+        assert!(program.run(parser::parse(lexer::lex(String::from("let 2 (x) \"oops\" fail end")).unwrap()).unwrap()).is_err());
+        assert_eq!(program.vars.get("x"), Some(&Value::Int(1)));
+    }
+    #[test]
+    fn macro_calls_get_their_own_frame() {
+        assert_eq!(stack("macro f [int] (x) end 1 (x) 5 f x").unwrap(), vec![Value::Int(1)]);
+        // the caller's variables can still be read
+        assert_eq!(stack("macro f [] @x end 1 (x) f").unwrap(), vec![Value::Int(1)]);
+        assert_eq!(run("macro f [] 2 (y) end f y").err().unwrap().msg(), "unknown id \"y\"");
+        // recursive calls don't share a frame either
+        assert_eq!(stack("macro f [int] (n) @n 0 = if \"done\" else 0 f @n end end 3 f").unwrap(),
+            vec![Value::String("done".to_string()), Value::Int(3)]);
+    }
+}
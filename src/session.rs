@@ -0,0 +1,224 @@
+//! REPL sessions saved as plain text: one line per stack value, variable, user macro and alias
+//!
+//! ```text
+//! stack int 3
+//! stack str "a\nb"
+//! var name char 'x'
+//! stack block "[1 +]"
+//! macro "## adds a bang\nmacro shout [str] \"!\" + end"
+//! alias yell shout
+//! ```
+use crate::{lexer, parser};
+use crate::lexer::Position;
+use crate::parser::{Node, NodeType};
+use crate::run::Program;
+use crate::value::{Type, Value};
+
+fn unescape(text: &str, quote: char) -> Result<String, String> {
+    let Some(inner) = text.strip_prefix(quote).and_then(|text| text.strip_suffix(quote)) else {
+        return Err(format!("expected {quote}-quoted text, got {text}"))
+    };
+    let mut string = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            string.push(c);
+            continue
+        }
+        match chars.next() {
+            Some('n') => string.push('\n'),
+            Some('t') => string.push('\t'),
+            Some('r') => string.push('\r'),
+            Some('0') => string.push('\0'),
+            Some('u') => {
+                let code: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                let code = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32);
+                string.push(code.ok_or(format!("invalid unicode escape in {text}"))?);
+            }
+            Some(c) => string.push(c),
+            None => return Err(format!("unfinished escape in {text}"))
+        }
+    }
+    Ok(string)
+}
+
+fn parse_value(typ: &str, text: &str) -> Result<Value, String> {
+    let invalid = |_| format!("invalid {typ} {text}");
+    match Type::get(typ) {
+        Some(Type::String) => Ok(Value::String(unescape(text, '"')?)),
+        Some(Type::Char) => {
+            let string = unescape(text, '\'')?;
+            let mut chars = string.chars();
+            match (chars.next(), chars.next()) {
+                (Some(char), None) => Ok(Value::Char(char)),
+                _ => Err(format!("invalid char {text}"))
+            }
+        }
+        Some(Type::Int) => text.parse().map(Value::Int).map_err(invalid),
+        Some(Type::Float) => text.parse().map(Value::Float).map_err(|_| format!("invalid float {text}")),
+        Some(Type::Boolean) => text.parse().map(Value::Boolean).map_err(|_| format!("invalid bool {text}")),
+        Some(Type::Block) => {
+            let source = unescape(text, '"')?;
+            let node = lexer::lex(source).and_then(parser::parse).map_err(|e| e.msg().to_string())?;
+            match node.node {
+                NodeType::Chunk(mut nodes) if nodes.len() == 1 => match nodes.pop().unwrap().node {
+                    NodeType::Block(body) => Ok(Value::Block(body)),
+                    _ => Err(format!("invalid block {text}"))
+                }
+                _ => Err(format!("invalid block {text}"))
+            }
+        }
+        #[cfg(feature = "decimal")]
+        Some(Type::Decimal) => crate::decimal::Decimal::parse(text).map(Value::Decimal).ok_or(format!("invalid dec {text}")),
+        _ => Err(format!("unknown type {typ}"))
+    }
+}
+
+/// the value as written after its type, blocks are quoted since their source may span lines
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::Block(_) => format!("{:?}", value.to_string()),
+        _ => format!("{value:?}")
+    }
+}
+
+/// writes the stack, the variables and the user defined macros of the program
+pub fn save(program: &Program) -> String {
+    let mut text = String::new();
+    for value in program.stack().iter() {
+        text.push_str(format!("stack {} {}\n", value.typ(), value_text(value)).as_str());
+    }
+    let mut vars: Vec<(&String, &Value)> = program.vars.iter().collect();
+    vars.sort_by_key(|(id, _)| *id);
+    for (id, value) in vars {
+        text.push_str(format!("var {id} {} {}\n", value.typ(), value_text(value)).as_str());
+    }
+    let mut ids: Vec<&String> = program.macros.keys().collect();
+    ids.sort();
+    for id in ids {
+        let macro_overload = &program.macros[id];
+        let mut doc = macro_overload.doc.clone();
+        for (types, outputs, guard, body) in macro_overload.user_macros() {
+            let guard = guard.map(|guard| Box::new(guard.clone()));
+            let definition = Node::new(NodeType::Macro(id.clone(), types.clone(), outputs.cloned(), guard, macro_overload.effect.clone(), doc.take(), Box::new(body.clone())), body.pos.clone());
+            text.push_str(format!("macro {:?}\n", definition.to_string()).as_str());
+        }
+    }
+    let mut aliases: Vec<(&String, &String)> = program.aliases.iter().collect();
+    aliases.sort();
+    for (name, target) in aliases {
+        text.push_str(format!("alias {name} {target}\n").as_str());
+    }
+    text
+}
+
+/// restores a session written by `save` into the program
+pub fn load(program: &mut Program, text: &str) -> Result<(), String> {
+    for (ln, line) in text.lines().enumerate() {
+        let error = |e: String| format!("{}: {e}", ln + 1);
+        let mut words = line.splitn(3, ' ');
+        match words.next() {
+            Some("stack") => {
+                let (Some(typ), Some(value)) = (words.next(), words.next()) else { return Err(error(String::from("expected type and value"))) };
+                program.stack_mut().push(parse_value(typ, value).map_err(error)?);
+            }
+            Some("var") => {
+                let (Some(id), Some(rest)) = (words.next(), words.next()) else { return Err(error(String::from("expected name, type and value"))) };
+                let Some((typ, value)) = rest.split_once(' ') else { return Err(error(String::from("expected type and value"))) };
+                program.vars.insert(id.to_string(), parse_value(typ, value).map_err(error)?);
+            }
+            Some("macro") => {
+                let source = unescape(line["macro ".len()..].trim(), '"').map_err(error)?;
+                let node = lexer::lex(source).and_then(parser::parse).map_err(|e| error(e.msg().to_string()))?;
+                program.run(node).map_err(|e| error(e.msg().to_string()))?;
+            }
+            Some("alias") => {
+                let (Some(name), Some(target)) = (words.next(), words.next()) else { return Err(error(String::from("expected name and macro"))) };
+                let alias = Node::new(NodeType::Alias(name.to_string(), target.to_string()), Position::zero());
+                program.run(alias).map_err(|e| error(e.msg().to_string()))?;
+            }
+            Some("") | None => {}
+            Some(word) => return Err(error(format!("unexpected {word:?}")))
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effect::Effect;
+    use crate::run::tests::run;
+
+    const SESSION: &str = r#"
+## shouts the string
+## with a bang
+macro shout [str] (s -- s) "!" + end
+macro sign [int] when [1 =] drop "one" end
+macro sign [int] when [0 =] drop "zero" end
+macro sign [int] drop "other" end
+macro double [int -- int] 2 * end
+alias yell shout
+alias size len
+3 "a" (v)
+"#;
+
+    fn reload(program: &Program) -> Program {
+        let mut loaded = Program::std_program();
+        load(&mut loaded, &save(program)).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn save_and_load() {
+        let program = run(SESSION).unwrap();
+        let loaded = reload(&program);
+        assert_eq!(save(&loaded), save(&program));
+        assert_eq!(loaded.stack().as_slice(), &[Value::Int(3)]);
+        assert_eq!(loaded.vars, program.vars);
+        assert_eq!(loaded.aliases, program.aliases);
+    }
+    #[test]
+    fn save_and_load_docs_guards_and_effects() {
+        let mut loaded = reload(&run(SESSION).unwrap());
+        assert_eq!(loaded.doc("shout"), Some("shouts the string\nwith a bang"));
+        assert_eq!(loaded.macros["shout"].effect, Some(Effect { inputs: vec!["s".to_string()], outputs: vec!["s".to_string()] }));
+        assert!(loaded.macros["double"].display("double").contains("[int -- int] double"));
+        let node = lexer::lex(r#"1 sign 0 sign 5 sign "hi" yell"#.to_string()).and_then(parser::parse).unwrap();
+        loaded.run(node).unwrap();
+        let strings = ["one", "zero", "other", "hi!"].map(|string| Value::String(string.to_string()));
+        assert_eq!(&loaded.stack().as_slice()[1..], &strings);
+    }
+    /// saves and loads `[] curry` of the values `text` leaves, then calls the block
+    fn reload_curried(text: &str) -> Value {
+        let program = run(&format!("{text} [] curry")).unwrap();
+        let mut loaded = reload(&program);
+        loaded.run(parser::parse(lexer::lex(String::from("call")).unwrap()).unwrap()).unwrap();
+        loaded.stack().peek().unwrap().clone()
+    }
+    #[test]
+    fn save_and_load_quoted_blocks() {
+        assert_eq!(reload_curried(r#""say \"hi\" \\o/""#), Value::String(r#"say "hi" \o/"#.to_string()));
+        assert_eq!(reload_curried(r"'\''"), Value::Char('\''));
+        assert_eq!(reload_curried(r"'\\'"), Value::Char('\\'));
+        assert_eq!(reload_curried("inf"), Value::Float(f64::INFINITY));
+        assert_eq!(reload_curried("-inf"), Value::Float(f64::NEG_INFINITY));
+        assert!(matches!(reload_curried("nan"), Value::Float(float) if float.is_nan()));
+        assert_eq!(reload_curried("0.5"), Value::Float(0.5));
+    }
+    #[test]
+    fn save_and_load_match_cases() {
+        let program = run(r#"[match case "\"" 1 case '\'' 2 else 3 end] (f)"#).unwrap();
+        let mut loaded = reload(&program);
+        loaded.run(parser::parse(lexer::lex(String::from(r#"'\'' @f call "\"" @f call 'x' f call"#)).unwrap()).unwrap()).unwrap();
+        assert_eq!(loaded.stack().as_slice(), &[Value::Int(2), Value::Int(1), Value::Int(3)]);
+    }
+    #[test]
+    fn load_checks_aliases() {
+        let mut program = Program::std_program();
+        assert!(load(&mut program, "alias yell shout").is_err());
+        assert!(load(&mut program, "alias len rev").is_err());
+        assert!(load(&mut program, "alias reverse rev").is_ok());
+        assert_eq!(program.aliases["reverse"], "rev");
+    }
+}
@@ -1,8 +1,18 @@
 use std::{fmt::{Display, Debug}, collections::HashMap, hash::Hash};
+use crate::error;
+use crate::error::Error;
+use crate::error_no_pos;
+use crate::parser::Node;
+#[cfg(feature = "decimal")]
+use crate::decimal::Decimal;
 
 #[derive(Clone, PartialEq)]
 pub enum Value {
-    String(String), Char(char), Int(i64), Float(f64), Boolean(bool)
+    String(String), Char(char), Int(i64), Float(f64), Boolean(bool),
+    /// unevaluated code pushed by a quotation like `[1 +]`, run by `call`
+    Block(Box<Node>),
+    #[cfg(feature = "decimal")]
+    Decimal(Decimal)
 }
 impl Value {
     pub fn typ(&self) -> Type {
@@ -12,6 +22,9 @@ impl Value {
             Self::Int(_) => Type::Int,
             Self::Float(_) => Type::Float,
             Self::Boolean(_) => Type::Boolean,
+            Self::Block(_) => Type::Block,
+            #[cfg(feature = "decimal")]
+            Self::Decimal(_) => Type::Decimal,
         }
     }
 }
@@ -23,6 +36,9 @@ impl Debug for Value {
             Self::Int(int) => write!(f, "{int:?}"),
             Self::Float(float) => write!(f, "{float:?}"),
             Self::Boolean(boolean) => write!(f, "{boolean:?}"),
+            Self::Block(body) => write!(f, "[{body}]"),
+            #[cfg(feature = "decimal")]
+            Self::Decimal(decimal) => write!(f, "{decimal:?}"),
         }
     }
 }
@@ -34,25 +50,114 @@ impl Display for Value {
             Self::Int(int) => write!(f, "{int}"),
             Self::Float(float) => write!(f, "{float}"),
             Self::Boolean(boolean) => write!(f, "{boolean}"),
+            Self::Block(body) => write!(f, "[{body}]"),
+            #[cfg(feature = "decimal")]
+            Self::Decimal(decimal) => write!(f, "{decimal}"),
         }
     }
 }
+/// `From` a Rust type into a value and `TryFrom` a value back, failing on other types
+macro_rules! conversions {
+    ($($rust:ty => $variant:ident $name:literal),* $(,)?) => {$(
+        impl From<$rust> for Value {
+            fn from(value: $rust) -> Self { Self::$variant(value) }
+        }
+        impl TryFrom<Value> for $rust {
+            type Error = Error;
+            fn try_from(value: Value) -> Result<Self, Error> {
+                match value {
+                    Value::$variant(value) => Ok(value),
+                    value => error_no_pos!("expected {}, got {}", $name, value.typ())
+                }
+            }
+        }
+    )*};
+}
+conversions! {
+    String => String "str",
+    char => Char "char",
+    i64 => Int "int",
+    f64 => Float "float",
+    bool => Boolean "bool",
+}
+impl From<&str> for Value {
+    fn from(value: &str) -> Self { Self::String(value.to_string()) }
+}
+
+/// values are written as the plain string, number or boolean, so scripts can exchange data with any
+/// serde format; blocks can't be written and reading gives strings, ints, floats and booleans
+#[cfg(feature = "serde")]
+impl serde::Serialize for Value {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::String(string) => serializer.serialize_str(string),
+            Self::Char(char) => serializer.serialize_char(*char),
+            Self::Int(int) => serializer.serialize_i64(*int),
+            Self::Float(float) => serializer.serialize_f64(*float),
+            Self::Boolean(boolean) => serializer.serialize_bool(*boolean),
+            Self::Block(_) => Err(serde::ser::Error::custom("blocks can't be serialized")),
+            #[cfg(feature = "decimal")]
+            Self::Decimal(decimal) => serializer.serialize_str(&decimal.to_string()),
+        }
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Value {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ValueVisitor;
+        impl serde::de::Visitor<'_> for ValueVisitor {
+            type Value = Value;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a string, char, number or boolean")
+            }
+            fn visit_str<E: serde::de::Error>(self, string: &str) -> Result<Value, E> { Ok(Value::String(string.to_string())) }
+            fn visit_string<E: serde::de::Error>(self, string: String) -> Result<Value, E> { Ok(Value::String(string)) }
+            fn visit_char<E: serde::de::Error>(self, char: char) -> Result<Value, E> { Ok(Value::Char(char)) }
+            fn visit_i64<E: serde::de::Error>(self, int: i64) -> Result<Value, E> { Ok(Value::Int(int)) }
+            fn visit_u64<E: serde::de::Error>(self, int: u64) -> Result<Value, E> {
+                i64::try_from(int).map(Value::Int).map_err(|_| E::custom(format!("{int} is too big for an int")))
+            }
+            fn visit_f64<E: serde::de::Error>(self, float: f64) -> Result<Value, E> { Ok(Value::Float(float)) }
+            fn visit_bool<E: serde::de::Error>(self, boolean: bool) -> Result<Value, E> { Ok(Value::Boolean(boolean)) }
+        }
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
 #[derive(Clone, Copy, Eq)]
 pub enum Type {
     Any,
-    String, Char, Int, Float, Boolean
+    String, Char, Int, Float, Boolean, Block,
+    #[cfg(feature = "decimal")]
+    Decimal
+}
+impl Type {
+    pub fn get(name: &str) -> Option<Self> {
+        match name {
+            "any" => Some(Self::Any),
+            "str" => Some(Self::String),
+            "char" => Some(Self::Char),
+            "int" => Some(Self::Int),
+            "float" => Some(Self::Float),
+            "bool" => Some(Self::Boolean),
+            "block" => Some(Self::Block),
+            #[cfg(feature = "decimal")]
+            "dec" => Some(Self::Decimal),
+            _ => None
+        }
+    }
+}
+/// a macro signature like `[str -- int bool]`, with the outputs only if they're declared
+pub fn signature(types: &[Type], outputs: Option<&[Type]>) -> String {
+    let names = |types: &[Type]| types.iter().map(|typ| typ.to_string()).collect::<Vec<String>>().join(" ");
+    match outputs {
+        Some(outputs) => format!("[{} -- {}]", names(types), names(outputs)),
+        None => format!("[{}]", names(types))
+    }
 }
 impl PartialEq for Type {
     fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Self::Any, _) | (_, Self::Any) => true,
-            (Self::String, Self::String) => true,
-            (Self::Char, Self::Char) => true,
-            (Self::Int, Self::Int) => true,
-            (Self::Float, Self::Float) => true,
-            (Self::Boolean, Self::Boolean) => true,
-            _ => false
-        }
+        matches!((self, other), (Self::Any, _) | (_, Self::Any)) || std::mem::discriminant(self) == std::mem::discriminant(other)
     }
 }
 impl Debug for Type {
@@ -64,6 +169,9 @@ impl Debug for Type {
             Self::Int => write!(f, "int"),
             Self::Float => write!(f, "float"),
             Self::Boolean => write!(f, "bool"),
+            Self::Block => write!(f, "block"),
+            #[cfg(feature = "decimal")]
+            Self::Decimal => write!(f, "dec"),
         }
     }
 }
@@ -82,3 +190,31 @@ impl Hash for Type {
         
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn native_conversions() {
+        assert_eq!(Value::from("a"), Value::String("a".to_string()));
+        assert_eq!(Value::from(3i64), Value::Int(3));
+        assert_eq!(i64::try_from(Value::Int(3)).unwrap(), 3);
+        assert_eq!(String::try_from(Value::from('c')).err().unwrap().msg(), "expected str, got char");
+        assert_eq!(bool::try_from(Value::Float(1.0)).err().unwrap().msg(), "expected bool, got float");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize() {
+        use serde::de::{Deserialize, IntoDeserializer, value};
+        fn de<'de, T: IntoDeserializer<'de, value::Error>>(from: T) -> Result<Value, value::Error> {
+            Value::deserialize(from.into_deserializer())
+        }
+        assert_eq!(de("a").unwrap(), Value::String("a".to_string()));
+        assert_eq!(de(-3i64).unwrap(), Value::Int(-3));
+        assert_eq!(de(true).unwrap(), Value::Boolean(true));
+        let error = de(u64::MAX).err().unwrap();
+        assert_eq!(error.to_string(), format!("{} is too big for an int", u64::MAX));
+    }
+}